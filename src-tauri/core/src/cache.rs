@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::{models::GitLabRelease, storage};
+
+// A single page of the releases API together with its validators
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPage {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub next_url: Option<String>,
+    pub releases: Vec<GitLabRelease>,
+}
+
+// Conditional-request cache keyed by request URL, persisted between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReleaseCache {
+    pages: HashMap<String, CachedPage>,
+    #[serde(skip)]
+    touched: HashSet<String>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl ReleaseCache {
+    pub fn file_path() -> PathBuf {
+        storage::cache_dir().join("releases-cache.json")
+    }
+
+    // Load the cache from `path`, starting empty if there is none
+    pub fn load_from(path: &Path) -> Self {
+        storage::load_json(path).unwrap_or_default()
+    }
+
+    // Write the cache to `path` if anything changed since the last save
+    pub fn save_to(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.dirty {
+            return Ok(());
+        }
+        storage::save_json(path, self)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&mut self, url: &str) -> Option<&CachedPage> {
+        self.touched.insert(url.to_string());
+        self.pages.get(url)
+    }
+
+    pub fn insert(&mut self, url: &str, page: CachedPage) {
+        self.touched.insert(url.to_string());
+        self.pages.insert(url.to_string(), page);
+        self.dirty = true;
+    }
+
+    // Drop pages that were not requested since the last prune (removed projects,
+    // pages that no longer exist) so the cache file does not grow forever
+    pub fn prune_untouched(&mut self) {
+        let touched = std::mem::take(&mut self.touched);
+        let before = self.pages.len();
+        self.pages.retain(|url, _| touched.contains(url));
+        if self.pages.len() != before {
+            self.dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(etag: &str) -> CachedPage {
+        CachedPage {
+            etag: Some(etag.to_string()),
+            last_modified: None,
+            next_url: None,
+            releases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn prunes_only_pages_that_were_not_requested() {
+        let mut cache = ReleaseCache::default();
+        cache.insert("https://x.test/a", page("a"));
+        cache.insert("https://x.test/b", page("b"));
+        cache.insert("https://x.test/c", page("c"));
+        cache.prune_untouched();
+        assert_eq!(cache.pages.len(), 3);
+
+        // A refresh that only requests `a` and `c`
+        assert!(cache.get("https://x.test/a").is_some());
+        cache.insert("https://x.test/c", page("c2"));
+        cache.prune_untouched();
+
+        let mut urls: Vec<_> = cache.pages.keys().cloned().collect();
+        urls.sort();
+        assert_eq!(urls, ["https://x.test/a", "https://x.test/c"]);
+        assert_eq!(cache.pages["https://x.test/c"].etag.as_deref(), Some("c2"));
+
+        // Nothing was requested since the last prune
+        cache.prune_untouched();
+        assert!(cache.pages.is_empty());
+    }
+
+    #[test]
+    fn saves_only_after_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("releases-cache.json");
+        let mut cache = ReleaseCache::default();
+
+        cache.save_to(&path).unwrap();
+        assert!(!path.exists());
+
+        cache.insert("https://x.test/a", page("a"));
+        cache.save_to(&path).unwrap();
+        let mut loaded = ReleaseCache::load_from(&path);
+        assert_eq!(
+            loaded.get("https://x.test/a").unwrap().etag.as_deref(),
+            Some("a")
+        );
+
+        // Saving again without changes leaves the file alone
+        std::fs::remove_file(&path).unwrap();
+        cache.save_to(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
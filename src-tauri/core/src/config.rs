@@ -0,0 +1,1538 @@
+use chrono::NaiveTime;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{
+    digest::{DigestSchedule, EmailSettings, SmtpTls},
+    discovery::{DiscoveryRule, ProjectSource},
+    grouping::TagGrouping,
+    secrets::{apply_stored_tokens, migrate_plaintext_tokens, secret_store},
+    sources::Provider,
+    storage,
+    validation::{parse_email, parse_webhook},
+    version::{LatestBy, ReleaseChannel},
+    webhooks::{WebhookConfig, WebhookKind},
+};
+
+// GitLab caps `per_page` at 100 for every list endpoint
+pub const MAX_PER_PAGE: u32 = 100;
+pub const DEFAULT_PER_PAGE: u32 = 100;
+pub const DEFAULT_MAX_PAGES: u32 = 20;
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+// Kept below the 60 s auto-refresh interval so refreshes never overlap
+pub const DEFAULT_REFRESH_TIMEOUT_SECS: u64 = 50;
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 300;
+pub const MIN_POLL_INTERVAL_SECS: u64 = 10;
+// Group, starred and membership project lists are re-read hourly
+pub const DEFAULT_DISCOVER_INTERVAL_SECS: u64 = 3600;
+// More new releases than this at once are announced in a single notification
+pub const DEFAULT_NOTIFICATION_BATCH_SIZE: usize = 3;
+// Instance built from the single-instance settings (.env or top-level config.toml
+// keys); releases seen before multiple instances were supported belong to it
+pub const DEFAULT_INSTANCE: &str = "default";
+
+// Where the API token of an instance came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenSource {
+    #[default]
+    Missing,
+    // `api_token` in config.toml
+    ConfigFile,
+    // GITLAB_API_TOKEN from the environment or the legacy .env file
+    Environment,
+    SecretStore,
+}
+
+// A server together with the credentials and TLS settings used for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceConfig {
+    pub name: String,
+    pub provider: Provider,
+    pub base_url: String,
+    pub api_token: String,
+    pub token_source: TokenSource,
+    // Additional CA certificate (PEM) to trust, e.g. an internal root CA
+    pub ca_cert: Option<PathBuf>,
+    // Skip certificate verification altogether; only for self-signed test servers
+    pub accept_invalid_certs: bool,
+}
+
+impl InstanceConfig {
+    pub fn new(name: &str, base_url: &str, api_token: &str) -> Self {
+        InstanceConfig {
+            name: name.to_string(),
+            provider: Provider::GitLab,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_token: api_token.to_string(),
+            token_source: TokenSource::Missing,
+            ca_cert: None,
+            accept_invalid_certs: false,
+        }
+    }
+}
+
+// Settings for a single monitored project
+#[derive(Debug, Clone)]
+pub struct ProjectConfig {
+    // Name of the instance the project lives on
+    pub instance: String,
+    pub path: String,
+    pub display_name: Option<String>,
+    pub tag_grouping: TagGrouping,
+    pub latest_by: LatestBy,
+    pub channel: ReleaseChannel,
+    // Whether changes in this project turn the tray icon blue
+    pub notify: bool,
+    // Whether new releases of this project show a desktop notification
+    // (only when `notify` is set too)
+    pub desktop_notify: bool,
+    pub poll_interval_secs: u64,
+    // Command (program and arguments) run for every new release
+    pub on_release: Option<Vec<String>>,
+    // Fetch the pipeline status of the releases' tags
+    pub pipelines: bool,
+    // Watch the latest pipeline on the default branch
+    pub branch_pipeline: bool,
+    // Track what is deployed to the project's environments
+    pub deployments: bool,
+    // Discovery rule entry (e.g. `group:platform/*`) the project was found by;
+    // None for listed projects
+    pub discovered_by: Option<String>,
+}
+
+impl ProjectConfig {
+    // Default settings for a project path on an instance
+    pub fn new(instance: &str, path: &str) -> Self {
+        ProjectConfig {
+            instance: instance.to_string(),
+            path: path.to_string(),
+            display_name: None,
+            tag_grouping: TagGrouping::default(),
+            latest_by: LatestBy::default(),
+            channel: ReleaseChannel::default(),
+            notify: true,
+            desktop_notify: true,
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            on_release: None,
+            pipelines: false,
+            branch_pipeline: false,
+            deployments: false,
+            discovered_by: None,
+        }
+    }
+
+    // Name shown in the UI: the configured display name or the last path segment
+    pub fn display_name(&self) -> String {
+        self.display_name.clone().unwrap_or_else(|| {
+            self.path
+                .split('/')
+                .next_back()
+                .unwrap_or(&self.path)
+                .to_string()
+        })
+    }
+}
+
+// Daily time range without desktop notifications, e.g. `22:00-07:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // Spans midnight
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid quiet hours '{}' (expected HH:MM-HH:MM)", s.trim());
+        let (start, end) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+        Ok(QuietHours {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl Serialize for QuietHours {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for QuietHours {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// Desktop notifications for new releases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub quiet_hours: Option<QuietHours>,
+    // Up to this many new releases get a notification each, more are summarised
+    pub batch_size: usize,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            enabled: true,
+            quiet_hours: None,
+            batch_size: DEFAULT_NOTIFICATION_BATCH_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GitLabConfig {
+    pub instances: Vec<InstanceConfig>,
+    // Projects of all instances, including those found by `discovery`
+    pub projects: Vec<ProjectConfig>,
+    // Group, starred and membership entries of the project lists
+    pub discovery: Vec<DiscoveryRule>,
+    pub discover_interval_secs: u64,
+    // Pagination settings for list endpoints
+    pub per_page: u32,
+    pub max_pages: u32,
+    pub keyset_pagination: bool,
+    // Concurrency and timeouts for a refresh
+    pub max_concurrency: usize,
+    pub request_timeout_secs: u64,
+    pub refresh_timeout_secs: u64,
+    // Retries for rate-limited (429) and failed (5xx) requests
+    pub max_retries: u32,
+    // Default poll interval; projects may poll less often
+    pub poll_interval_secs: u64,
+    pub notifications: NotificationSettings,
+    // Chat and HTTP endpoints told about release changes
+    pub webhooks: Vec<WebhookConfig>,
+    // Scheduled email digest of new releases
+    pub email: Option<EmailSettings>,
+    // Seconds an `on_release` hook may run before it is killed
+    pub hook_timeout_secs: u64,
+}
+
+impl GitLabConfig {
+    // Config used when loading fails so the app can still start
+    pub fn empty() -> Self {
+        GitLabConfig {
+            instances: Vec::new(),
+            projects: Vec::new(),
+            discovery: Vec::new(),
+            discover_interval_secs: DEFAULT_DISCOVER_INTERVAL_SECS,
+            per_page: DEFAULT_PER_PAGE,
+            max_pages: DEFAULT_MAX_PAGES,
+            keyset_pagination: true,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            refresh_timeout_secs: DEFAULT_REFRESH_TIMEOUT_SECS,
+            max_retries: DEFAULT_MAX_RETRIES,
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            notifications: NotificationSettings::default(),
+            webhooks: Vec::new(),
+            email: None,
+            hook_timeout_secs: DEFAULT_HOOK_TIMEOUT_SECS,
+        }
+    }
+}
+
+impl GitLabConfig {
+    pub fn instance(&self, name: &str) -> Option<&InstanceConfig> {
+        self.instances.iter().find(|i| i.name == name)
+    }
+
+    pub fn project(&self, instance: &str, project_path: &str) -> Option<&ProjectConfig> {
+        self.projects
+            .iter()
+            .find(|p| p.instance == instance && p.path == project_path)
+    }
+
+    // Settings for a project, falling back to the defaults for unknown projects
+    pub fn project_settings(&self, instance: &str, project_path: &str) -> ProjectConfig {
+        self.project(instance, project_path)
+            .cloned()
+            .unwrap_or_else(|| ProjectConfig::new(instance, project_path))
+    }
+
+    // Why monitoring cannot start with this configuration, if it cannot
+    pub fn incomplete_reason(&self) -> Option<String> {
+        if self.projects.is_empty() && self.discovery.is_empty() {
+            return Some("no projects are configured".to_string());
+        }
+        // GitHub and Gitea serve public repositories without a token
+        self.instances
+            .iter()
+            .find(|i| i.provider == Provider::GitLab && i.api_token.is_empty())
+            .map(|i| format!("no API token is set for instance '{}'", i.name))
+    }
+
+    // How often the background poller has to wake up to serve every project
+    pub fn tick_interval(&self) -> Duration {
+        let secs = self
+            .projects
+            .iter()
+            .chain(self.discovery.iter().map(|r| &r.settings))
+            .map(|p| p.poll_interval_secs)
+            .min()
+            .unwrap_or(self.poll_interval_secs);
+        Duration::from_secs(secs.max(MIN_POLL_INTERVAL_SECS))
+    }
+}
+
+// Read an optional environment variable, falling back to `default` when unset
+fn env_or<T: FromStr>(env: &EnvFile, name: &str, default: T) -> Result<T, String> {
+    match env_var(env, name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: '{}'", name, value)),
+        _ => Ok(default),
+    }
+}
+
+// Comma-separated list from an optional environment variable
+fn env_list(env: &EnvFile, name: &str) -> Vec<String> {
+    env_var(env, name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Per-project setting read from a single environment variable.
+// The value is a `;`-separated list of entries, each either `<project>=<value>`
+// or a bare `<value>` that applies to every project without its own entry, e.g.
+// `GITLAB_TAG_GROUPING=none;group/api=regex:^(?P<family>.+)_v\d`.
+struct PerProjectSetting<T> {
+    default: Option<T>,
+    overrides: HashMap<String, T>,
+}
+
+impl<T: Clone> PerProjectSetting<T> {
+    fn get(&self, project_path: &str) -> Option<T> {
+        self.overrides
+            .get(project_path)
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+fn env_per_project<T>(env: &EnvFile, name: &str) -> Result<PerProjectSetting<T>, String>
+where
+    T: FromStr<Err = String>,
+{
+    let mut setting = PerProjectSetting {
+        default: None,
+        overrides: HashMap::new(),
+    };
+    let value = match env_var(env, name) {
+        Ok(value) => value,
+        Err(_) => return Ok(setting),
+    };
+
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        // A bare value applies to all projects
+        if let Ok(parsed) = entry.parse::<T>() {
+            setting.default = Some(parsed);
+            continue;
+        }
+        let (project, spec) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid entry in {}: '{}'", name, entry))?;
+        let parsed = spec
+            .parse::<T>()
+            .map_err(|e| format!("Invalid entry in {} for {}: {}", name, project.trim(), e))?;
+        setting.overrides.insert(project.trim().to_string(), parsed);
+    }
+
+    Ok(setting)
+}
+
+// Get the directory where the executable is located
+fn get_executable_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let exe_path = std::env::current_exe()?;
+    let exe_dir = exe_path
+        .parent()
+        .ok_or("Failed to get executable directory")?;
+    Ok(exe_dir.to_path_buf())
+}
+
+// Ensure .env file exists in the executable directory, provide helpful guidance if not
+fn ensure_env_file_exists() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let exe_dir = get_executable_dir()?;
+    let env_file_path = exe_dir.join(".env");
+    let template_path = exe_dir.join("gitlab-config.example");
+
+    // Check if .env exists
+    if !env_file_path.exists() {
+        if template_path.exists() {
+            eprintln!("Configuration needed: Please copy 'gitlab-config.example' to '.env' and edit it with your GitLab settings.");
+            eprintln!("Template found at: {}", template_path.display());
+            eprintln!("Create .env file at: {}", env_file_path.display());
+        } else {
+            eprintln!("Configuration template not found. Please ensure 'gitlab-config.example' exists in the installation directory.");
+        }
+    }
+
+    Ok(env_file_path)
+}
+
+// Location of the TOML configuration file
+// (~/.config/gitlab-monitor/config.toml, %APPDATA%\gitlab-monitor\config.toml, ...)
+pub fn config_file_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("gitlab-monitor")
+        .join("config.toml")
+}
+
+// Files whose modification makes the running app reload its configuration
+pub fn watched_config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![config_file_path()];
+    if let Ok(exe_dir) = get_executable_dir() {
+        paths.push(exe_dir.join(".env"));
+    }
+    paths
+}
+
+// Load configuration from the TOML config file, falling back to the legacy
+// .env file next to the executable when there is none. Instances without a
+// token in either get theirs from the secret store. Loading never modifies
+// config.toml: plaintext tokens are only moved by `move_config_tokens`.
+pub fn load_config() -> Result<GitLabConfig, Box<dyn std::error::Error>> {
+    let config_path = config_file_path();
+    let mut config = if config_path.exists() {
+        load_toml_config(&config_path)?
+    } else {
+        load_env_config(&load_dotenv()?)?
+    };
+    apply_stored_tokens(&mut config, secret_store());
+    Ok(config)
+}
+
+// Move the plaintext tokens of config.toml into the secret store, at the
+// user's request. Returns the instances whose token was moved.
+pub fn move_config_tokens() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let config_path = config_file_path();
+    if !config_path.exists() {
+        return Ok(Vec::new());
+    }
+    let moved = migrate_plaintext_tokens(&config_path, secret_store())?;
+    for instance in &moved {
+        eprintln!(
+            "Moved the API token of {} from {} to the {}",
+            instance,
+            config_path.display(),
+            secret_store().name()
+        );
+    }
+    Ok(moved)
+}
+
+// Variables of a .env file, read with `env_var` instead of being copied into
+// the process environment: a reload must not modify the environment while
+// other threads may be reading it
+type EnvFile = HashMap<String, String>;
+
+fn read_env_file(path: &Path) -> Result<EnvFile, dotenv::Error> {
+    // The only public way to read the file without touching the environment
+    #[allow(deprecated)]
+    dotenv::from_path_iter(path)?.collect()
+}
+
+// The .env file next to the executable, or else the first .env found from the
+// current directory up
+fn load_dotenv() -> Result<EnvFile, Box<dyn std::error::Error>> {
+    let env_file_path = ensure_env_file_exists()?;
+    if env_file_path.exists() {
+        return Ok(match read_env_file(&env_file_path) {
+            Ok(vars) => {
+                eprintln!("Loaded configuration from: {}", env_file_path.display());
+                vars
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to load .env file: {}", e);
+                EnvFile::new()
+            }
+        });
+    }
+    #[allow(deprecated)]
+    let vars = dotenv::from_filename_iter(".env")
+        .ok()
+        .and_then(|iter| iter.collect::<Result<_, _>>().ok())
+        .unwrap_or_default();
+    Ok(vars)
+}
+
+// A variable from the .env file, falling back to the process environment
+fn env_var(env: &EnvFile, name: &str) -> Result<String, std::env::VarError> {
+    match env.get(name) {
+        Some(value) => Ok(value.clone()),
+        None => std::env::var(name),
+    }
+}
+
+// Load configuration from environment variables and the .env file
+// This was previously inline in lib.rs; moved here for clarity.
+fn load_env_config(env: &EnvFile) -> Result<GitLabConfig, Box<dyn std::error::Error>> {
+    let projects_str = env_var(env, "GITLAB_PROJECTS")
+        .map_err(|_| {
+            let exe_dir = get_executable_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let template_path = exe_dir.join("gitlab-config.example");
+            let env_file_path = exe_dir.join(".env");
+
+            if template_path.exists() {
+                format!(
+                    "GITLAB_PROJECTS environment variable not set.\n\
+                    \n\
+                    SETUP REQUIRED:\n\
+                    1. Copy 'gitlab-config.example' to '.env' in the installation directory\n\
+                    2. Replace <your-base-url> with your GitLab URL (or remove for gitlab.com)\n\
+                    3. Replace <your-projects> with comma-separated project paths (e.g., group/project1,group/project2)\n\
+                    4. Store your GitLab API token with the key button in the app window\n\
+                    \n\
+                    Files location:\n\
+                    Template: {}\n\
+                    Create: {}\n\
+                    \n\
+                    Example .env content:\n\
+                    GITLAB_BASE_URL=https://gitlab.com\n\
+                    GITLAB_PROJECTS=mygroup/project1,mygroup/project2\n\
+                    \n\
+                    Alternatively create a TOML configuration at: {}",
+                    template_path.display(),
+                    env_file_path.display(),
+                    config_file_path().display()
+                )
+            } else {
+                "GITLAB_PROJECTS environment variable not set. Please create a .env file with your GitLab configuration.".to_string()
+            }
+        })?;
+
+    // A token in the environment (or .env) overrides the one in the secret store
+    let (api_token, token_source) = match env_var(env, "GITLAB_API_TOKEN") {
+        Ok(token) if !token.trim().is_empty() => {
+            eprintln!("Using GITLAB_API_TOKEN from the environment (legacy); store the token from the app to keep it out of plaintext files");
+            (token, TokenSource::Environment)
+        }
+        _ => (String::new(), TokenSource::Missing),
+    };
+
+    let base_url =
+        env_var(env, "GITLAB_BASE_URL").unwrap_or_else(|_| "https://gitlab.com".to_string());
+    let instance = InstanceConfig {
+        ca_cert: env_var(env, "GITLAB_CA_CERT")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from),
+        accept_invalid_certs: env_or(env, "GITLAB_ACCEPT_INVALID_CERTS", false)?,
+        token_source,
+        ..InstanceConfig::new(DEFAULT_INSTANCE, &base_url, &api_token)
+    };
+
+    let poll_interval_secs = env_or(env, "GITLAB_POLL_INTERVAL", DEFAULT_POLL_INTERVAL_SECS)?;
+    if poll_interval_secs < MIN_POLL_INTERVAL_SECS {
+        return Err(format!(
+            "GITLAB_POLL_INTERVAL must be at least {} seconds",
+            MIN_POLL_INTERVAL_SECS
+        )
+        .into());
+    }
+
+    let tag_grouping = env_per_project::<TagGrouping>(env, "GITLAB_TAG_GROUPING")?;
+    let latest_by = env_per_project::<LatestBy>(env, "GITLAB_LATEST_BY")?;
+    let channel = env_per_project::<ReleaseChannel>(env, "GITLAB_CHANNEL")?;
+
+    // Patterns that filter the projects of group, starred and member entries
+    let include = env_list(env, "GITLAB_DISCOVER_INCLUDE");
+    let exclude = env_list(env, "GITLAB_DISCOVER_EXCLUDE");
+    let discover_interval_secs = env_or(
+        env,
+        "GITLAB_DISCOVER_INTERVAL",
+        DEFAULT_DISCOVER_INTERVAL_SECS,
+    )?;
+    if discover_interval_secs < MIN_POLL_INTERVAL_SECS {
+        return Err(format!(
+            "GITLAB_DISCOVER_INTERVAL must be at least {} seconds",
+            MIN_POLL_INTERVAL_SECS
+        )
+        .into());
+    }
+
+    let mut projects = Vec::new();
+    let mut discovery = Vec::new();
+    for path in projects_str
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+    {
+        let project = ProjectConfig {
+            tag_grouping: tag_grouping.get(path).unwrap_or_default(),
+            latest_by: latest_by.get(path).unwrap_or_default(),
+            channel: channel.get(path).unwrap_or_default(),
+            poll_interval_secs,
+            ..ProjectConfig::new(DEFAULT_INSTANCE, path)
+        };
+        if ProjectSource::is_rule(path) {
+            discovery.push(
+                DiscoveryRule::new(project, include.clone(), exclude.clone())
+                    .map_err(|e| format!("Invalid entry in GITLAB_PROJECTS: {}", e))?,
+            );
+        } else {
+            projects.push(project);
+        }
+    }
+
+    if projects.is_empty() && discovery.is_empty() {
+        return Err("No projects specified in GITLAB_PROJECTS".into());
+    }
+
+    let per_page = env_or(env, "GITLAB_PER_PAGE", DEFAULT_PER_PAGE)?;
+    if per_page == 0 || per_page > MAX_PER_PAGE {
+        return Err(format!("GITLAB_PER_PAGE must be between 1 and {}", MAX_PER_PAGE).into());
+    }
+
+    let max_pages = env_or(env, "GITLAB_MAX_PAGES", DEFAULT_MAX_PAGES)?;
+    if max_pages == 0 {
+        return Err("GITLAB_MAX_PAGES must be at least 1".into());
+    }
+
+    let keyset_pagination = env_or(env, "GITLAB_KEYSET_PAGINATION", true)?;
+
+    let max_concurrency = env_or(env, "GITLAB_MAX_CONCURRENCY", DEFAULT_MAX_CONCURRENCY)?;
+    if max_concurrency == 0 {
+        return Err("GITLAB_MAX_CONCURRENCY must be at least 1".into());
+    }
+
+    let request_timeout_secs = env_or(env, "GITLAB_REQUEST_TIMEOUT", DEFAULT_REQUEST_TIMEOUT_SECS)?;
+    let refresh_timeout_secs = env_or(env, "GITLAB_REFRESH_TIMEOUT", DEFAULT_REFRESH_TIMEOUT_SECS)?;
+    if request_timeout_secs == 0 || refresh_timeout_secs == 0 {
+        return Err(
+            "GITLAB_REQUEST_TIMEOUT and GITLAB_REFRESH_TIMEOUT must be at least 1 second".into(),
+        );
+    }
+
+    let max_retries = env_or(env, "GITLAB_MAX_RETRIES", DEFAULT_MAX_RETRIES)?;
+
+    let notifications = NotificationSettings {
+        enabled: env_or(env, "GITLAB_NOTIFICATIONS", true)?,
+        quiet_hours: match env_var(env, "GITLAB_QUIET_HOURS") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                value
+                    .parse()
+                    .map_err(|e| format!("Invalid GITLAB_QUIET_HOURS: {}", e))?,
+            ),
+            _ => None,
+        },
+        batch_size: env_or(
+            env,
+            "GITLAB_NOTIFICATION_BATCH",
+            DEFAULT_NOTIFICATION_BATCH_SIZE,
+        )?,
+    };
+
+    // `<kind>=<url>` entries; Matrix rooms need config.toml for their credentials
+    let mut webhooks = Vec::new();
+    for (index, entry) in env_list(env, "GITLAB_WEBHOOKS").iter().enumerate() {
+        let (kind, url) = entry.split_once('=').ok_or_else(|| {
+            format!(
+                "Invalid entry in GITLAB_WEBHOOKS: '{}' (expected <kind>=<url>)",
+                entry
+            )
+        })?;
+        let webhook = WebhookFile {
+            kind: kind
+                .parse()
+                .map_err(|e| format!("Invalid entry in GITLAB_WEBHOOKS: {}", e))?,
+            ..WebhookFile::new(url)
+        };
+        webhooks.push(parse_webhook(
+            &format!("GITLAB_WEBHOOKS[{}]", index),
+            webhook,
+        )?);
+    }
+
+    // The digest is sent once an SMTP server is set
+    let email = match env_var(env, "GITLAB_SMTP_HOST") {
+        Ok(host) if !host.trim().is_empty() => {
+            let email = EmailFile {
+                smtp_host: host,
+                smtp_port: env_var(env, "GITLAB_SMTP_PORT")
+                    .ok()
+                    .map(|port| port.trim().parse())
+                    .transpose()
+                    .map_err(|_| "Invalid value for GITLAB_SMTP_PORT")?,
+                tls: env_var(env, "GITLAB_SMTP_TLS")
+                    .ok()
+                    .map(|tls| tls.parse())
+                    .transpose()
+                    .map_err(|e| format!("Invalid GITLAB_SMTP_TLS: {}", e))?,
+                username: env_var(env, "GITLAB_SMTP_USERNAME").ok(),
+                password: env_var(env, "GITLAB_SMTP_PASSWORD").ok(),
+                from: env_var(env, "GITLAB_DIGEST_FROM").unwrap_or_default(),
+                to: env_list(env, "GITLAB_DIGEST_TO"),
+                schedule: env_var(env, "GITLAB_DIGEST_SCHEDULE")
+                    .ok()
+                    .map(|schedule| schedule.parse())
+                    .transpose()
+                    .map_err(|e| format!("Invalid GITLAB_DIGEST_SCHEDULE: {}", e))?,
+            };
+            Some(parse_email("GITLAB_SMTP_HOST", email)?)
+        }
+        _ => None,
+    };
+
+    Ok(GitLabConfig {
+        instances: vec![instance],
+        projects,
+        discovery,
+        discover_interval_secs,
+        per_page,
+        max_pages,
+        keyset_pagination,
+        max_concurrency,
+        request_timeout_secs,
+        refresh_timeout_secs,
+        max_retries,
+        poll_interval_secs,
+        notifications,
+        webhooks,
+        email,
+        hook_timeout_secs: DEFAULT_HOOK_TIMEOUT_SECS,
+    })
+}
+
+// Per-project settings shared by `[defaults]` and `[[projects]]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectDefaultsFile {
+    pub tag_grouping: Option<TagGrouping>,
+    pub latest_by: Option<LatestBy>,
+    pub channel: Option<ReleaseChannel>,
+    pub notify: Option<bool>,
+    pub desktop_notify: Option<bool>,
+    pub poll_interval: Option<u64>,
+    pub on_release: Option<Vec<String>>,
+    pub pipelines: Option<bool>,
+    pub branch_pipeline: Option<bool>,
+    pub deployments: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectFile {
+    pub path: String,
+    // Only needed for top-level projects that are not on the default instance's
+    // provider: they are watched on the instance named after their provider
+    pub provider: Option<Provider>,
+    pub display_name: Option<String>,
+    pub tag_grouping: Option<TagGrouping>,
+    pub latest_by: Option<LatestBy>,
+    pub channel: Option<ReleaseChannel>,
+    pub notify: Option<bool>,
+    pub desktop_notify: Option<bool>,
+    pub poll_interval: Option<u64>,
+    pub on_release: Option<Vec<String>>,
+    pub pipelines: Option<bool>,
+    pub branch_pipeline: Option<bool>,
+    pub deployments: Option<bool>,
+    // Project path patterns for group, starred and member entries
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+}
+
+impl ProjectFile {
+    // A project that uses the `[defaults]` for everything
+    pub fn new(path: &str) -> Self {
+        ProjectFile {
+            path: path.to_string(),
+            provider: None,
+            display_name: None,
+            tag_grouping: None,
+            latest_by: None,
+            channel: None,
+            notify: None,
+            desktop_notify: None,
+            poll_interval: None,
+            on_release: None,
+            pipelines: None,
+            branch_pipeline: None,
+            deployments: None,
+            include: None,
+            exclude: None,
+        }
+    }
+}
+
+// The `[notifications]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsFile {
+    pub enabled: Option<bool>,
+    pub quiet_hours: Option<QuietHours>,
+    pub batch_size: Option<usize>,
+}
+
+// A `[[webhooks]]` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookFile {
+    pub name: Option<String>,
+    pub kind: WebhookKind,
+    pub url: String,
+    pub room_id: Option<String>,
+    pub access_token: Option<String>,
+}
+
+impl WebhookFile {
+    // A generic JSON webhook
+    pub fn new(url: &str) -> Self {
+        WebhookFile {
+            name: None,
+            kind: WebhookKind::Json,
+            url: url.trim().to_string(),
+            room_id: None,
+            access_token: None,
+        }
+    }
+}
+
+// The `[email]` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailFile {
+    pub smtp_host: String,
+    pub smtp_port: Option<u16>,
+    pub tls: Option<SmtpTls>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub schedule: Option<DigestSchedule>,
+}
+
+// A `[[instances]]` entry with its own `[[instances.projects]]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceFile {
+    pub name: String,
+    pub provider: Option<Provider>,
+    pub base_url: Option<String>,
+    pub api_token: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub accept_invalid_certs: Option<bool>,
+    #[serde(default)]
+    pub projects: Vec<ProjectFile>,
+}
+
+// Layout of config.toml, see gitlab-config.example.toml. The top-level
+// connection keys and `[[projects]]` describe the "default" instance.
+// This is also the settings model the settings UI edits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub api_token: Option<String>,
+    pub provider: Option<Provider>,
+    pub base_url: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub accept_invalid_certs: Option<bool>,
+    pub poll_interval: Option<u64>,
+    pub discover_interval: Option<u64>,
+    pub per_page: Option<u32>,
+    pub max_pages: Option<u32>,
+    pub keyset_pagination: Option<bool>,
+    pub max_concurrency: Option<usize>,
+    pub request_timeout: Option<u64>,
+    pub refresh_timeout: Option<u64>,
+    pub max_retries: Option<u32>,
+    pub hook_timeout: Option<u64>,
+    #[serde(default)]
+    pub notifications: NotificationsFile,
+    #[serde(default)]
+    pub defaults: ProjectDefaultsFile,
+    #[serde(default)]
+    pub projects: Vec<ProjectFile>,
+    #[serde(default)]
+    pub instances: Vec<InstanceFile>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookFile>,
+    pub email: Option<EmailFile>,
+}
+
+impl ConfigFile {
+    // Current settings in file layout: config.toml when it exists, otherwise the
+    // legacy .env configuration converted to it
+    pub fn load() -> Result<ConfigFile, Box<dyn std::error::Error>> {
+        let path = config_file_path();
+        if path.exists() {
+            return read_config_file(&path);
+        }
+        Ok(ConfigFile::from_config(&load_env_config(&load_dotenv()?)?))
+    }
+
+    // Validate and write to config.toml
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(&config_file_path())
+    }
+
+    // Validate and write to `path`. Only the entries that changed are rewritten,
+    // so the comments and the order of the rest of the file are kept.
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.clone()
+            .into_config()
+            .map_err(|e| format!("Invalid settings: {}", e))?;
+        let updated: toml_edit::DocumentMut = toml::to_string_pretty(self)?.parse()?;
+        let mut document: toml_edit::DocumentMut = match std::fs::read_to_string(path) {
+            Ok(contents) => contents.parse()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+            Err(e) => return Err(e.into()),
+        };
+        merge_table(document.as_table_mut(), updated.as_table());
+        // May contain API tokens, so keep it private like the secrets file
+        storage::save_private(path, document.to_string().as_bytes()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // File layout of a runtime configuration; API tokens are never written out
+    pub fn from_config(config: &GitLabConfig) -> ConfigFile {
+        let to_file = |p: &ProjectConfig| ProjectFile {
+            display_name: p.display_name.clone(),
+            tag_grouping: Some(p.tag_grouping.clone())
+                .filter(|g| g.to_string() != TagGrouping::default().to_string()),
+            latest_by: Some(p.latest_by).filter(|l| *l != LatestBy::default()),
+            channel: Some(p.channel).filter(|c| *c != ReleaseChannel::default()),
+            notify: Some(p.notify).filter(|n| !n),
+            desktop_notify: Some(p.desktop_notify).filter(|n| !n),
+            poll_interval: Some(p.poll_interval_secs).filter(|i| *i != config.poll_interval_secs),
+            on_release: p.on_release.clone(),
+            pipelines: Some(p.pipelines).filter(|p| *p),
+            branch_pipeline: Some(p.branch_pipeline).filter(|b| *b),
+            deployments: Some(p.deployments).filter(|d| *d),
+            ..ProjectFile::new(&p.path)
+        };
+        // Listed projects, then the discovery rules in place of what they found
+        let projects_of = |instance: &str| -> Vec<ProjectFile> {
+            let listed = config
+                .projects
+                .iter()
+                .filter(|p| p.instance == instance && p.discovered_by.is_none())
+                .map(to_file);
+            let rules = config
+                .discovery
+                .iter()
+                .filter(|r| r.settings.instance == instance)
+                .map(|r| ProjectFile {
+                    include: Some(r.include.clone()).filter(|i| !i.is_empty()),
+                    exclude: Some(r.exclude.clone()).filter(|e| !e.is_empty()),
+                    ..to_file(&r.settings)
+                });
+            listed.chain(rules).collect()
+        };
+
+        let mut file = ConfigFile {
+            poll_interval: Some(config.poll_interval_secs),
+            discover_interval: Some(config.discover_interval_secs)
+                .filter(|i| *i != DEFAULT_DISCOVER_INTERVAL_SECS),
+            per_page: Some(config.per_page),
+            max_pages: Some(config.max_pages),
+            keyset_pagination: Some(config.keyset_pagination),
+            max_concurrency: Some(config.max_concurrency),
+            request_timeout: Some(config.request_timeout_secs),
+            refresh_timeout: Some(config.refresh_timeout_secs),
+            max_retries: Some(config.max_retries),
+            hook_timeout: Some(config.hook_timeout_secs)
+                .filter(|t| *t != DEFAULT_HOOK_TIMEOUT_SECS),
+            notifications: NotificationsFile {
+                enabled: Some(config.notifications.enabled).filter(|e| !e),
+                quiet_hours: config.notifications.quiet_hours,
+                batch_size: Some(config.notifications.batch_size)
+                    .filter(|b| *b != DEFAULT_NOTIFICATION_BATCH_SIZE),
+            },
+            webhooks: config
+                .webhooks
+                .iter()
+                .map(|w| WebhookFile {
+                    name: Some(w.name.clone()),
+                    kind: w.kind,
+                    url: w.url.clone(),
+                    room_id: w.room_id.clone(),
+                    access_token: w.access_token.clone(),
+                })
+                .collect(),
+            email: config.email.as_ref().map(|e| EmailFile {
+                smtp_host: e.smtp_host.clone(),
+                smtp_port: Some(e.smtp_port).filter(|p| *p != e.tls.default_port()),
+                tls: Some(e.tls),
+                username: e.username.clone(),
+                password: None,
+                from: e.from.to_string(),
+                to: e.to.iter().map(|to| to.to_string()).collect(),
+                schedule: Some(e.schedule),
+            }),
+            ..ConfigFile::default()
+        };
+        for instance in &config.instances {
+            let provider = Some(instance.provider).filter(|p| *p != Provider::GitLab);
+            if instance.name == DEFAULT_INSTANCE {
+                file.provider = provider;
+                file.base_url = Some(instance.base_url.clone());
+                file.ca_cert = instance.ca_cert.clone();
+                file.accept_invalid_certs = Some(instance.accept_invalid_certs).filter(|a| *a);
+                file.projects = projects_of(&instance.name);
+            } else {
+                file.instances.push(InstanceFile {
+                    name: instance.name.clone(),
+                    provider,
+                    base_url: Some(instance.base_url.clone()),
+                    api_token: None,
+                    ca_cert: instance.ca_cert.clone(),
+                    accept_invalid_certs: Some(instance.accept_invalid_certs).filter(|a| *a),
+                    projects: projects_of(&instance.name),
+                });
+            }
+        }
+        file
+    }
+
+    // Copy without API tokens, Matrix access tokens and the SMTP password, for
+    // showing in the UI
+    pub fn without_tokens(mut self) -> Self {
+        self.api_token = None;
+        for instance in &mut self.instances {
+            instance.api_token = None;
+        }
+        for webhook in &mut self.webhooks {
+            webhook.access_token = None;
+        }
+        if let Some(email) = &mut self.email {
+            email.password = None;
+        }
+        self
+    }
+
+    // Take over the tokens of `current` (the UI never sees them). A token only
+    // stays with its server: instances whose `base_url` changed lose it, and
+    // their names are returned so the stored token can be removed as well.
+    pub fn keep_tokens_from(&mut self, current: &ConfigFile) -> Vec<String> {
+        let mut moved = Vec::new();
+        if same_url(&self.base_url, &current.base_url) {
+            self.api_token = current.api_token.clone();
+        } else {
+            self.api_token = None;
+            moved.push(DEFAULT_INSTANCE.to_string());
+        }
+        for instance in &mut self.instances {
+            let previous = current.instances.iter().find(|i| i.name == instance.name);
+            instance.api_token = match previous {
+                Some(previous) if same_url(&instance.base_url, &previous.base_url) => {
+                    previous.api_token.clone()
+                }
+                Some(_) => {
+                    moved.push(instance.name.clone());
+                    None
+                }
+                None => None,
+            };
+        }
+        for webhook in &mut self.webhooks {
+            webhook.access_token = current
+                .webhooks
+                .iter()
+                .find(|w| w.url == webhook.url && w.room_id == webhook.room_id)
+                .and_then(|w| w.access_token.clone());
+        }
+        if let Some(email) = &mut self.email {
+            email.password = current
+                .email
+                .as_ref()
+                .filter(|e| e.smtp_host.trim() == email.smtp_host.trim())
+                .and_then(|e| e.password.clone());
+        }
+        moved
+    }
+
+    // Take over the `on_release` hooks and their timeout of `current`. Hooks run
+    // programs, so they are only set by editing the file, never from the UI.
+    pub fn keep_hooks_from(&mut self, current: &ConfigFile) {
+        self.hook_timeout = current.hook_timeout;
+        self.defaults.on_release = current.defaults.on_release.clone();
+        keep_project_hooks(&mut self.projects, &current.projects);
+        for instance in &mut self.instances {
+            let current_projects = current
+                .instances
+                .iter()
+                .find(|i| i.name == instance.name)
+                .map(|i| i.projects.as_slice())
+                .unwrap_or_default();
+            keep_project_hooks(&mut instance.projects, current_projects);
+        }
+    }
+
+    // Projects of an instance; those of the default instance are at the top level
+    fn instance_projects_mut(&mut self, instance: &str) -> Option<&mut Vec<ProjectFile>> {
+        if instance == DEFAULT_INSTANCE {
+            return Some(&mut self.projects);
+        }
+        self.instances
+            .iter_mut()
+            .find(|i| i.name == instance)
+            .map(|i| &mut i.projects)
+    }
+
+    pub fn add_project(&mut self, instance: &str, path: &str) -> Result<(), String> {
+        let path = path.trim().trim_matches('/');
+        if path.is_empty() {
+            return Err("The project path must not be empty".to_string());
+        }
+        let projects = self
+            .instance_projects_mut(instance)
+            .ok_or_else(|| format!("Unknown instance '{}'", instance))?;
+        if projects
+            .iter()
+            .any(|p| p.path.trim().trim_matches('/') == path)
+        {
+            return Err(format!("Project '{}' is already monitored", path));
+        }
+        projects.push(ProjectFile::new(path));
+        Ok(())
+    }
+
+    pub fn remove_project(&mut self, instance: &str, path: &str) -> Result<(), String> {
+        let path = path.trim().trim_matches('/');
+        let projects = self
+            .instance_projects_mut(instance)
+            .ok_or_else(|| format!("Unknown instance '{}'", instance))?;
+        let before = projects.len();
+        projects.retain(|p| p.path.trim().trim_matches('/') != path);
+        if projects.len() == before {
+            return Err(format!(
+                "Project '{}' is not monitored on {}",
+                path, instance
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Give each project the hook it has in `current`; projects that are not in
+// `current` get none
+// Whether two `base_url` settings point at the same server
+fn same_url(a: &Option<String>, b: &Option<String>) -> bool {
+    let normalize = |url: &Option<String>| {
+        url.as_deref()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+    };
+    normalize(a) == normalize(b)
+}
+
+fn keep_project_hooks(projects: &mut [ProjectFile], current: &[ProjectFile]) {
+    for project in projects {
+        project.on_release = current
+            .iter()
+            .find(|p| p.path == project.path)
+            .and_then(|p| p.on_release.clone());
+    }
+}
+
+// Parse the TOML configuration file without validating its values
+fn read_config_file(path: &Path) -> Result<ConfigFile, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid configuration in {}:\n{}", path.display(), e))?;
+    Ok(file)
+}
+
+// Make `table` hold the entries of `updated`. Entries that did not change, and
+// the comments around the ones that did, are left as they are.
+fn merge_table(table: &mut toml_edit::Table, updated: &toml_edit::Table) {
+    let removed: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !updated.contains_key(key))
+        .collect();
+    for key in removed {
+        table.remove(&key);
+    }
+    for (key, item) in updated.iter() {
+        match table.get_mut(key) {
+            Some(current) => merge_item(current, item),
+            // Sections left at their defaults are not added
+            None if item.as_table().is_some_and(|t| t.is_empty()) => {}
+            None => {
+                table.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn merge_item(item: &mut toml_edit::Item, updated: &toml_edit::Item) {
+    if let (Some(table), Some(updated)) = (item.as_table_mut(), updated.as_table()) {
+        return merge_table(table, updated);
+    }
+    if let (Some(array), Some(updated)) =
+        (item.as_array_of_tables_mut(), updated.as_array_of_tables())
+    {
+        return merge_array(array, updated);
+    }
+    if plain_value(item) == plain_value(updated) {
+        return;
+    }
+    // Keep a comment at the end of the line
+    let decor = item.as_value().map(|value| value.decor().clone());
+    *item = updated.clone();
+    if let (Some(decor), Some(value)) = (decor, item.as_value_mut()) {
+        *value.decor_mut() = decor;
+    }
+}
+
+// `[[projects]]`, `[[instances]]` and `[[webhooks]]` entries are matched by
+// their path or name, so removing one entry keeps the comments of the others
+fn merge_array(array: &mut toml_edit::ArrayOfTables, updated: &toml_edit::ArrayOfTables) {
+    let id = |table: &toml_edit::Table| {
+        table
+            .get("path")
+            .or_else(|| table.get("name"))
+            .and_then(|id| id.as_str())
+            .map(str::to_string)
+    };
+    let mut current: Vec<toml_edit::Table> = array.iter().cloned().collect();
+    let mut merged = toml_edit::ArrayOfTables::new();
+    for table in updated.iter() {
+        let found = id(table).and_then(|wanted| {
+            current
+                .iter()
+                .position(|t| id(t).as_deref() == Some(wanted.as_str()))
+        });
+        match found {
+            Some(index) => {
+                let mut entry = current.remove(index);
+                merge_table(&mut entry, table);
+                merged.push(entry);
+            }
+            None => merged.push(table.clone()),
+        }
+    }
+    *array = merged;
+}
+
+// The value of an entry without its formatting, for comparisons
+fn plain_value(item: &toml_edit::Item) -> Option<toml::Value> {
+    let mut document = toml_edit::DocumentMut::new();
+    document.insert("value", item.clone());
+    toml::from_str::<toml::Table>(&document.to_string())
+        .ok()?
+        .remove("value")
+}
+
+// Load and validate the TOML configuration file
+pub fn load_toml_config(path: &Path) -> Result<GitLabConfig, Box<dyn std::error::Error>> {
+    let config = read_config_file(path)?
+        .into_config()
+        .map_err(|e| format!("Invalid configuration in {}: {}", path.display(), e))?;
+    eprintln!("Loaded configuration from: {}", path.display());
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(toml: &str) -> ConfigFile {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> EnvFile {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn saving_keeps_comments_and_unchanged_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"# Work GitLab
+base_url = "https://gitlab.example.org"
+poll_interval = 300 # five minutes
+
+[defaults]
+# Only stable releases
+channel = "stable"
+
+# The main app
+[[projects]]
+path = "grp/app"
+display_name = "App"
+
+# Retired soon
+[[projects]]
+path = "grp/old"
+"#,
+        )
+        .unwrap();
+
+        let mut settings = read_config_file(&path).unwrap();
+        settings.poll_interval = Some(600);
+        settings.projects.retain(|p| p.path != "grp/old");
+        settings.projects.push(ProjectFile::new("grp/new"));
+        settings.save_to(&path).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# Work GitLab\n"), "{}", saved);
+        assert!(
+            saved.contains("poll_interval = 600 # five minutes"),
+            "{}",
+            saved
+        );
+        assert!(saved.contains("# Only stable releases\nchannel = \"stable\""));
+        assert!(saved.contains("# The main app\n[[projects]]\npath = \"grp/app\""));
+        assert!(!saved.contains("grp/old") && !saved.contains("Retired soon"));
+
+        let reloaded = read_config_file(&path).unwrap().into_config().unwrap();
+        let paths: Vec<&str> = reloaded.projects.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, ["grp/app", "grp/new"]);
+        assert_eq!(reloaded.poll_interval_secs, 600);
+    }
+
+    #[test]
+    fn the_last_project_can_be_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[[projects]]\npath = \"grp/app\"\n").unwrap();
+
+        let mut settings = read_config_file(&path).unwrap();
+        settings.projects.clear();
+        settings.save_to(&path).unwrap();
+
+        let config = read_config_file(&path).unwrap().into_config().unwrap();
+        assert!(config.projects.is_empty());
+        assert!(config.incomplete_reason().is_some());
+    }
+
+    #[test]
+    fn reads_the_env_file_without_changing_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "GITLAB_MONITOR_TEST_ONLY_IN_FILE=from-file\nGITLAB_PROJECTS=grp/app\n",
+        )
+        .unwrap();
+
+        let vars = read_env_file(&path).unwrap();
+        assert_eq!(vars["GITLAB_MONITOR_TEST_ONLY_IN_FILE"], "from-file");
+        assert!(std::env::var_os("GITLAB_MONITOR_TEST_ONLY_IN_FILE").is_none());
+        assert_eq!(
+            env_var(&vars, "GITLAB_MONITOR_TEST_ONLY_IN_FILE").as_deref(),
+            Ok("from-file")
+        );
+        // Anything else comes from the process environment
+        assert_eq!(env_var(&vars, "PATH").ok(), std::env::var("PATH").ok());
+    }
+
+    #[test]
+    fn env_config_is_read_from_the_env_file() {
+        let config = load_env_config(&env(&[
+            ("GITLAB_PROJECTS", "grp/app, group:platform"),
+            ("GITLAB_BASE_URL", "https://gitlab.example.org"),
+            ("GITLAB_POLL_INTERVAL", "600"),
+            ("GITLAB_CHANNEL", "stable;grp/app=all"),
+            ("GITLAB_SMTP_HOST", "smtp.example.org"),
+            ("GITLAB_SMTP_USERNAME", "monitor"),
+            ("GITLAB_SMTP_PASSWORD", "secret"),
+            ("GITLAB_DIGEST_FROM", "monitor@example.org"),
+            ("GITLAB_DIGEST_TO", "team@example.org"),
+        ]))
+        .unwrap();
+
+        assert_eq!(config.instances[0].base_url, "https://gitlab.example.org");
+        assert_eq!(config.projects.len(), 1);
+        assert_eq!(config.projects[0].path, "grp/app");
+        assert_eq!(config.projects[0].poll_interval_secs, 600);
+        assert_eq!(config.projects[0].channel, ReleaseChannel::All);
+        assert_eq!(config.discovery.len(), 1);
+        assert_eq!(config.discovery[0].settings.channel, ReleaseChannel::Stable);
+        let email = config.email.unwrap();
+        assert_eq!(email.smtp_host, "smtp.example.org");
+        assert_eq!(email.password.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn env_config_reports_invalid_values() {
+        let error = load_env_config(&env(&[
+            ("GITLAB_PROJECTS", "grp/app"),
+            ("GITLAB_PER_PAGE", "many"),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid value for GITLAB_PER_PAGE: 'many'"
+        );
+    }
+
+    #[test]
+    fn settings_from_the_ui_keep_the_hooks_of_the_file() {
+        let current = file(
+            r#"
+            hook_timeout = 60
+
+            [defaults]
+            on_release = ["./notify.sh"]
+
+            [[projects]]
+            path = "a/b"
+            on_release = ["./deploy.sh"]
+
+            [[instances]]
+            name = "work"
+            base_url = "https://gitlab.example.org"
+
+            [[instances.projects]]
+            path = "c/d"
+            on_release = ["./work.sh"]
+            "#,
+        );
+        let mut settings = file(
+            r#"
+            hook_timeout = 100000
+
+            [defaults]
+            on_release = ["./from-ui.sh"]
+
+            [[projects]]
+            path = "a/b"
+            on_release = ["./from-ui.sh"]
+
+            [[projects]]
+            path = "new/project"
+            on_release = ["./from-ui.sh"]
+
+            [[instances]]
+            name = "work"
+            base_url = "https://gitlab.example.org"
+
+            [[instances.projects]]
+            path = "c/d"
+
+            [[instances]]
+            name = "new"
+            base_url = "https://git.example.org"
+
+            [[instances.projects]]
+            path = "e/f"
+            on_release = ["./from-ui.sh"]
+            "#,
+        );
+
+        settings.keep_hooks_from(&current);
+        assert_eq!(settings.hook_timeout, Some(60));
+        assert_eq!(
+            settings.defaults.on_release,
+            Some(vec!["./notify.sh".to_string()])
+        );
+        assert_eq!(
+            settings.projects[0].on_release,
+            Some(vec!["./deploy.sh".to_string()])
+        );
+        assert_eq!(settings.projects[1].on_release, None);
+        assert_eq!(
+            settings.instances[0].projects[0].on_release,
+            Some(vec!["./work.sh".to_string()])
+        );
+        assert_eq!(settings.instances[1].projects[0].on_release, None);
+    }
+
+    #[test]
+    fn tokens_stay_with_their_server() {
+        let current = file(
+            r#"
+            api_token = "default-token"
+            base_url = "https://gitlab.example.org/"
+
+            [[instances]]
+            name = "work"
+            base_url = "https://work.example.org"
+            api_token = "work-token"
+
+            [[instances]]
+            name = "lab"
+            base_url = "https://lab.example.org"
+            api_token = "lab-token"
+
+            [email]
+            smtp_host = "smtp.example.org"
+            password = "mail-secret"
+            from = "monitor@example.org"
+            to = ["team@example.org"]
+            "#,
+        );
+        let mut settings = file(
+            r#"
+            base_url = "https://gitlab.example.org"
+
+            [[instances]]
+            name = "work"
+            base_url = "https://work.example.org"
+
+            [[instances]]
+            name = "lab"
+            base_url = "https://elsewhere.example.com"
+
+            [email]
+            smtp_host = "smtp.elsewhere.example.com"
+            from = "monitor@example.org"
+            to = ["team@example.org"]
+            "#,
+        );
+
+        let moved = settings.keep_tokens_from(&current);
+        assert_eq!(moved, ["lab"]);
+        assert_eq!(settings.api_token.as_deref(), Some("default-token"));
+        assert_eq!(
+            settings.instances[0].api_token.as_deref(),
+            Some("work-token")
+        );
+        assert_eq!(settings.instances[1].api_token, None);
+        assert_eq!(settings.email.unwrap().password, None);
+    }
+}
@@ -0,0 +1,303 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use crate::{
+    config::ProjectConfig,
+    gitlab::{GitLabSource, ProjectReleases},
+    models::Deployment,
+    sources::{get_json, SourceContext},
+};
+
+fn parse_deployment(
+    project: &ProjectConfig,
+    environment: &serde_json::Value,
+    deployment: &serde_json::Value,
+) -> Option<Deployment> {
+    Some(Deployment {
+        instance: project.instance.clone(),
+        project_path: project.path.clone(),
+        project_name: project.display_name(),
+        environment: environment["name"].as_str()?.to_string(),
+        environment_url: environment["external_url"]
+            .as_str()
+            .filter(|url| !url.is_empty())
+            .map(str::to_string),
+        id: deployment["id"].as_u64()?,
+        ref_name: deployment["ref"].as_str()?.to_string(),
+        tag: deployment["deployable"]["tag"].as_bool().unwrap_or(false),
+        sha: deployment["sha"].as_str().unwrap_or("").to_string(),
+        deployed_at: deployment["finished_at"]
+            .as_str()
+            .or_else(|| deployment["updated_at"].as_str())
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&Utc)),
+        release_name: None,
+        release_url: None,
+    })
+}
+
+// The latest successful deployment of every available environment of a project.
+// Environments that were never deployed to successfully are left out.
+pub async fn fetch_deployments(
+    ctx: &SourceContext<'_>,
+    project: &ProjectConfig,
+) -> Result<Vec<Deployment>, Box<dyn std::error::Error + Send + Sync>> {
+    let project_url = format!(
+        "{}/api/v4/projects/{}",
+        ctx.instance.base_url,
+        urlencoding::encode(&project.path)
+    );
+    let url = format!(
+        "{}/environments?states=available&per_page={}",
+        project_url, ctx.config.per_page
+    );
+    let environments = match get_json(&GitLabSource, ctx, &url).await? {
+        (status, environments) if status.is_success() => environments,
+        (status, _) => {
+            return Err(format!(
+                "Failed to fetch the environments of {}: {}",
+                project.path, status
+            )
+            .into())
+        }
+    };
+
+    let mut deployments = Vec::new();
+    for environment in environments.as_array().into_iter().flatten() {
+        let Some(name) = environment["name"].as_str() else {
+            continue;
+        };
+        let url = format!(
+            "{}/deployments?environment={}&status=success&order_by=id&sort=desc&per_page=1",
+            project_url,
+            urlencoding::encode(name)
+        );
+        match get_json(&GitLabSource, ctx, &url).await? {
+            (status, found) if status.is_success() => deployments.extend(
+                found
+                    .as_array()
+                    .and_then(|found| found.first())
+                    .and_then(|deployment| parse_deployment(project, environment, deployment)),
+            ),
+            (status, _) => {
+                return Err(format!(
+                    "Failed to fetch the deployments of {} to {}: {}",
+                    project.path, name, status
+                )
+                .into())
+            }
+        }
+    }
+    Ok(deployments)
+}
+
+// Fetch what is deployed where and link it to the releases: each release lists
+// the environments its tag is live in, and each deployment of a release tag
+// names the release. Failures are logged and keep the `previous` deployments
+// of the project, so the next successful fetch only reports actual changes.
+pub async fn attach_deployments(
+    ctx: &SourceContext<'_>,
+    project: &ProjectConfig,
+    fetched: &mut ProjectReleases,
+    previous: &[Deployment],
+) {
+    if !project.deployments {
+        return;
+    }
+    let mut deployments = match fetch_deployments(ctx, project).await {
+        Ok(deployments) => deployments,
+        Err(e) => {
+            eprintln!("{}", e);
+            previous.to_vec()
+        }
+    };
+
+    for deployment in deployments.iter_mut().filter(|d| d.tag) {
+        if let Some(release) = fetched
+            .releases
+            .iter_mut()
+            .find(|r| r.tag_name == deployment.ref_name)
+        {
+            release.environments.push(deployment.environment.clone());
+            deployment.release_name = Some(release.name.clone()).filter(|n| !n.is_empty());
+            deployment.release_url = Some(release.web_url.clone());
+        }
+    }
+    fetched.deployments = deployments;
+}
+
+// Tags deployed to an environment since the previous refresh, e.g. "v1.2.0
+// deployed to production". Like new releases, environments seen for the first
+// time only seed the comparison, and branch deployments are not reported.
+pub fn detect_new_deployments(previous: &[Deployment], current: &[Deployment]) -> Vec<Deployment> {
+    let previous: HashMap<(&str, &str, &str), u64> = previous
+        .iter()
+        .map(|d| {
+            (
+                (
+                    d.instance.as_str(),
+                    d.project_path.as_str(),
+                    d.environment.as_str(),
+                ),
+                d.id,
+            )
+        })
+        .collect();
+    current
+        .iter()
+        .filter(|d| d.tag)
+        .filter(|d| {
+            let key = (
+                d.instance.as_str(),
+                d.project_path.as_str(),
+                d.environment.as_str(),
+            );
+            previous.get(&key).is_some_and(|id| *id != d.id)
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReleaseCache,
+        config::{GitLabConfig, InstanceConfig},
+        models::GitLabRelease,
+        rate_limit::RateLimiter,
+        test_support::HttpStub,
+    };
+    use tokio::sync::Mutex;
+
+    fn release(tag_name: &str) -> GitLabRelease {
+        GitLabRelease {
+            instance: "default".to_string(),
+            project_name: "app".to_string(),
+            project_path: "grp/app".to_string(),
+            tag_name: tag_name.to_string(),
+            name: format!("Release {}", tag_name),
+            description: String::new(),
+            created_at: "2026-10-01T10:00:00Z".parse().unwrap(),
+            released_at: None,
+            web_url: format!("https://gitlab.example.com/grp/app/-/releases/{}", tag_name),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline: None,
+            environments: Vec::new(),
+        }
+    }
+
+    fn deployment(environment: &str, id: u64, ref_name: &str) -> Deployment {
+        Deployment {
+            instance: "default".to_string(),
+            project_path: "grp/app".to_string(),
+            project_name: "app".to_string(),
+            environment: environment.to_string(),
+            environment_url: None,
+            id,
+            ref_name: ref_name.to_string(),
+            tag: true,
+            sha: String::new(),
+            deployed_at: None,
+            release_name: None,
+            release_url: None,
+        }
+    }
+
+    // Attach the deployments served by `stub` to releases v1.0.0 and v1.1.0
+    async fn attach(stub: &HttpStub, previous: &[Deployment]) -> ProjectReleases {
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 0;
+        let instance = InstanceConfig::new("default", &stub.url, "token");
+        let mut project = ProjectConfig::new("default", "grp/app");
+        project.deployments = true;
+        let client = reqwest::Client::new();
+        let cache = Mutex::new(ReleaseCache::default());
+        let limiter = RateLimiter::new();
+        let ctx = SourceContext {
+            client: &client,
+            config: &config,
+            instance: &instance,
+            cache: &cache,
+            limiter: &limiter,
+        };
+
+        let mut fetched = ProjectReleases {
+            releases: vec![release("v1.1.0"), release("v1.0.0")],
+            truncated: false,
+            pipeline: None,
+            deployments: Vec::new(),
+        };
+        attach_deployments(&ctx, &project, &mut fetched, previous).await;
+        fetched
+    }
+
+    #[tokio::test]
+    async fn links_deployments_to_releases() {
+        let stub = HttpStub::start(|request| {
+            let body = if request.path.contains("/environments?") {
+                r#"[{"name": "production", "external_url": ""}]"#
+            } else {
+                r#"[{"id": 7, "ref": "v1.0.0", "sha": "abc", "deployable": {"tag": true},
+                     "finished_at": "2026-10-02T10:00:00Z"}]"#
+            };
+            (200, body.to_string())
+        })
+        .await;
+
+        let fetched = attach(&stub, &[]).await;
+        assert_eq!(fetched.deployments.len(), 1);
+        assert_eq!(fetched.deployments[0].id, 7);
+        assert_eq!(
+            fetched.deployments[0].release_name.as_deref(),
+            Some("Release v1.0.0")
+        );
+        assert!(fetched.releases[0].environments.is_empty());
+        assert_eq!(fetched.releases[1].environments, ["production"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_previous_deployments_when_the_fetch_fails() {
+        let stub = HttpStub::start(|_| (500, "{}".to_string())).await;
+        let previous = vec![deployment("production", 7, "v1.0.0")];
+
+        let fetched = attach(&stub, &previous).await;
+        assert_eq!(fetched.deployments.len(), 1);
+        assert_eq!(fetched.deployments[0].id, 7);
+        assert_eq!(fetched.releases[1].environments, ["production"]);
+
+        // Nothing counts as newly deployed once the fetch succeeds again
+        assert!(detect_new_deployments(&previous, &fetched.deployments).is_empty());
+    }
+
+    #[test]
+    fn reports_new_deployments_to_known_environments_only() {
+        let previous = vec![
+            deployment("production", 7, "v1.0.0"),
+            deployment("staging", 8, "v1.1.0"),
+        ];
+        let current = vec![
+            deployment("production", 9, "v1.1.0"),
+            deployment("staging", 8, "v1.1.0"),
+            deployment("review", 10, "v1.1.0"),
+        ];
+
+        let deployed = detect_new_deployments(&previous, &current);
+        assert_eq!(deployed.len(), 1);
+        assert_eq!(deployed[0].environment, "production");
+        assert_eq!(deployed[0].id, 9);
+        assert_eq!(deployed[0].ref_name, "v1.1.0");
+    }
+
+    #[test]
+    fn branch_deployments_are_not_reported() {
+        let previous = vec![deployment("staging", 8, "v1.1.0")];
+        let mut branch = deployment("staging", 9, "main");
+        branch.tag = false;
+
+        assert!(detect_new_deployments(&previous, &[branch]).is_empty());
+    }
+}
@@ -0,0 +1,370 @@
+use chrono::{NaiveDate, Utc};
+use reqwest::StatusCode;
+
+use crate::{
+    config::{GitLabConfig, InstanceConfig},
+    gitlab::{api_get, instance_client},
+    models::{CheckStatus, DiagnosticReport},
+    sources::{Provider, SourceContext},
+    state::AppState,
+};
+
+// Token scopes that allow reading releases
+const READ_SCOPES: [&str; 2] = ["read_api", "api"];
+// Tokens expiring within this many days are reported
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+// Check the configuration against GitLab: that every instance is reachable,
+// its token is accepted and has a read scope, and every project is visible.
+// A configuration that failed to load is reported with its error.
+pub async fn diagnose(state: &AppState) -> DiagnosticReport {
+    let config = state.current_config().await;
+    let mut report = DiagnosticReport::default();
+    if let Some(error) = state.config_error.lock().await.clone() {
+        report.push("", "configuration", CheckStatus::Error, error);
+    } else if config.projects.is_empty() && config.discovery.is_empty() {
+        report.push(
+            "",
+            "configuration",
+            CheckStatus::Error,
+            "No projects are configured".to_string(),
+        );
+    }
+    for instance in &config.instances {
+        diagnose_instance(state, &config, instance, &mut report).await;
+    }
+    report
+}
+
+async fn diagnose_instance(
+    state: &AppState,
+    config: &GitLabConfig,
+    instance: &InstanceConfig,
+    report: &mut DiagnosticReport,
+) {
+    let name = instance.name.as_str();
+    // Discovered projects were just listed by GitLab, so only the listed ones
+    // are looked up
+    let projects: Vec<&str> = config
+        .projects
+        .iter()
+        .filter(|p| p.instance == instance.name && p.discovered_by.is_none())
+        .map(|p| p.path.as_str())
+        .collect();
+    let skip_projects = |report: &mut DiagnosticReport| {
+        for path in &projects {
+            report.push(name, path, CheckStatus::Skipped, "Not checked".to_string());
+        }
+    };
+
+    if instance.provider != Provider::GitLab {
+        diagnose_repositories(state, config, instance, &projects, report).await;
+        return;
+    }
+
+    if instance.api_token.is_empty() {
+        report.push(
+            name,
+            "token",
+            CheckStatus::Error,
+            "No API token is set; store one with the key button".to_string(),
+        );
+        skip_projects(report);
+        return;
+    }
+
+    // Reachability and authentication
+    match api_get(state, instance, "user").await {
+        Err(e) => {
+            report.push(
+                name,
+                "connection",
+                CheckStatus::Error,
+                format!("Cannot reach {}: {}", instance.base_url, e),
+            );
+            skip_projects(report);
+            return;
+        }
+        Ok((StatusCode::UNAUTHORIZED, _)) => {
+            report.push(
+                name,
+                "connection",
+                CheckStatus::Ok,
+                format!("Reachable at {}", instance.base_url),
+            );
+            report.push(
+                name,
+                "token",
+                CheckStatus::Error,
+                "The token was rejected (expired, revoked or mistyped)".to_string(),
+            );
+            skip_projects(report);
+            return;
+        }
+        Ok((status, _)) if !status.is_success() => {
+            report.push(
+                name,
+                "connection",
+                CheckStatus::Error,
+                format!("{} answered {}", instance.base_url, status),
+            );
+            skip_projects(report);
+            return;
+        }
+        Ok((_, user)) => {
+            report.push(
+                name,
+                "connection",
+                CheckStatus::Ok,
+                format!("Reachable at {}", instance.base_url),
+            );
+            report.push(
+                name,
+                "token",
+                CheckStatus::Ok,
+                format!(
+                    "Authenticated as {}",
+                    user["username"].as_str().unwrap_or("unknown user")
+                ),
+            );
+        }
+    }
+
+    // Scopes; the endpoint needs GitLab 15.5+ and does not exist for OAuth tokens
+    match api_get(state, instance, "personal_access_tokens/self").await {
+        Ok((status, token)) if status.is_success() => {
+            let scopes: Vec<&str> = token["scopes"]
+                .as_array()
+                .map(|s| s.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default();
+            if scopes.iter().any(|s| READ_SCOPES.contains(s)) {
+                report.push(
+                    name,
+                    "scopes",
+                    CheckStatus::Ok,
+                    format!("Token scopes: {}", scopes.join(", ")),
+                );
+            } else {
+                report.push(
+                    name,
+                    "scopes",
+                    CheckStatus::Error,
+                    format!(
+                        "The token lacks the read_api scope (it has: {})",
+                        scopes.join(", ")
+                    ),
+                );
+            }
+
+            let expires_at = token["expires_at"]
+                .as_str()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+            if let Some(expires_at) = expires_at {
+                if (expires_at - Utc::now().date_naive()).num_days() <= EXPIRY_WARNING_DAYS {
+                    report.push(
+                        name,
+                        "token",
+                        CheckStatus::Warning,
+                        format!("The token expires on {}", expires_at),
+                    );
+                }
+            }
+        }
+        Ok((status, _)) => report.push(
+            name,
+            "scopes",
+            CheckStatus::Warning,
+            format!("Could not read the token scopes ({})", status),
+        ),
+        Err(e) => report.push(
+            name,
+            "scopes",
+            CheckStatus::Warning,
+            format!("Could not read the token scopes: {}", e),
+        ),
+    }
+
+    for rule in config
+        .discovery
+        .iter()
+        .filter(|r| r.settings.instance == instance.name)
+    {
+        let entry = rule.settings.path.as_str();
+        let found = config
+            .projects
+            .iter()
+            .filter(|p| p.discovered_by.as_deref() == Some(entry))
+            .count();
+        if found == 0 {
+            report.push(
+                name,
+                entry,
+                CheckStatus::Warning,
+                "Found no projects".to_string(),
+            );
+        } else {
+            report.push(
+                name,
+                entry,
+                CheckStatus::Ok,
+                format!("Found {} project(s)", found),
+            );
+        }
+    }
+
+    for path in projects {
+        let api_path = format!("projects/{}", urlencoding::encode(path));
+        match api_get(state, instance, &api_path).await {
+            Err(e) => report.push(
+                name,
+                path,
+                CheckStatus::Error,
+                format!("Failed to look up the project: {}", e),
+            ),
+            Ok((StatusCode::NOT_FOUND, _)) => report.push(
+                name,
+                path,
+                CheckStatus::Error,
+                "Not found, or not visible to the token's user".to_string(),
+            ),
+            Ok((status, _)) if !status.is_success() => report.push(
+                name,
+                path,
+                CheckStatus::Error,
+                format!("Failed to look up the project: {}", status),
+            ),
+            Ok((_, project)) => {
+                let visibility = project["visibility"].as_str().unwrap_or("unknown");
+                if project["releases_access_level"].as_str() == Some("disabled") {
+                    report.push(
+                        name,
+                        path,
+                        CheckStatus::Warning,
+                        format!("Found ({}), but its releases are disabled", visibility),
+                    );
+                } else {
+                    report.push(
+                        name,
+                        path,
+                        CheckStatus::Ok,
+                        format!("Found ({})", visibility),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// GitHub and Gitea instances: the token is optional, and each repository is
+// looked up through the instance's provider
+async fn diagnose_repositories(
+    state: &AppState,
+    config: &GitLabConfig,
+    instance: &InstanceConfig,
+    projects: &[&str],
+    report: &mut DiagnosticReport,
+) {
+    let name = instance.name.as_str();
+    if instance.api_token.is_empty() {
+        report.push(
+            name,
+            "token",
+            CheckStatus::Warning,
+            "No API token is set; only public repositories can be read, at a lower rate limit"
+                .to_string(),
+        );
+    }
+
+    let client = match instance_client(state, instance).await {
+        Ok(client) => client,
+        Err(e) => {
+            report.push(
+                name,
+                "connection",
+                CheckStatus::Error,
+                format!("Cannot connect to {}: {}", instance.base_url, e),
+            );
+            return;
+        }
+    };
+    let limiter = state.rate_limiter(&instance.name).await;
+    let ctx = SourceContext {
+        client: &client,
+        config,
+        instance,
+        cache: &state.release_cache,
+        limiter: &limiter,
+    };
+    for path in projects {
+        match instance.provider.lookup_project(&ctx, path).await {
+            Ok(project) => report.push(
+                name,
+                path,
+                CheckStatus::Ok,
+                format!("Found ({})", project.web_url),
+            ),
+            Err(e) => report.push(name, path, CheckStatus::Error, e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ProjectConfig, discovery::DiscoveryRule, state::StateFiles};
+    use tempfile::TempDir;
+
+    // State keeping its files in a temporary directory, removed with it
+    fn state(config: GitLabConfig) -> (TempDir, AppState) {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::with_files(config, StateFiles::in_dir(dir.path()));
+        (dir, state)
+    }
+
+    fn configuration_errors(report: &DiagnosticReport) -> Vec<&str> {
+        report
+            .errors()
+            .filter(|check| check.subject == "configuration")
+            .map(|check| check.message.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reports_a_configuration_without_projects() {
+        let (_dir, state) = state(GitLabConfig::empty());
+        let report = diagnose(&state).await;
+        assert_eq!(
+            configuration_errors(&report),
+            ["No projects are configured"]
+        );
+    }
+
+    #[tokio::test]
+    async fn discovery_rules_count_as_projects() {
+        let mut config = GitLabConfig::empty();
+        let rule = DiscoveryRule::new(
+            ProjectConfig::new("default", "group:platform"),
+            Vec::new(),
+            Vec::new(),
+        )
+        .unwrap();
+        config.discovery.push(rule);
+        let (_dir, state) = state(config);
+        let report = diagnose(&state).await;
+        assert!(configuration_errors(&report).is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_why_the_configuration_failed_to_load() {
+        let (_dir, state) = state(GitLabConfig::empty());
+        *state.config_error.lock().await = Some(
+            "Invalid configuration in config.toml: per_page must be between 1 and 100, got 0"
+                .to_string(),
+        );
+        let report = diagnose(&state).await;
+        assert_eq!(
+            configuration_errors(&report),
+            ["Invalid configuration in config.toml: per_page must be between 1 and 100, got 0"]
+        );
+    }
+}
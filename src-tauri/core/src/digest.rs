@@ -0,0 +1,592 @@
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveTime, TimeZone, Utc, Weekday,
+};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use once_cell::sync::Lazy;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{models::GitLabRelease, state::AppState, storage, webhooks::html_escape};
+
+// Wait this long before trying again after a digest could not be sent
+const RETRY_DELAY_MINUTES: i64 = 15;
+
+// Held while a digest is being sent so a slow server never gets it twice
+static SENDING: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+// When the digest goes out, in local time: `daily 08:00` or `weekly mon 08:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestSchedule {
+    Daily { at: NaiveTime },
+    Weekly { day: Weekday, at: NaiveTime },
+}
+
+// Every morning at 8
+impl Default for DigestSchedule {
+    fn default() -> Self {
+        DigestSchedule::Daily {
+            at: NaiveTime::from_hms_opt(8, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+impl DigestSchedule {
+    // The latest scheduled time at or before `now`
+    pub fn last_slot(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let (at, days_back) = match self {
+            DigestSchedule::Daily { at } => (*at, 0),
+            DigestSchedule::Weekly { day, at } => (
+                *at,
+                (now.weekday().num_days_from_monday() + 7 - day.num_days_from_monday()) % 7,
+            ),
+        };
+        let date = now.date_naive() - ChronoDuration::days(days_back.into());
+        // A time skipped by a DST change counts as the start of that day
+        let slot = |date: chrono::NaiveDate| {
+            Local
+                .from_local_datetime(&date.and_time(at))
+                .earliest()
+                .unwrap_or_else(|| Local.from_utc_datetime(&date.and_time(NaiveTime::MIN)))
+        };
+        let candidate = slot(date);
+        if candidate <= now {
+            return candidate;
+        }
+        let period = match self {
+            DigestSchedule::Daily { .. } => 1,
+            DigestSchedule::Weekly { .. } => 7,
+        };
+        slot(date - ChronoDuration::days(period))
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            DigestSchedule::Daily { .. } => "Daily release digest",
+            DigestSchedule::Weekly { .. } => "Weekly release digest",
+        }
+    }
+}
+
+impl FromStr for DigestSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid digest schedule '{}' (expected 'daily HH:MM' or 'weekly <day> HH:MM')",
+                s
+            )
+        };
+        let parse_time =
+            |value: &str| NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| invalid());
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        match parts.as_slice() {
+            ["daily", at] => Ok(DigestSchedule::Daily {
+                at: parse_time(at)?,
+            }),
+            ["weekly", day, at] => Ok(DigestSchedule::Weekly {
+                day: day.parse().map_err(|_| invalid())?,
+                at: parse_time(at)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for DigestSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DigestSchedule::Daily { at } => write!(f, "daily {}", at.format("%H:%M")),
+            DigestSchedule::Weekly { day, at } => {
+                let day = day.to_string().to_ascii_lowercase();
+                write!(f, "weekly {} {}", day, at.format("%H:%M"))
+            }
+        }
+    }
+}
+
+impl Serialize for DigestSchedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DigestSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    // Plain connection upgraded with STARTTLS (port 587)
+    StartTls,
+    // TLS from the start (port 465)
+    Tls,
+    // No encryption, e.g. a local relay or test sink (port 25)
+    None,
+}
+
+impl SmtpTls {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpTls::StartTls => 587,
+            SmtpTls::Tls => 465,
+            SmtpTls::None => 25,
+        }
+    }
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            "none" => Ok(SmtpTls::None),
+            other => Err(format!(
+                "unknown SMTP security '{}' (expected starttls, tls or none)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SmtpTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtpTls::StartTls => write!(f, "starttls"),
+            SmtpTls::Tls => write!(f, "tls"),
+            SmtpTls::None => write!(f, "none"),
+        }
+    }
+}
+
+impl Serialize for SmtpTls {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SmtpTls {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// Email digest of new releases
+#[derive(Debug, Clone)]
+pub struct EmailSettings {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub tls: SmtpTls,
+    // Both are needed to log in; without them the server must accept mail as is
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    pub schedule: DigestSchedule,
+}
+
+// New releases waiting for the next digest, persisted so a restart does not
+// lose them
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DigestQueue {
+    #[serde(default)]
+    pub releases: Vec<GitLabRelease>,
+    // When the last digest went out; the first one covers releases since then
+    #[serde(default)]
+    pub last_sent_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl DigestQueue {
+    pub fn file_path() -> PathBuf {
+        storage::data_dir().join("digest-queue.json")
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        storage::load_json(path).unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        storage::save_json(path, self)
+    }
+
+    // Queue releases, skipping those already waiting
+    pub fn add(&mut self, releases: &[GitLabRelease]) {
+        for release in releases {
+            let queued = self.releases.iter().any(|r| {
+                r.instance == release.instance
+                    && r.project_path == release.project_path
+                    && r.tag_name == release.tag_name
+            });
+            if !queued {
+                self.releases.push(release.clone());
+            }
+        }
+    }
+}
+
+// Queue the new releases of a refresh for the next digest
+pub async fn queue_for_digest(state: &AppState, releases: &[GitLabRelease]) {
+    if releases.is_empty() {
+        return;
+    }
+    let mut queue = state.digest_queue.lock().await;
+    queue.add(releases);
+    if let Err(e) = queue.save_to(&state.digest_file) {
+        eprintln!("Failed to save digest queue: {}", e);
+    }
+}
+
+// Send the digest when its scheduled time has passed since the last one.
+// Nothing is sent for a period without new releases.
+pub async fn send_due_digest(state: &AppState) {
+    let config = state.current_config().await;
+    let Some(settings) = &config.email else {
+        return;
+    };
+    let Ok(_sending) = SENDING.try_lock() else {
+        return;
+    };
+
+    let now = Utc::now();
+    let mut queue = state.digest_queue.lock().await;
+    let Some(last_sent_at) = queue.last_sent_at else {
+        // First run: the first digest covers releases from now on
+        queue.last_sent_at = Some(now);
+        if let Err(e) = queue.save_to(&state.digest_file) {
+            eprintln!("Failed to save digest queue: {}", e);
+        }
+        return;
+    };
+    if settings.schedule.last_slot(now.with_timezone(&Local)) <= last_sent_at
+        || queue
+            .failed_at
+            .is_some_and(|failed| now - failed < ChronoDuration::minutes(RETRY_DELAY_MINUTES))
+    {
+        return;
+    }
+    let releases = queue.releases.clone();
+    drop(queue);
+
+    let result = if releases.is_empty() {
+        Ok(())
+    } else {
+        send_digest(
+            settings,
+            &releases,
+            Some(last_sent_at),
+            config.request_timeout_secs,
+        )
+        .await
+    };
+
+    let mut queue = state.digest_queue.lock().await;
+    match result {
+        Ok(()) => {
+            if !releases.is_empty() {
+                println!("Sent release digest with {} release(s)", releases.len());
+            }
+            // Releases queued while sending wait for the next digest
+            queue.releases.drain(..releases.len());
+            queue.last_sent_at = Some(now);
+            queue.failed_at = None;
+        }
+        Err(e) => {
+            eprintln!(
+                "Failed to send release digest, retrying in {} minutes: {}",
+                RETRY_DELAY_MINUTES, e
+            );
+            queue.failed_at = Some(now);
+        }
+    }
+    if let Err(e) = queue.save_to(&state.digest_file) {
+        eprintln!("Failed to save digest queue: {}", e);
+    }
+}
+
+// Send a digest of `releases` to every recipient
+pub async fn send_digest(
+    settings: &EmailSettings,
+    releases: &[GitLabRelease],
+    since: Option<DateTime<Utc>>,
+    timeout_secs: u64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let subject = format!(
+        "{}: {} new release{}",
+        settings.schedule.title(),
+        releases.len(),
+        if releases.len() == 1 { "" } else { "s" }
+    );
+    let (text, html) = digest_body(releases, since);
+
+    let mut message = Message::builder()
+        .from(settings.from.clone())
+        .subject(subject);
+    for to in &settings.to {
+        message = message.to(to.clone());
+    }
+    let message = message.multipart(MultiPart::alternative_plain_html(text, html))?;
+
+    let host = settings.smtp_host.as_str();
+    let transport = match settings.tls {
+        SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+    };
+    let mut transport = transport
+        .port(settings.smtp_port)
+        .timeout(Some(Duration::from_secs(timeout_secs)));
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport.build().send(message).await?;
+    Ok(())
+}
+
+// Plain text and HTML body, with the releases grouped by project
+pub fn digest_body(releases: &[GitLabRelease], since: Option<DateTime<Utc>>) -> (String, String) {
+    let mut projects: BTreeMap<(&str, &str), Vec<&GitLabRelease>> = BTreeMap::new();
+    for release in releases {
+        projects
+            .entry((release.project_name.as_str(), release.project_path.as_str()))
+            .or_default()
+            .push(release);
+    }
+
+    let intro = match since {
+        Some(since) => format!(
+            "{} new release(s) since {}",
+            releases.len(),
+            since.with_timezone(&Local).format("%a, %d %b %Y %H:%M")
+        ),
+        None => format!("{} new release(s)", releases.len()),
+    };
+
+    let mut text = format!("{}\n", intro);
+    let mut html = format!(
+        "<html><body style=\"font-family: sans-serif\"><p>{}</p>",
+        html_escape(&intro)
+    );
+    for ((name, path), releases) in &projects {
+        text.push_str(&format!("\n{} ({})\n", name, path));
+        html.push_str(&format!(
+            "<h3 style=\"margin-bottom: 4px\">{} <small style=\"color: #666\">{}</small></h3><ul>",
+            html_escape(name),
+            html_escape(path)
+        ));
+        for release in releases {
+            let date = release
+                .released_at
+                .unwrap_or(release.created_at)
+                .with_timezone(&Local)
+                .format("%Y-%m-%d");
+            let title = if release.name.is_empty() || release.name == release.tag_name {
+                String::new()
+            } else {
+                format!(" - {}", release.name)
+            };
+            text.push_str(&format!(
+                "  {}{} ({})\n    {}\n",
+                release.tag_name, title, date, release.web_url
+            ));
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>{} <span style=\"color: #666\">({})</span></li>",
+                html_escape(&release.web_url),
+                html_escape(&release.tag_name),
+                html_escape(&title),
+                date
+            ));
+        }
+        html.push_str("</ul>");
+    }
+    html.push_str(
+        "<p style=\"color: #666; font-size: small\">Sent by GitLab Releases Monitor</p></body></html>",
+    );
+    (text, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::GitLabConfig, state::StateFiles, test_support::SmtpSink, webhooks::sample_changes,
+    };
+    use tempfile::TempDir;
+
+    fn release(project: &str, tag: &str) -> GitLabRelease {
+        GitLabRelease {
+            project_name: project.to_string(),
+            project_path: format!("example/{}", project),
+            tag_name: tag.to_string(),
+            name: format!("Release {}", tag),
+            web_url: format!(
+                "https://gitlab.example.org/example/{}/-/releases/{}",
+                project, tag
+            ),
+            ..sample_changes().added.remove(0)
+        }
+    }
+
+    fn settings(port: u16) -> EmailSettings {
+        EmailSettings {
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: port,
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "Release Monitor <monitor@example.org>".parse().unwrap(),
+            to: vec![
+                "dev@example.org".parse().unwrap(),
+                "ops@example.org".parse().unwrap(),
+            ],
+            schedule: DigestSchedule::default(),
+        }
+    }
+
+    // State sending digests to `port`, with its queue kept in a temporary
+    // directory
+    fn state(port: u16) -> (TempDir, AppState) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = GitLabConfig::empty();
+        config.email = Some(settings(port));
+        config.request_timeout_secs = 5;
+        let state = AppState::with_files(config, StateFiles::in_dir(dir.path()));
+        (dir, state)
+    }
+
+    #[test]
+    fn queues_each_release_once() {
+        let mut queue = DigestQueue::default();
+        queue.add(&[release("api", "v1.0.0"), release("web", "v2.0.0")]);
+        queue.add(&[release("api", "v1.0.0"), release("api", "v1.1.0")]);
+        let tags: Vec<&str> = queue.releases.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(tags, ["v1.0.0", "v2.0.0", "v1.1.0"]);
+    }
+
+    #[test]
+    fn groups_the_body_by_project() {
+        let releases = [
+            release("web", "v2.0.0"),
+            release("api", "v1.0.0"),
+            release("web", "v2.1.0"),
+        ];
+        let (text, html) = digest_body(&releases, None);
+        assert!(text.starts_with("3 new release(s)\n"));
+        let api = text.find("api (example/api)").unwrap();
+        let web = text.find("web (example/web)").unwrap();
+        assert!(api < web);
+        assert!(text[web..].contains("v2.0.0 - Release v2.0.0"));
+        assert!(text[web..].contains("v2.1.0 - Release v2.1.0"));
+        assert!(html.contains(
+            "<a href=\"https://gitlab.example.org/example/api/-/releases/v1.0.0\">v1.0.0</a>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn sends_the_digest_over_smtp() {
+        let sink = SmtpSink::start().await;
+        let mut settings = settings(sink.port);
+        settings.username = Some("monitor".to_string());
+        settings.password = Some("secret".to_string());
+        let releases = [release("api", "v1.0.0"), release("web", "v2.0.0")];
+
+        send_digest(&settings, &releases, None, 5).await.unwrap();
+
+        let messages = sink.messages();
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert_eq!(message.from, "monitor@example.org");
+        assert_eq!(message.to, ["dev@example.org", "ops@example.org"]);
+        // AUTH PLAIN: "\0monitor\0secret" in base64
+        assert_eq!(message.auth.as_deref(), Some("AG1vbml0b3IAc2VjcmV0"));
+
+        let data = &message.data;
+        assert!(data.contains("Subject: Daily release digest: 2 new releases"));
+        assert!(data.contains("From: \"Release Monitor\" <monitor@example.org>"));
+        assert!(data.contains("To: dev@example.org, ops@example.org"));
+        assert!(data.contains("Content-Type: multipart/alternative"));
+        assert!(data.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(data.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(data.contains("api (example/api)"));
+        assert!(data.contains("web (example/web)"));
+    }
+
+    #[tokio::test]
+    async fn batches_queued_releases_into_one_digest() {
+        let sink = SmtpSink::start().await;
+        let (_dir, state) = state(sink.port);
+
+        // The first run only starts the period the first digest covers
+        send_due_digest(&state).await;
+        assert!(state.digest_queue.lock().await.last_sent_at.is_some());
+        assert!(sink.messages().is_empty());
+
+        queue_for_digest(
+            &state,
+            &[release("api", "v1.0.0"), release("web", "v2.0.0")],
+        )
+        .await;
+        queue_for_digest(
+            &state,
+            &[release("api", "v1.0.0"), release("api", "v1.1.0")],
+        )
+        .await;
+        state.digest_queue.lock().await.last_sent_at = Some(Utc::now() - ChronoDuration::days(2));
+
+        send_due_digest(&state).await;
+        let messages = sink.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0]
+            .data
+            .contains("Subject: Daily release digest: 3 new releases"));
+
+        let queue = state.digest_queue.lock().await.clone();
+        assert!(queue.releases.is_empty());
+        assert!(queue.failed_at.is_none());
+        // The queue on disk matches
+        let saved = DigestQueue::load_from(&state.digest_file);
+        assert!(saved.releases.is_empty());
+        assert_eq!(saved.last_sent_at, queue.last_sent_at);
+
+        // Nothing more until the next scheduled time
+        queue_for_digest(&state, &[release("api", "v1.2.0")]).await;
+        send_due_digest(&state).await;
+        assert_eq!(sink.messages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_queue_when_sending_fails() {
+        // Nothing listens on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (_dir, state) = state(port);
+        queue_for_digest(&state, &[release("api", "v1.0.0")]).await;
+        let last_sent_at = Utc::now() - ChronoDuration::days(2);
+        state.digest_queue.lock().await.last_sent_at = Some(last_sent_at);
+
+        send_due_digest(&state).await;
+        let queue = state.digest_queue.lock().await.clone();
+        assert_eq!(queue.releases.len(), 1);
+        assert_eq!(queue.last_sent_at, Some(last_sent_at));
+        assert!(queue.failed_at.is_some());
+    }
+}
@@ -0,0 +1,531 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    config::{GitLabConfig, InstanceConfig, ProjectConfig},
+    gitlab::api_get_all,
+    models::FetchFailure,
+    state::AppState,
+};
+
+// Names GitLab uses for its access levels
+const ACCESS_LEVELS: [(&str, u32); 5] = [
+    ("guest", 10),
+    ("reporter", 20),
+    ("developer", 30),
+    ("maintainer", 40),
+    ("owner", 50),
+];
+
+// Where a project list entry finds its projects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectSource {
+    // `group:<path>` for the group's own projects, `group:<path>/*` to include
+    // all subgroups
+    Group { path: String, subgroups: bool },
+    // `starred`: projects starred by the token's user
+    Starred,
+    // `member` or `member:<access level>`: projects the token's user is a member
+    // of, optionally with at least that access level (e.g. `member:developer`)
+    Member { min_access_level: Option<u32> },
+}
+
+impl ProjectSource {
+    // Whether a project list entry is a discovery rule rather than a project path
+    pub fn is_rule(entry: &str) -> bool {
+        let entry = entry.trim();
+        entry.starts_with("group:")
+            || entry == "starred"
+            || entry == "member"
+            || entry.starts_with("member:")
+    }
+
+    // List endpoint returning the projects of this source (without `per_page`)
+    fn api_path(&self) -> String {
+        // Archived projects no longer get releases
+        match self {
+            ProjectSource::Group { path, subgroups } => format!(
+                "groups/{}/projects?include_subgroups={}&archived=false&simple=true",
+                urlencoding::encode(path),
+                subgroups
+            ),
+            ProjectSource::Starred => {
+                "projects?starred=true&archived=false&simple=true".to_string()
+            }
+            ProjectSource::Member { min_access_level } => {
+                let mut path = "projects?membership=true&archived=false&simple=true".to_string();
+                if let Some(level) = min_access_level {
+                    path.push_str(&format!("&min_access_level={}", level));
+                }
+                path
+            }
+        }
+    }
+}
+
+impl FromStr for ProjectSource {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let entry = entry.trim();
+        if let Some(group) = entry.strip_prefix("group:") {
+            let (path, subgroups) = match group.strip_suffix("/*") {
+                Some(path) => (path, true),
+                None => (group, false),
+            };
+            let path = path.trim().trim_matches('/');
+            if path.is_empty() || path.contains('*') {
+                return Err(format!(
+                    "invalid group entry '{}' (expected group:<path> or group:<path>/*)",
+                    entry
+                ));
+            }
+            return Ok(ProjectSource::Group {
+                path: path.to_string(),
+                subgroups,
+            });
+        }
+        if entry == "starred" {
+            return Ok(ProjectSource::Starred);
+        }
+        if entry == "member" {
+            return Ok(ProjectSource::Member {
+                min_access_level: None,
+            });
+        }
+        if let Some(level) = entry.strip_prefix("member:") {
+            let level = level.trim().to_ascii_lowercase();
+            let min_access_level = ACCESS_LEVELS
+                .iter()
+                .find(|(name, _)| *name == level)
+                .map(|(_, value)| *value)
+                .or_else(|| level.parse().ok())
+                .ok_or_else(|| {
+                    format!(
+                        "unknown access level '{}' in '{}' (expected guest, reporter, developer, maintainer or owner)",
+                        level, entry
+                    )
+                })?;
+            return Ok(ProjectSource::Member {
+                min_access_level: Some(min_access_level),
+            });
+        }
+        Err(format!(
+            "unknown project source '{}' (expected group:<path>, starred or member[:<access level>])",
+            entry
+        ))
+    }
+}
+
+impl fmt::Display for ProjectSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectSource::Group { path, subgroups } => {
+                write!(f, "group:{}{}", path, if *subgroups { "/*" } else { "" })
+            }
+            ProjectSource::Starred => write!(f, "starred"),
+            ProjectSource::Member {
+                min_access_level: None,
+            } => write!(f, "member"),
+            ProjectSource::Member {
+                min_access_level: Some(level),
+            } => match ACCESS_LEVELS.iter().find(|(_, value)| value == level) {
+                Some((name, _)) => write!(f, "member:{}", name),
+                None => write!(f, "member:{}", level),
+            },
+        }
+    }
+}
+
+// A project list entry that expands to every project its source finds
+#[derive(Debug, Clone)]
+pub struct DiscoveryRule {
+    pub source: ProjectSource,
+    // Glob patterns on the project path (`*` stays within one namespace level,
+    // `**` crosses them); without include patterns every project is included
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // Settings given to each discovered project; `path` is the entry itself
+    pub settings: ProjectConfig,
+    include_set: GlobSet,
+    exclude_set: GlobSet,
+}
+
+impl DiscoveryRule {
+    pub fn new(
+        settings: ProjectConfig,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<Self, String> {
+        let source = settings.path.parse()?;
+        Ok(DiscoveryRule {
+            source,
+            include_set: build_glob_set(&include)?,
+            exclude_set: build_glob_set(&exclude)?,
+            include,
+            exclude,
+            settings,
+        })
+    }
+
+    pub fn matches(&self, project_path: &str) -> bool {
+        (self.include.is_empty() || self.include_set.is_match(project_path))
+            && !self.exclude_set.is_match(project_path)
+    }
+
+    // Settings of a project found by this rule
+    fn project(&self, project_path: &str) -> ProjectConfig {
+        ProjectConfig {
+            path: project_path.to_string(),
+            display_name: None,
+            discovered_by: Some(self.settings.path.clone()),
+            ..self.settings.clone()
+        }
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern.trim())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("invalid project pattern '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("invalid project patterns: {}", e))
+}
+
+// Project list of `config` with its discovery rules resolved, and the rules
+// that could not be resolved. Listed projects take precedence over discovered
+// ones; a rule that cannot be resolved keeps the projects it found last time.
+pub async fn discover_projects(
+    state: &AppState,
+    config: &GitLabConfig,
+) -> (Vec<ProjectConfig>, Vec<FetchFailure>) {
+    let mut projects: Vec<ProjectConfig> = config
+        .projects
+        .iter()
+        .filter(|p| p.discovered_by.is_none())
+        .cloned()
+        .collect();
+    let mut known: HashSet<(String, String)> = projects
+        .iter()
+        .map(|p| (p.instance.clone(), p.path.clone()))
+        .collect();
+    let mut failures = Vec::new();
+
+    for rule in &config.discovery {
+        let instance_name = &rule.settings.instance;
+        let found = match config.instance(instance_name) {
+            Some(instance) => list_projects(state, instance, rule).await,
+            None => Err(format!("Instance {} is not configured", instance_name).into()),
+        };
+        let paths = match found {
+            Ok(paths) => {
+                eprintln!(
+                    "{} on {} matched {} project(s)",
+                    rule.settings.path,
+                    instance_name,
+                    paths.len()
+                );
+                paths
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to discover projects for {} on {}: {}",
+                    rule.settings.path, instance_name, e
+                );
+                failures.push(FetchFailure {
+                    instance: instance_name.clone(),
+                    project_path: rule.settings.path.clone(),
+                    error: e.to_string(),
+                });
+                config
+                    .projects
+                    .iter()
+                    .filter(|p| {
+                        p.instance == *instance_name
+                            && p.discovered_by.as_deref() == Some(rule.settings.path.as_str())
+                    })
+                    .map(|p| p.path.clone())
+                    .collect()
+            }
+        };
+
+        for path in paths {
+            if known.insert((instance_name.clone(), path.clone())) {
+                projects.push(rule.project(&path));
+            }
+        }
+    }
+    (projects, failures)
+}
+
+async fn list_projects(
+    state: &AppState,
+    instance: &InstanceConfig,
+    rule: &DiscoveryRule,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let items = api_get_all(state, instance, &rule.source.api_path()).await?;
+    let mut paths: Vec<String> = items
+        .iter()
+        .filter_map(|project| project["path_with_namespace"].as_str())
+        .filter(|path| rule.matches(path))
+        .map(str::to_string)
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+// Re-run discovery and swap the resulting project list into the configuration.
+// Returns the rules that could not be resolved.
+pub async fn update_discovered_projects(state: &AppState) -> Vec<FetchFailure> {
+    let config = state.current_config().await;
+    if config.discovery.is_empty() {
+        return Vec::new();
+    }
+    let (projects, failures) = discover_projects(state, &config).await;
+
+    let keep: HashSet<(String, String)> = projects
+        .iter()
+        .map(|p| (p.instance.clone(), p.path.clone()))
+        .collect();
+    state
+        .project_snapshots
+        .lock()
+        .await
+        .retain(|key, _| keep.contains(key));
+    state.config.write().await.projects = projects;
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{state::StateFiles, test_support::HttpStub};
+    use tempfile::TempDir;
+
+    fn rule(entry: &str, include: &[&str], exclude: &[&str]) -> DiscoveryRule {
+        DiscoveryRule::new(
+            ProjectConfig::new("default", entry),
+            include.iter().map(|p| p.to_string()).collect(),
+            exclude.iter().map(|p| p.to_string()).collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn single_star_stays_within_one_namespace_level() {
+        let rule = rule("group:platform/*", &["platform/*"], &[]);
+        assert!(rule.matches("platform/api"));
+        assert!(!rule.matches("platform/tools/cli"));
+        assert!(!rule.matches("other/api"));
+    }
+
+    #[test]
+    fn double_star_crosses_namespace_levels() {
+        let rule = rule("group:platform/*", &["platform/**/cli"], &[]);
+        assert!(rule.matches("platform/tools/cli"));
+        assert!(rule.matches("platform/a/b/cli"));
+        assert!(!rule.matches("platform/tools/api"));
+    }
+
+    #[test]
+    fn without_include_patterns_every_project_matches() {
+        let rule = rule("starred", &[], &[]);
+        assert!(rule.matches("anything/at/all"));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let rule = rule(
+            "member",
+            &["platform/**"],
+            &["**/*-archive", "platform/legacy"],
+        );
+        assert!(rule.matches("platform/api"));
+        assert!(!rule.matches("platform/legacy"));
+        assert!(!rule.matches("platform/tools/docs-archive"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let error = DiscoveryRule::new(
+            ProjectConfig::new("default", "starred"),
+            vec!["platform/[".to_string()],
+            Vec::new(),
+        )
+        .unwrap_err();
+        assert!(error.starts_with("invalid project pattern 'platform/['"));
+    }
+
+    #[test]
+    fn parses_project_sources() {
+        let source = |entry: &str| entry.parse::<ProjectSource>().unwrap();
+        assert_eq!(
+            source("group:platform/tools"),
+            ProjectSource::Group {
+                path: "platform/tools".to_string(),
+                subgroups: false
+            }
+        );
+        assert_eq!(
+            source(" group:platform/* "),
+            ProjectSource::Group {
+                path: "platform".to_string(),
+                subgroups: true
+            }
+        );
+        assert_eq!(source("starred"), ProjectSource::Starred);
+        assert_eq!(
+            source("member"),
+            ProjectSource::Member {
+                min_access_level: None
+            }
+        );
+        assert_eq!(
+            source("member:Developer"),
+            ProjectSource::Member {
+                min_access_level: Some(30)
+            }
+        );
+        assert_eq!(
+            source("member:35"),
+            ProjectSource::Member {
+                min_access_level: Some(35)
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_project_sources() {
+        for entry in [
+            "group:",
+            "group:/*",
+            "group:platform/*/api",
+            "member:admin",
+            "forks",
+        ] {
+            assert!(entry.parse::<ProjectSource>().is_err(), "{}", entry);
+        }
+    }
+
+    #[test]
+    fn displays_sources_as_written() {
+        for entry in [
+            "group:platform",
+            "group:platform/*",
+            "starred",
+            "member",
+            "member:maintainer",
+            "member:35",
+        ] {
+            assert_eq!(entry.parse::<ProjectSource>().unwrap().to_string(), entry);
+        }
+    }
+
+    #[test]
+    fn tells_rules_from_project_paths() {
+        assert!(ProjectSource::is_rule("group:platform"));
+        assert!(ProjectSource::is_rule("starred"));
+        assert!(ProjectSource::is_rule("member:owner"));
+        assert!(!ProjectSource::is_rule("platform/api"));
+        assert!(!ProjectSource::is_rule("members/api"));
+    }
+
+    // State keeping its files in a temporary directory, removed with it
+    fn state(config: GitLabConfig) -> (TempDir, AppState) {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::with_files(config, StateFiles::in_dir(dir.path()));
+        (dir, state)
+    }
+
+    #[tokio::test]
+    async fn expands_rules_through_the_api() {
+        let stub = HttpStub::start(|request| {
+            let projects = if request.path.starts_with("/api/v4/groups/platform/projects") {
+                r#"[{"path_with_namespace": "platform/web"},
+                    {"path_with_namespace": "platform/api"},
+                    {"path_with_namespace": "platform/legacy"}]"#
+            } else if request.path.contains("starred=true") {
+                r#"[{"path_with_namespace": "platform/api"},
+                    {"path_with_namespace": "tools/cli"}]"#
+            } else {
+                "[]"
+            };
+            (200, projects.to_string())
+        })
+        .await;
+
+        let mut config = GitLabConfig::empty();
+        config
+            .instances
+            .push(InstanceConfig::new("default", &stub.url, "token"));
+        config
+            .projects
+            .push(ProjectConfig::new("default", "platform/api"));
+        config
+            .discovery
+            .push(rule("group:platform/*", &[], &["platform/legacy"]));
+        config.discovery.push(rule("starred", &[], &[]));
+        config.discovery.push(rule("member:developer", &[], &[]));
+
+        let (_dir, state) = state(config.clone());
+        let (projects, failures) = discover_projects(&state, &config).await;
+        assert!(failures.is_empty());
+        let found: Vec<(&str, Option<&str>)> = projects
+            .iter()
+            .map(|p| (p.path.as_str(), p.discovered_by.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("platform/api", None),
+                ("platform/web", Some("group:platform/*")),
+                ("tools/cli", Some("starred")),
+            ]
+        );
+
+        let requests = stub.requests();
+        assert!(requests
+            .iter()
+            .all(|r| r.method == "GET" && r.body.is_empty()));
+        assert_eq!(requests[0].header("private-token"), Some("token"));
+        let paths: Vec<String> = requests.into_iter().map(|r| r.path).collect();
+        assert!(paths[0]
+            .starts_with("/api/v4/groups/platform/projects?include_subgroups=true&archived=false"));
+        assert!(paths[1].starts_with("/api/v4/projects?starred=true"));
+        assert!(paths[2].contains("membership=true"));
+        assert!(paths[2].contains("min_access_level=30"));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_previous_projects_when_a_rule_fails() {
+        let stub = HttpStub::start(|_| (500, "{}".to_string())).await;
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 0;
+        config
+            .instances
+            .push(InstanceConfig::new("default", &stub.url, "token"));
+        let rule = rule("group:platform", &[], &[]);
+        config.projects.push(rule.project("platform/api"));
+        config.discovery.push(rule);
+
+        let (_dir, state) = state(config.clone());
+        let (projects, failures) = discover_projects(&state, &config).await;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].path, "platform/api");
+        assert_eq!(projects[0].discovered_by.as_deref(), Some("group:platform"));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].project_path, "group:platform");
+        assert_eq!(
+            failures[0].error,
+            "Failed to list groups/platform/projects?include_subgroups=false&archived=false&simple=true: 500 Internal Server Error"
+        );
+    }
+}
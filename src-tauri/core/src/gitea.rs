@@ -0,0 +1,150 @@
+use reqwest::RequestBuilder;
+
+use crate::{
+    config::InstanceConfig,
+    github::{parse_releases, repository_info},
+    gitlab::ProjectReleases,
+    models::ProjectInfo,
+    sources::{
+        fetch_linked_pages, get_json, lookup_result, owner_and_repo, ReleaseSource, SourceContext,
+    },
+};
+
+// Gitea caps page sizes at 50 by default
+const MAX_PAGE_SIZE: u32 = 50;
+
+// The Gitea (and Forgejo) releases API, which mirrors GitHub's
+pub struct GiteaSource;
+
+impl ReleaseSource for GiteaSource {
+    fn authorize(&self, request: RequestBuilder, instance: &InstanceConfig) -> RequestBuilder {
+        if instance.api_token.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("token {}", instance.api_token))
+        }
+    }
+
+    async fn fetch_releases(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectReleases, Box<dyn std::error::Error + Send + Sync>> {
+        let (owner, repo) = owner_and_repo(project_path)?;
+        let url = format!(
+            "{}/api/v1/repos/{}/{}/releases?limit={}",
+            ctx.instance.base_url,
+            owner,
+            repo,
+            ctx.config.per_page.min(MAX_PAGE_SIZE)
+        );
+        fetch_linked_pages(self, ctx, project_path, url, parse_releases).await
+    }
+
+    async fn lookup_project(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectInfo, Box<dyn std::error::Error + Send + Sync>> {
+        let (owner, repo) = owner_and_repo(project_path)?;
+        let url = format!("{}/api/v1/repos/{}/{}", ctx.instance.base_url, owner, repo);
+        let project = lookup_result(ctx, project_path, get_json(self, ctx, &url).await?)?;
+        Ok(repository_info(ctx, project_path, &project))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReleaseCache,
+        config::GitLabConfig,
+        rate_limit::RateLimiter,
+        test_support::{HttpStub, StubResponse},
+    };
+    use tokio::sync::Mutex;
+
+    // Two pages as Gitea serves them, linked by a `Link` header
+    const FIRST_PAGE: &str = r#"[
+        {"id": 3, "tag_name": "v2.0.0", "name": "Draft", "body": "", "draft": true,
+         "prerelease": false, "created_at": "2026-10-05T10:00:00+02:00", "published_at": null,
+         "html_url": "https://gitea.example.com/octo/app/releases/tag/v2.0.0",
+         "author": {"login": "octo"}},
+        {"id": 2, "tag_name": "v1.1.0-beta", "name": "", "body": "Beta", "draft": false,
+         "prerelease": true, "created_at": "2026-10-02T10:00:00+02:00",
+         "published_at": "2026-10-03T09:00:00+02:00",
+         "html_url": "https://gitea.example.com/octo/app/releases/tag/v1.1.0-beta",
+         "author": {"login": "octo"}}
+    ]"#;
+    const SECOND_PAGE: &str = r#"[
+        {"id": 1, "tag_name": "v1.0.0", "name": "First", "body": "Notes", "draft": false,
+         "prerelease": false, "created_at": "2026-10-01T10:00:00+02:00",
+         "published_at": "2026-10-01T10:00:00+02:00",
+         "html_url": "https://gitea.example.com/octo/app/releases/tag/v1.0.0",
+         "author": {"login": "octo"}}
+    ]"#;
+
+    #[tokio::test]
+    async fn fetches_and_parses_releases() {
+        let stub = HttpStub::start(|request| {
+            if request.path.contains("page=2") {
+                StubResponse::new(200, SECOND_PAGE)
+            } else {
+                StubResponse::new(200, FIRST_PAGE).header(
+                    "Link",
+                    "</api/v1/repos/octo/app/releases?limit=50&page=2>; rel=\"next\"",
+                )
+            }
+        })
+        .await;
+        let mut config = GitLabConfig::empty();
+        config.per_page = 100;
+        config.max_retries = 0;
+        let instance = InstanceConfig::new("gitea", &stub.url, "secret");
+        let client = reqwest::Client::new();
+        let cache = Mutex::new(ReleaseCache::default());
+        let limiter = RateLimiter::new();
+        let ctx = SourceContext {
+            client: &client,
+            config: &config,
+            instance: &instance,
+            cache: &cache,
+            limiter: &limiter,
+        };
+
+        let fetched = GiteaSource.fetch_releases(&ctx, "octo/app").await.unwrap();
+        let tags: Vec<_> = fetched
+            .releases
+            .iter()
+            .map(|r| r.tag_name.as_str())
+            .collect();
+        assert_eq!(tags, ["v1.1.0-beta", "v1.0.0"], "the draft is skipped");
+
+        let beta = &fetched.releases[0];
+        assert_eq!(beta.instance, "gitea");
+        assert_eq!(beta.project_path, "octo/app");
+        assert_eq!(beta.name, "v1.1.0-beta", "an empty name shows the tag");
+        assert!(beta.prerelease);
+        assert_eq!(
+            beta.created_at,
+            "2026-10-02T08:00:00Z"
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+        );
+        assert_eq!(
+            beta.released_at,
+            Some("2026-10-03T07:00:00Z".parse().unwrap())
+        );
+
+        let first = &fetched.releases[1];
+        assert_eq!(first.name, "First");
+        assert_eq!(first.description, "Notes");
+        assert!(!first.prerelease);
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        // Page sizes above Gitea's default maximum are capped
+        assert_eq!(requests[0].path, "/api/v1/repos/octo/app/releases?limit=50");
+        assert_eq!(requests[0].header("authorization"), Some("token secret"));
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use reqwest::RequestBuilder;
+
+use crate::{
+    config::InstanceConfig,
+    gitlab::ProjectReleases,
+    models::{GitLabRelease, ProjectInfo},
+    sources::{
+        fetch_linked_pages, get_json, lookup_result, owner_and_repo, ReleaseSource, SourceContext,
+    },
+    version::ReleaseVersion,
+};
+
+// The GitHub releases API (github.com, or GitHub Enterprise Server with
+// `base_url` set to its `https://host/api/v3`)
+pub struct GitHubSource;
+
+impl ReleaseSource for GitHubSource {
+    fn authorize(&self, request: RequestBuilder, instance: &InstanceConfig) -> RequestBuilder {
+        let request = request
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        // Public repositories can be read without a token, at a lower rate limit
+        if instance.api_token.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&instance.api_token)
+        }
+    }
+
+    async fn fetch_releases(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectReleases, Box<dyn std::error::Error + Send + Sync>> {
+        let (owner, repo) = owner_and_repo(project_path)?;
+        let url = format!(
+            "{}/repos/{}/{}/releases?per_page={}",
+            ctx.instance.base_url, owner, repo, ctx.config.per_page
+        );
+        fetch_linked_pages(self, ctx, project_path, url, parse_releases).await
+    }
+
+    async fn lookup_project(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectInfo, Box<dyn std::error::Error + Send + Sync>> {
+        let (owner, repo) = owner_and_repo(project_path)?;
+        let url = format!("{}/repos/{}/{}", ctx.instance.base_url, owner, repo);
+        let project = lookup_result(ctx, project_path, get_json(self, ctx, &url).await?)?;
+        Ok(repository_info(ctx, project_path, &project))
+    }
+}
+
+// Project info from a repository object, which GitHub and Gitea share
+pub fn repository_info(
+    ctx: &SourceContext<'_>,
+    project_path: &str,
+    repository: &serde_json::Value,
+) -> ProjectInfo {
+    ProjectInfo {
+        instance: ctx.instance.name.clone(),
+        path: repository["full_name"]
+            .as_str()
+            .unwrap_or(project_path)
+            .to_string(),
+        name: repository["name"]
+            .as_str()
+            .unwrap_or(project_path)
+            .to_string(),
+        web_url: repository["html_url"].as_str().unwrap_or("").to_string(),
+    }
+}
+
+// Convert one page of the releases API response into our model. Gitea answers
+// in the same format. Drafts are only visible to maintainers and are skipped.
+pub fn parse_releases(
+    instance: &str,
+    project_path: &str,
+    releases_json: Vec<serde_json::Value>,
+) -> Result<Vec<GitLabRelease>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut releases = Vec::new();
+    for r in releases_json {
+        if r.get("draft").and_then(|v| v.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        if let (Some(tag_name), Some(created_at)) = (
+            r.get("tag_name").and_then(|v| v.as_str()),
+            r.get("created_at").and_then(|v| v.as_str()),
+        ) {
+            let created = DateTime::parse_from_rfc3339(created_at)?.with_timezone(&Utc);
+            let published_at = r
+                .get("published_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+
+            releases.push(GitLabRelease {
+                instance: instance.to_string(),
+                project_name: project_path
+                    .split('/')
+                    .next_back()
+                    .unwrap_or(project_path)
+                    .to_string(),
+                project_path: project_path.to_string(),
+                tag_name: tag_name.to_string(),
+                // Releases without a title show the tag on GitHub
+                name: r
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(tag_name)
+                    .to_string(),
+                description: r
+                    .get("body")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                created_at: created,
+                released_at: published_at,
+                web_url: r
+                    .get("html_url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                tag_family: String::new(),
+                version: ReleaseVersion::parse(tag_name),
+                upcoming_release: false,
+                prerelease: r
+                    .get("prerelease")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                pipeline: None,
+                environments: Vec::new(),
+            });
+        }
+    }
+
+    Ok(releases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReleaseCache,
+        config::GitLabConfig,
+        rate_limit::RateLimiter,
+        test_support::{HttpStub, StubResponse},
+    };
+    use serde_json::json;
+    use tokio::sync::Mutex;
+
+    #[test]
+    fn parses_a_releases_page() {
+        let page = vec![
+            json!({
+                "tag_name": "v2.0.0-rc.1",
+                "name": "",
+                "body": "Release candidate",
+                "draft": false,
+                "prerelease": true,
+                "created_at": "2026-10-03T10:00:00Z",
+                "published_at": "2026-10-04T08:30:00Z",
+                "html_url": "https://github.com/octo/app/releases/tag/v2.0.0-rc.1"
+            }),
+            json!({
+                "tag_name": "v2.0.0",
+                "name": "Next",
+                "draft": true,
+                "prerelease": false,
+                "created_at": "2026-10-05T10:00:00Z",
+                "published_at": null
+            }),
+            json!({
+                "tag_name": "v1.0.0",
+                "name": "First",
+                "created_at": "2026-10-01T10:00:00Z",
+                "published_at": null,
+                "html_url": "https://github.com/octo/app/releases/tag/v1.0.0"
+            }),
+        ];
+
+        let releases = parse_releases("github", "octo/app", page).unwrap();
+        assert_eq!(releases.len(), 2, "the draft is skipped");
+
+        let candidate = &releases[0];
+        assert_eq!(candidate.instance, "github");
+        assert_eq!(candidate.project_name, "app");
+        assert_eq!(candidate.tag_name, "v2.0.0-rc.1");
+        assert_eq!(candidate.name, "v2.0.0-rc.1", "an empty name shows the tag");
+        assert_eq!(candidate.description, "Release candidate");
+        assert!(candidate.prerelease);
+        assert_eq!(
+            candidate.created_at,
+            "2026-10-03T10:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            candidate.released_at,
+            Some("2026-10-04T08:30:00Z".parse().unwrap())
+        );
+
+        let first = &releases[1];
+        assert_eq!(first.name, "First");
+        assert!(!first.prerelease);
+        assert_eq!(first.released_at, None);
+        assert_eq!(first.description, "");
+        assert_eq!(
+            first.web_url,
+            "https://github.com/octo/app/releases/tag/v1.0.0"
+        );
+    }
+
+    #[test]
+    fn skips_releases_without_a_tag_or_date() {
+        let page = vec![
+            json!({"name": "No tag", "created_at": "2026-10-01T10:00:00Z"}),
+            json!({"tag_name": "v1.0.0"}),
+        ];
+        assert!(parse_releases("github", "octo/app", page)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn follows_link_headers_across_pages() {
+        let stub = HttpStub::start(|request| {
+            if request.path.contains("page=2") {
+                StubResponse::new(
+                    200,
+                    r#"[{"tag_name": "v1.0.0", "created_at": "2026-10-01T10:00:00Z"}]"#,
+                )
+            } else {
+                StubResponse::new(
+                    200,
+                    r#"[{"tag_name": "v1.1.0", "created_at": "2026-10-02T10:00:00Z"}]"#,
+                )
+                .header(
+                    "Link",
+                    "</repos/octo/app/releases?per_page=1&page=2>; rel=\"next\", \
+                     </repos/octo/app/releases?per_page=1&page=2>; rel=\"last\"",
+                )
+            }
+        })
+        .await;
+        let mut config = GitLabConfig::empty();
+        config.per_page = 1;
+        config.max_retries = 0;
+        let instance = InstanceConfig::new("github", &stub.url, "secret");
+        let client = reqwest::Client::new();
+        let cache = Mutex::new(ReleaseCache::default());
+        let limiter = RateLimiter::new();
+        let ctx = SourceContext {
+            client: &client,
+            config: &config,
+            instance: &instance,
+            cache: &cache,
+            limiter: &limiter,
+        };
+
+        let fetched = GitHubSource.fetch_releases(&ctx, "octo/app").await.unwrap();
+        let tags: Vec<_> = fetched
+            .releases
+            .iter()
+            .map(|r| r.tag_name.as_str())
+            .collect();
+        assert_eq!(tags, ["v1.1.0", "v1.0.0"]);
+        assert!(!fetched.truncated);
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/repos/octo/app/releases?per_page=1");
+        assert_eq!(
+            requests[1].path,
+            "/repos/octo/app/releases?per_page=1&page=2"
+        );
+        assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+        assert_eq!(
+            requests[0].header("accept"),
+            Some("application/vnd.github+json")
+        );
+    }
+}
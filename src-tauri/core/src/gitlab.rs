@@ -0,0 +1,1028 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, HeaderName, LINK};
+use reqwest::{RequestBuilder, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use urlencoding;
+
+pub use crate::grouping::extract_tag_attribute;
+
+use crate::{
+    config::{GitLabConfig, InstanceConfig, ProjectConfig},
+    deployments::attach_deployments,
+    models::{
+        Deployment, FetchFailure, FieldChange, GitLabRelease, Pipeline, ProjectInfo,
+        ReleaseChangeSet, RemovedRelease, UpdatedRelease,
+    },
+    pipelines::attach_pipelines,
+    rate_limit::send_with_retry,
+    seen::SeenReleases,
+    sources::{
+        fetch_linked_pages, get_json, lookup_result, PageRejected, Provider, ReleaseSource,
+        SourceContext,
+    },
+    state::AppState,
+    version::{LatestBy, ReleaseChannel, ReleaseVersion},
+};
+
+// Tolerance when deciding whether a project is due, so a project whose poll
+// interval equals the poller tick is not skipped because of timer jitter
+const SCHEDULE_SLACK: Duration = Duration::from_secs(5);
+
+// Base URLs whose releases endpoint rejected keyset pagination; we fall back to
+// offset pagination for them instead of re-trying keyset on every refresh.
+static KEYSET_UNSUPPORTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn keyset_supported(config: &GitLabConfig, instance: &InstanceConfig) -> bool {
+    config.keyset_pagination
+        && !KEYSET_UNSUPPORTED
+            .lock()
+            .map(|set| set.contains(&instance.base_url))
+            .unwrap_or(false)
+}
+
+fn mark_keyset_unsupported(instance: &InstanceConfig) {
+    if let Ok(mut set) = KEYSET_UNSUPPORTED.lock() {
+        set.insert(instance.base_url.clone());
+    }
+}
+
+// HTTP client for an instance, honouring its TLS settings
+pub fn build_client(
+    instance: &InstanceConfig,
+) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
+    // GitHub rejects requests without a User-Agent
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("gitlab-monitor/", env!("CARGO_PKG_VERSION")))
+        .danger_accept_invalid_certs(instance.accept_invalid_certs);
+    if let Some(path) = &instance.ca_cert {
+        let pem = std::fs::read(path)
+            .map_err(|e| format!("Failed to read CA certificate {}: {}", path.display(), e))?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| format!("Invalid CA certificate {}: {}", path.display(), e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder.build()?)
+}
+
+// Client for an instance from `state`, (re)built when the instance settings
+// differ from the ones it was built with
+pub async fn instance_client(
+    state: &AppState,
+    instance: &InstanceConfig,
+) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
+    let mut clients = state.clients.lock().await;
+    if let Some((built_for, client)) = clients.get(&instance.name) {
+        if built_for == instance {
+            return Ok(client.clone());
+        }
+    }
+    let client = build_client(instance)?;
+    clients.insert(instance.name.clone(), (instance.clone(), client.clone()));
+    Ok(client)
+}
+
+// Build the URL of the first releases page for a project
+fn releases_url(
+    config: &GitLabConfig,
+    instance: &InstanceConfig,
+    project_path: &str,
+    keyset: bool,
+) -> String {
+    let encoded_project = urlencoding::encode(project_path);
+    let mut url = format!(
+        "{}/api/v4/projects/{}/releases?per_page={}&order_by=created_at&sort=desc",
+        instance.base_url, encoded_project, config.per_page
+    );
+    if keyset {
+        url.push_str("&pagination=keyset");
+    }
+    url
+}
+
+// Work out the URL of the next page from the response headers.
+// Keyset responses only carry a `Link: <...>; rel="next"` header, offset responses
+// carry both that and `X-Next-Page`, so the Link header is preferred.
+pub fn next_page_url(headers: &HeaderMap, current_url: &str) -> Option<String> {
+    if let Some(link) = headers.get(LINK).and_then(|v| v.to_str().ok()) {
+        for part in link.split(',') {
+            let mut sections = part.split(';');
+            let target = sections.next().unwrap_or("").trim();
+            // `rel` may list several relation types, e.g. rel="next last"
+            let is_next = sections.any(|p| {
+                p.trim().strip_prefix("rel=").is_some_and(|rels| {
+                    rels.trim_matches('"')
+                        .split_whitespace()
+                        .any(|r| r == "next")
+                })
+            });
+            if is_next && target.starts_with('<') && target.ends_with('>') {
+                // Targets may be relative to the current page
+                let target = &target[1..target.len() - 1];
+                return match reqwest::Url::parse(current_url).and_then(|url| url.join(target)) {
+                    Ok(url) => Some(url.to_string()),
+                    Err(_) => Some(target.to_string()),
+                };
+            }
+        }
+    }
+
+    let next_page = headers
+        .get("x-next-page")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())?;
+
+    let mut url = reqwest::Url::parse(current_url).ok()?;
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "page")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("page", next_page);
+    Some(url.to_string())
+}
+
+pub fn header_string(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+// Convert one page of the releases API response into our model
+fn parse_releases(
+    instance: &str,
+    project_path: &str,
+    releases_json: Vec<serde_json::Value>,
+) -> Result<Vec<GitLabRelease>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut releases = Vec::new();
+    for r in releases_json {
+        if let (Some(tag_name), Some(name), Some(created_at)) = (
+            r.get("tag_name").and_then(|v| v.as_str()),
+            r.get("name").and_then(|v| v.as_str()),
+            r.get("created_at").and_then(|v| v.as_str()),
+        ) {
+            let created = DateTime::parse_from_rfc3339(created_at)?.with_timezone(&Utc);
+            let released_at = r
+                .get("released_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+
+            releases.push(GitLabRelease {
+                instance: instance.to_string(),
+                project_name: project_path
+                    .split('/')
+                    .next_back()
+                    .unwrap_or(project_path)
+                    .to_string(),
+                project_path: project_path.to_string(),
+                tag_name: tag_name.to_string(),
+                name: name.to_string(),
+                description: r
+                    .get("description")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                created_at: created,
+                released_at,
+                web_url: r
+                    .get("_links")
+                    .and_then(|l| l.get("self"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                tag_family: String::new(),
+                version: ReleaseVersion::parse(tag_name),
+                upcoming_release: r
+                    .get("upcoming_release")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                prerelease: false,
+                pipeline: None,
+                environments: Vec::new(),
+            });
+        }
+    }
+
+    Ok(releases)
+}
+
+// Releases of one project; `truncated` is set when the page cap was hit, in which
+// case older releases are missing from `releases`
+#[derive(Debug, Clone)]
+pub struct ProjectReleases {
+    pub releases: Vec<GitLabRelease>,
+    pub truncated: bool,
+    // Latest pipeline on the default branch, for projects with `branch_pipeline`
+    pub pipeline: Option<Pipeline>,
+    // What is deployed to each environment, for projects with `deployments`
+    pub deployments: Vec<Deployment>,
+}
+
+// Last successful fetch of a project, reused until its poll interval elapses
+#[derive(Debug, Clone)]
+pub struct ProjectSnapshot {
+    pub fetched_at: Instant,
+    pub releases: ProjectReleases,
+}
+
+// Releases of all configured projects from one refresh
+#[derive(Debug, Clone, Default)]
+pub struct FetchedReleases {
+    // Every release, newest first
+    pub releases: Vec<GitLabRelease>,
+    // (instance, project path) of projects whose full release list was fetched;
+    // only these can report removals
+    pub complete_projects: HashSet<(String, String)>,
+    // Latest default branch pipeline per (instance, project path) watching it
+    pub branch_pipelines: HashMap<(String, String), Pipeline>,
+    // Current deployment per environment of the projects tracking them
+    pub deployments: Vec<Deployment>,
+    // Projects that could not be fetched; their releases are those of their
+    // last successful fetch, if any
+    pub failures: Vec<FetchFailure>,
+}
+
+// The GitLab releases API
+pub struct GitLabSource;
+
+impl ReleaseSource for GitLabSource {
+    fn authorize(&self, request: RequestBuilder, instance: &InstanceConfig) -> RequestBuilder {
+        request.header("PRIVATE-TOKEN", &instance.api_token)
+    }
+
+    // Follows pagination, returning all releases up to `config.max_pages` pages.
+    // Keyset pagination is used where the server supports it.
+    async fn fetch_releases(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectReleases, Box<dyn std::error::Error + Send + Sync>> {
+        let keyset = keyset_supported(ctx.config, ctx.instance);
+        let url = releases_url(ctx.config, ctx.instance, project_path, keyset);
+        match fetch_linked_pages(self, ctx, project_path, url, parse_releases).await {
+            Err(e)
+                if keyset
+                    && e.downcast_ref::<PageRejected>().is_some_and(|rejected| {
+                        rejected.first_page
+                            && (rejected.status == StatusCode::BAD_REQUEST
+                                || rejected.status == StatusCode::METHOD_NOT_ALLOWED)
+                    }) =>
+            {
+                // Server does not support keyset pagination for releases
+                mark_keyset_unsupported(ctx.instance);
+                let url = releases_url(ctx.config, ctx.instance, project_path, false);
+                fetch_linked_pages(self, ctx, project_path, url, parse_releases).await
+            }
+            result => result,
+        }
+    }
+
+    async fn lookup_project(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> Result<ProjectInfo, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!(
+            "{}/api/v4/projects/{}",
+            ctx.instance.base_url,
+            urlencoding::encode(project_path)
+        );
+        let project = lookup_result(ctx, project_path, get_json(self, ctx, &url).await?)?;
+        Ok(ProjectInfo {
+            instance: ctx.instance.name.clone(),
+            path: project["path_with_namespace"]
+                .as_str()
+                .unwrap_or(project_path)
+                .to_string(),
+            name: project["name"].as_str().unwrap_or(project_path).to_string(),
+            web_url: project["web_url"].as_str().unwrap_or("").to_string(),
+        })
+    }
+}
+
+// Keep the latest release of each (instance, project, tag family); `releases` must be
+// sorted newest first. "Latest" is the newest by date or the highest version,
+// depending on the project's `latest_by` setting. The tag family is recorded on
+// the returned releases.
+pub fn filter_latest_releases(
+    releases: &[GitLabRelease],
+    config: &GitLabConfig,
+) -> Vec<GitLabRelease> {
+    let mut settings: HashMap<(&str, &str), ProjectConfig> = HashMap::new();
+    let mut latest_index: HashMap<(String, String, String), usize> = HashMap::new();
+    let mut filtered: Vec<GitLabRelease> = Vec::new();
+
+    for release in releases {
+        let project = settings
+            .entry((release.instance.as_str(), release.project_path.as_str()))
+            .or_insert_with(|| config.project_settings(&release.instance, &release.project_path));
+        let family = project.tag_grouping.family(&release.tag_name);
+        let key = (
+            release.instance.clone(),
+            release.project_path.clone(),
+            family.clone(),
+        );
+
+        match latest_index.get(&key) {
+            None => {
+                latest_index.insert(key, filtered.len());
+                let mut latest = release.clone();
+                latest.tag_family = family;
+                filtered.push(latest);
+            }
+            Some(&index) => {
+                // Releases without a version rank lowest; on equal versions the
+                // newer release (seen first) wins
+                if project.latest_by == LatestBy::Version
+                    && release.version > filtered[index].version
+                {
+                    let mut latest = release.clone();
+                    latest.tag_family = family;
+                    filtered[index] = latest;
+                }
+            }
+        }
+    }
+
+    filtered
+}
+
+// Releases in `current` that have never been seen before
+pub fn detect_new_releases(current: &[GitLabRelease], seen: &SeenReleases) -> Vec<GitLabRelease> {
+    current
+        .iter()
+        .filter(|release| {
+            !seen.contains(&release.instance, &release.project_path, &release.tag_name)
+        })
+        .cloned()
+        .collect()
+}
+
+fn format_released_at(value: Option<DateTime<Utc>>) -> String {
+    value.map(|dt| dt.to_rfc3339()).unwrap_or_default()
+}
+
+// Compare a refresh against the seen state. Projects seen for the first time are
+// not reported (their history is not "new"), and removals are only reported for
+// projects in `complete_projects`, since a failed or truncated fetch would
+// otherwise look like deleted releases. Seen releases outside the project's
+// release channel are not reported as removed either.
+pub fn detect_release_changes(
+    current: &[GitLabRelease],
+    seen: &SeenReleases,
+    complete_projects: &HashSet<(String, String)>,
+    config: &GitLabConfig,
+) -> ReleaseChangeSet {
+    let mut changes = ReleaseChangeSet::default();
+
+    for release in current {
+        match seen.get(&release.instance, &release.project_path, &release.tag_name) {
+            None => {
+                if seen.has_project(&release.instance, &release.project_path) {
+                    changes.added.push(release.clone());
+                }
+            }
+            Some(previous) => {
+                // Fields missing from an older snapshot count as unchanged
+                let mut field_changes = Vec::new();
+                if let Some(name) = previous.name.as_ref().filter(|n| **n != release.name) {
+                    field_changes.push(FieldChange {
+                        field: "name".to_string(),
+                        old: name.clone(),
+                        new: release.name.clone(),
+                    });
+                }
+                if let Some(description) = previous
+                    .description
+                    .as_ref()
+                    .filter(|d| **d != release.description)
+                {
+                    field_changes.push(FieldChange {
+                        field: "description".to_string(),
+                        old: description.clone(),
+                        new: release.description.clone(),
+                    });
+                }
+                if let Some(released_at) =
+                    previous.released_at.filter(|r| *r != release.released_at)
+                {
+                    field_changes.push(FieldChange {
+                        field: "released_at".to_string(),
+                        old: format_released_at(released_at),
+                        new: format_released_at(release.released_at),
+                    });
+                }
+                if !field_changes.is_empty() {
+                    changes.updated.push(UpdatedRelease {
+                        release: release.clone(),
+                        changes: field_changes,
+                    });
+                }
+            }
+        }
+    }
+
+    let current_keys: HashSet<(&str, &str, &str)> = current
+        .iter()
+        .map(|r| {
+            (
+                r.instance.as_str(),
+                r.project_path.as_str(),
+                r.tag_name.as_str(),
+            )
+        })
+        .collect();
+    let mut projects: Vec<&(String, String)> = complete_projects.iter().collect();
+    projects.sort();
+    for (instance, project_path) in projects {
+        let channel = config.project_settings(instance, project_path).channel;
+        let mut removed: Vec<RemovedRelease> = seen
+            .project_releases(instance, project_path)
+            .filter(|prev| channel.admits(prev.prerelease))
+            .filter(|prev| {
+                !current_keys.contains(&(
+                    prev.instance.as_str(),
+                    prev.project_path.as_str(),
+                    prev.tag_name.as_str(),
+                ))
+            })
+            .map(|prev| RemovedRelease {
+                instance: prev.instance.clone(),
+                project_path: prev.project_path.clone(),
+                tag_name: prev.tag_name.clone(),
+                name: prev.name.clone().unwrap_or_default(),
+            })
+            .collect();
+        removed.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+        changes.removed.extend(removed);
+    }
+
+    changes
+}
+
+// Keep only releases admitted by their project's release channel
+pub fn apply_release_channels(releases: &mut Vec<GitLabRelease>, config: &GitLabConfig) {
+    let mut channels: HashMap<(String, String), ReleaseChannel> = HashMap::new();
+    releases.retain(|release| {
+        channels
+            .entry((release.instance.clone(), release.project_path.clone()))
+            .or_insert_with(|| {
+                config
+                    .project_settings(&release.instance, &release.project_path)
+                    .channel
+            })
+            .admits(release.is_prerelease())
+    });
+}
+
+// GET an API v4 endpoint of an instance (`api_path` without the `/api/v4/`
+// prefix). The body is only parsed for successful responses, otherwise it is null.
+pub async fn api_get(
+    state: &AppState,
+    instance: &InstanceConfig,
+    api_path: &str,
+) -> Result<(StatusCode, serde_json::Value), Box<dyn std::error::Error + Send + Sync>> {
+    let config = state.current_config().await;
+    let client = instance_client(state, instance).await?;
+    let request = client
+        .get(format!("{}/api/v4/{}", instance.base_url, api_path))
+        .header("PRIVATE-TOKEN", &instance.api_token)
+        .timeout(Duration::from_secs(config.request_timeout_secs));
+    let limiter = state.rate_limiter(&instance.name).await;
+    let response = send_with_retry(request, &limiter, config.max_retries).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Ok((status, serde_json::Value::Null));
+    }
+    Ok((status, response.json().await?))
+}
+
+// GET every page of an API v4 list endpoint, up to `max_pages` pages
+pub async fn api_get_all(
+    state: &AppState,
+    instance: &InstanceConfig,
+    api_path: &str,
+) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = state.current_config().await;
+    let client = instance_client(state, instance).await?;
+    let separator = if api_path.contains('?') { '&' } else { '?' };
+    let mut url = format!(
+        "{}/api/v4/{}{}per_page={}",
+        instance.base_url, api_path, separator, config.per_page
+    );
+    let limiter = state.rate_limiter(&instance.name).await;
+    let mut items = Vec::new();
+
+    for _ in 0..config.max_pages {
+        let request = client
+            .get(&url)
+            .header("PRIVATE-TOKEN", &instance.api_token)
+            .timeout(Duration::from_secs(config.request_timeout_secs));
+        let response = send_with_retry(request, &limiter, config.max_retries).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Failed to list {}: {}", api_path, status).into());
+        }
+        let next_url = next_page_url(response.headers(), &url);
+        let page: Vec<serde_json::Value> = response.json().await?;
+        items.extend(page);
+
+        match next_url {
+            Some(next_url) => url = next_url,
+            None => return Ok(items),
+        }
+    }
+
+    eprintln!(
+        "Stopped listing {} after {} pages (GITLAB_MAX_PAGES)",
+        api_path, config.max_pages
+    );
+    Ok(items)
+}
+
+// Check that a project exists on an instance and the token can read it
+pub async fn lookup_project(
+    state: &AppState,
+    instance_name: &str,
+    project_path: &str,
+) -> Result<ProjectInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let config = state.current_config().await;
+    let instance = config
+        .instance(instance_name)
+        .ok_or_else(|| format!("Unknown instance '{}'", instance_name))?;
+    if instance.provider == Provider::GitLab && instance.api_token.is_empty() {
+        return Err(format!("No API token is set for instance '{}'", instance.name).into());
+    }
+    let client = instance_client(state, instance).await?;
+    let limiter = state.rate_limiter(&instance.name).await;
+    let ctx = SourceContext {
+        client: &client,
+        config: &config,
+        instance,
+        cache: &state.release_cache,
+        limiter: &limiter,
+    };
+    instance
+        .provider
+        .lookup_project(&ctx, project_path.trim().trim_matches('/'))
+        .await
+}
+
+// Reduce a refresh to the latest release per tag family, sorted by project name
+pub fn latest_releases(releases: &[GitLabRelease], config: &GitLabConfig) -> Vec<GitLabRelease> {
+    let mut filtered_releases = filter_latest_releases(releases, config);
+
+    // Sort alphabetically by project name
+    filtered_releases.sort_by(|a, b| a.project_name.cmp(&b.project_name));
+
+    filtered_releases
+}
+
+// Fetch the latest releases for configured projects. Projects that cannot be
+// fetched are logged and left out.
+pub async fn fetch_all_releases(
+    state: &AppState,
+) -> Result<Vec<GitLabRelease>, Box<dyn std::error::Error + Send + Sync>> {
+    let fetched = fetch_releases(state, true).await;
+    Ok(latest_releases(
+        &fetched.releases,
+        &state.current_config().await,
+    ))
+}
+
+// Fetch every release of the configured projects.
+// Projects are fetched concurrently (at most `max_concurrency` at a time); whatever
+// has not finished when the refresh deadline passes is dropped from this refresh.
+// Unless `force` is set, projects polled more recently than their poll interval
+// reuse their last result. A project that fails keeps its last known releases
+// but is not considered complete.
+pub async fn fetch_releases(state: &AppState, force: bool) -> FetchedReleases {
+    let config = Arc::new(state.current_config().await);
+    let snapshots = state.project_snapshots.lock().await.clone();
+    let semaphore = Arc::new(Semaphore::new(config.max_concurrency));
+    let mut tasks = JoinSet::new();
+    let mut results = HashMap::new();
+
+    let mut clients = HashMap::new();
+    for instance in &config.instances {
+        match instance_client(state, instance).await {
+            Ok(client) => {
+                clients.insert(instance.name.clone(), client);
+            }
+            Err(e) => eprintln!("Cannot connect to instance {}: {}", instance.name, e),
+        }
+    }
+
+    let mut under_pressure = HashSet::new();
+    for (index, project) in config.projects.iter().enumerate() {
+        let key = (project.instance.clone(), project.path.clone());
+        let interval = Duration::from_secs(project.poll_interval_secs);
+        let limiter = state.rate_limiter(&project.instance).await;
+        if let Some(snapshot) = snapshots.get(&key) {
+            if !force && snapshot.fetched_at.elapsed() + SCHEDULE_SLACK < interval {
+                results.insert(index, Ok(snapshot.releases.clone()));
+                continue;
+            }
+            // An instance that reported a nearly exhausted quota is left alone
+            // until it resets; the other instances are polled as usual
+            if let Some(delay) = limiter.pressure_delay().filter(|_| !force) {
+                if under_pressure.insert(project.instance.clone()) {
+                    eprintln!(
+                        "Instance {} is rate limiting, skipping its projects for {}s",
+                        project.instance,
+                        delay.as_secs()
+                    );
+                }
+                results.insert(index, Ok(snapshot.releases.clone()));
+                continue;
+            }
+        }
+
+        let (Some(instance), Some(client)) = (
+            config.instance(&project.instance).cloned(),
+            clients.get(&project.instance).cloned(),
+        ) else {
+            results.insert(
+                index,
+                Err(format!("Instance {} is not available", project.instance).into()),
+            );
+            continue;
+        };
+
+        // Pipelines and deployments are kept if they cannot be fetched this time
+        let previous = snapshots
+            .get(&key)
+            .map(|snapshot| snapshot.releases.clone());
+        let project = project.clone();
+        let config = config.clone();
+        let cache = state.release_cache.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let ctx = SourceContext {
+                client: &client,
+                config: &config,
+                instance: &instance,
+                cache: &cache,
+                limiter: &limiter,
+            };
+            let mut result = instance.provider.fetch_releases(&ctx, &project.path).await;
+            if let Ok(fetched) = &mut result {
+                attach_pipelines(&ctx, &project, fetched, previous.as_ref()).await;
+                let previous_deployments = previous.as_ref().map(|p| p.deployments.as_slice());
+                attach_deployments(
+                    &ctx,
+                    &project,
+                    fetched,
+                    previous_deployments.unwrap_or_default(),
+                )
+                .await;
+            }
+            (index, result)
+        });
+    }
+
+    let started_at = Instant::now();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(config.refresh_timeout_secs);
+    let mut completed = true;
+    loop {
+        match tokio::time::timeout_at(deadline, tasks.join_next()).await {
+            Ok(Some(Ok((index, result)))) => {
+                if let Ok(project_releases) = &result {
+                    let project = &config.projects[index];
+                    if project_releases.releases.is_empty() {
+                        eprintln!("No releases found for project: {}", project.path);
+                    }
+                    state.project_snapshots.lock().await.insert(
+                        (project.instance.clone(), project.path.clone()),
+                        ProjectSnapshot {
+                            fetched_at: started_at,
+                            releases: project_releases.clone(),
+                        },
+                    );
+                }
+                results.insert(index, result);
+            }
+            Ok(Some(Err(e))) => eprintln!("Release fetch task failed: {}", e),
+            Ok(None) => break,
+            Err(_) => {
+                eprintln!(
+                    "Refresh deadline of {}s exceeded, skipping {} unfinished project(s)",
+                    config.refresh_timeout_secs,
+                    tasks.len()
+                );
+                tasks.abort_all();
+                completed = false;
+                break;
+            }
+        }
+    }
+
+    {
+        let mut cache = state.release_cache.lock().await;
+        if completed && force {
+            cache.prune_untouched();
+        }
+        if let Err(e) = cache.save_to(&state.cache_file) {
+            eprintln!("Failed to save release cache: {}", e);
+        }
+    }
+
+    // Merge in configuration order so the output does not depend on completion order
+    let mut releases = Vec::new();
+    let mut complete_projects = HashSet::new();
+    let mut branch_pipelines = HashMap::new();
+    let mut deployments = Vec::new();
+    let mut failures = Vec::new();
+    for (index, project) in config.projects.iter().enumerate() {
+        let key = (project.instance.clone(), project.path.clone());
+        let project_releases = match results.remove(&index) {
+            Some(Ok(project_releases)) => {
+                if let Some(pipeline) = project_releases.pipeline {
+                    branch_pipelines.insert(key.clone(), pipeline);
+                }
+                deployments.extend(project_releases.deployments);
+                if !project_releases.truncated {
+                    complete_projects.insert(key);
+                }
+                project_releases.releases
+            }
+            failed => {
+                let error = match failed {
+                    Some(Err(e)) => e.to_string(),
+                    _ => "no result before the refresh deadline".to_string(),
+                };
+                eprintln!(
+                    "Error fetching releases for {} on {}: {}",
+                    project.path, project.instance, error
+                );
+                failures.push(FetchFailure {
+                    instance: project.instance.clone(),
+                    project_path: project.path.clone(),
+                    error,
+                });
+                let Some(snapshot) = snapshots.get(&key) else {
+                    continue;
+                };
+                if let Some(pipeline) = &snapshot.releases.pipeline {
+                    branch_pipelines.insert(key.clone(), pipeline.clone());
+                }
+                deployments.extend(snapshot.releases.deployments.iter().cloned());
+                snapshot.releases.releases.clone()
+            }
+        };
+
+        let display_name = project.display_name();
+        releases.extend(project_releases.into_iter().map(|mut release| {
+            // Cached pages are shared by URL, so the instance name may be stale
+            release.instance = project.instance.clone();
+            release.project_name = display_name.clone();
+            release
+        }));
+    }
+
+    // Drop releases outside each project's release channel before anything else
+    // (latest selection, change detection) sees them
+    apply_release_channels(&mut releases, &config);
+
+    // Sort releases by creation date (newest first), ties broken by instance,
+    // project and tag
+    releases.sort_by(|a, b| {
+        b.created_at
+            .cmp(&a.created_at)
+            .then_with(|| a.instance.cmp(&b.instance))
+            .then_with(|| a.project_path.cmp(&b.project_path))
+            .then_with(|| a.tag_name.cmp(&b.tag_name))
+    });
+
+    FetchedReleases {
+        releases,
+        complete_projects,
+        branch_pipelines,
+        deployments,
+        failures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateFiles;
+    use crate::test_support::{HttpStub, StubResponse};
+    use reqwest::header::HeaderValue;
+
+    const CURRENT: &str =
+        "https://gitlab.example.com/api/v4/projects/1/releases?per_page=20&page=1";
+
+    fn header_map(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn release(tag_name: &str, name: &str) -> GitLabRelease {
+        GitLabRelease {
+            instance: "default".to_string(),
+            project_name: "app".to_string(),
+            project_path: "grp/app".to_string(),
+            tag_name: tag_name.to_string(),
+            name: name.to_string(),
+            description: "Notes".to_string(),
+            created_at: "2026-10-01T10:00:00Z".parse().unwrap(),
+            released_at: Some("2026-10-01T10:00:00Z".parse().unwrap()),
+            web_url: format!("https://gitlab.example.com/grp/app/-/releases/{}", tag_name),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline: None,
+            environments: Vec::new(),
+        }
+    }
+
+    fn seen_state(releases: &[GitLabRelease]) -> SeenReleases {
+        let mut seen = SeenReleases::default();
+        seen.mark_project("default", "grp/app");
+        seen.record(releases);
+        seen
+    }
+
+    fn complete() -> HashSet<(String, String)> {
+        HashSet::from([("default".to_string(), "grp/app".to_string())])
+    }
+
+    #[test]
+    fn detect_release_changes_reports_added_updated_and_removed() {
+        let seen = seen_state(&[release("v1.0.0", "One"), release("v0.9.0", "Old")]);
+        let current = vec![release("v1.1.0", "New"), release("v1.0.0", "One, renamed")];
+        let changes = detect_release_changes(&current, &seen, &complete(), &GitLabConfig::empty());
+
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].tag_name, "v1.1.0");
+        assert_eq!(changes.updated.len(), 1);
+        assert_eq!(changes.updated[0].release.tag_name, "v1.0.0");
+        assert_eq!(changes.updated[0].changes.len(), 1);
+        assert_eq!(changes.updated[0].changes[0].field, "name");
+        assert_eq!(changes.updated[0].changes[0].old, "One");
+        assert_eq!(changes.removed.len(), 1);
+        assert_eq!(changes.removed[0].tag_name, "v0.9.0");
+        assert_eq!(changes.removed[0].name, "Old");
+    }
+
+    #[test]
+    fn detect_release_changes_ignores_new_projects_and_incomplete_fetches() {
+        let mut seen = SeenReleases::default();
+        seen.record(&[release("v1.0.0", "One")]);
+        let current = vec![release("v1.1.0", "New")];
+        let changes =
+            detect_release_changes(&current, &seen, &HashSet::new(), &GitLabConfig::empty());
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn detect_release_changes_treats_missing_snapshot_as_unchanged() {
+        // Written before releases kept a snapshot of their fields
+        let mut seen: SeenReleases = serde_json::from_value(serde_json::json!({
+            "projects": [{ "instance": "default", "path": "grp/app" }],
+            "releases": [{
+                "instance": "default",
+                "project_path": "grp/app",
+                "tag_name": "v1.0.0",
+                "first_seen_at": "2026-09-01T10:00:00Z",
+                "last_seen_at": "2026-09-01T10:00:00Z"
+            }]
+        }))
+        .unwrap();
+        let current = vec![release("v1.0.0", "One")];
+        let changes = detect_release_changes(&current, &seen, &complete(), &GitLabConfig::empty());
+        assert!(changes.is_empty());
+
+        // Recording backfills the snapshot, so later edits are reported
+        seen.record(&current);
+        let edited = vec![release("v1.0.0", "One, renamed")];
+        let changes = detect_release_changes(&edited, &seen, &complete(), &GitLabConfig::empty());
+        assert_eq!(changes.updated.len(), 1);
+    }
+
+    #[test]
+    fn detect_release_changes_keeps_a_stored_missing_release_date() {
+        let mut without_date = release("v1.0.0", "One");
+        without_date.released_at = None;
+        let seen = seen_state(&[without_date.clone()]);
+        let seen: SeenReleases =
+            serde_json::from_value(serde_json::to_value(&seen).unwrap()).unwrap();
+
+        let changes =
+            detect_release_changes(&[without_date], &seen, &complete(), &GitLabConfig::empty());
+        assert!(changes.is_empty());
+        let dated = vec![release("v1.0.0", "One")];
+        let changes = detect_release_changes(&dated, &seen, &complete(), &GitLabConfig::empty());
+        assert_eq!(changes.updated[0].changes[0].field, "released_at");
+    }
+
+    #[test]
+    fn next_page_url_picks_next_among_several_rels() {
+        let headers = header_map(&[(
+            "link",
+            "<https://gitlab.example.com/api/v4/projects/1/releases?page=1>; rel=\"prev\", \
+             <https://gitlab.example.com/api/v4/projects/1/releases?page=3>; rel=\"next\", \
+             <https://gitlab.example.com/api/v4/projects/1/releases?page=9>; rel=\"last\"",
+        )]);
+        assert_eq!(
+            next_page_url(&headers, CURRENT).as_deref(),
+            Some("https://gitlab.example.com/api/v4/projects/1/releases?page=3")
+        );
+    }
+
+    #[test]
+    fn next_page_url_accepts_unquoted_and_combined_rels() {
+        let unquoted = header_map(&[("link", "<https://x.test/r?page=2>; rel=next")]);
+        assert_eq!(
+            next_page_url(&unquoted, CURRENT).as_deref(),
+            Some("https://x.test/r?page=2")
+        );
+        let combined = header_map(&[("link", "<https://x.test/r?page=2>; rel=\"next last\"")]);
+        assert_eq!(
+            next_page_url(&combined, CURRENT).as_deref(),
+            Some("https://x.test/r?page=2")
+        );
+    }
+
+    #[test]
+    fn next_page_url_without_next_is_none() {
+        let headers = header_map(&[(
+            "link",
+            "<https://gitlab.example.com/api/v4/projects/1/releases?page=1>; rel=\"first\", \
+             <https://gitlab.example.com/api/v4/projects/1/releases?page=1>; rel=\"last\"",
+        )]);
+        assert_eq!(next_page_url(&headers, CURRENT), None);
+        assert_eq!(next_page_url(&HeaderMap::new(), CURRENT), None);
+    }
+
+    #[test]
+    fn next_page_url_resolves_relative_targets() {
+        let headers = header_map(&[(
+            "link",
+            "</api/v4/projects/1/releases?page=2&pagination=keyset>; rel=\"next\"",
+        )]);
+        assert_eq!(
+            next_page_url(&headers, CURRENT).as_deref(),
+            Some("https://gitlab.example.com/api/v4/projects/1/releases?page=2&pagination=keyset")
+        );
+    }
+
+    #[test]
+    fn next_page_url_falls_back_to_x_next_page() {
+        let headers = header_map(&[("x-next-page", "2")]);
+        assert_eq!(
+            next_page_url(&headers, CURRENT).as_deref(),
+            Some("https://gitlab.example.com/api/v4/projects/1/releases?per_page=20&page=2")
+        );
+        let last_page = header_map(&[("x-next-page", "")]);
+        assert_eq!(next_page_url(&last_page, CURRENT), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_rate_limits_per_instance() {
+        let busy = HttpStub::start(|_| {
+            StubResponse::new(200, "{}")
+                .header("RateLimit-Remaining", "1")
+                .header("RateLimit-Limit", "100")
+        })
+        .await;
+        let idle = HttpStub::start(|_| (200, "{}".to_string())).await;
+        let mut config = GitLabConfig::empty();
+        config
+            .instances
+            .push(InstanceConfig::new("busy", &busy.url, "token"));
+        config
+            .instances
+            .push(InstanceConfig::new("idle", &idle.url, "token"));
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::with_files(config.clone(), StateFiles::in_dir(dir.path()));
+
+        for instance in &config.instances {
+            api_get(&state, instance, "version").await.unwrap();
+        }
+        assert!(state.rate_limiter("busy").await.pressure_delay().is_some());
+        assert!(state.rate_limiter("idle").await.pressure_delay().is_none());
+        assert!(Arc::ptr_eq(
+            &state.rate_limiter("busy").await,
+            &state.rate_limiter("busy").await
+        ));
+    }
+}
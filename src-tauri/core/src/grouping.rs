@@ -0,0 +1,187 @@
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+// How tags of a project are split into families; `filter_latest_releases`
+// keeps the newest release of each family.
+//
+// Spec strings (as used in the configuration):
+//   dash-v                  - family is everything before the first "-v" (default)
+//   none                    - no families, one latest release per project
+//   regex:<pattern>         - family is the named capture `family`
+//   glob:<pattern>[,...]    - family is the first glob the tag matches
+#[derive(Debug, Clone, Default)]
+pub enum TagGrouping {
+    #[default]
+    DashV,
+    None,
+    Regex(Regex),
+    Glob(Vec<GlobMatcher>),
+}
+
+impl TagGrouping {
+    // Family a tag belongs to; tags that match no family share the empty family
+    pub fn family(&self, tag: &str) -> String {
+        match self {
+            TagGrouping::DashV => extract_tag_attribute(tag),
+            TagGrouping::None => String::new(),
+            TagGrouping::Regex(regex) => regex
+                .captures(tag)
+                .and_then(|caps| caps.name("family"))
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_default(),
+            TagGrouping::Glob(globs) => globs
+                .iter()
+                .find(|glob| glob.is_match(tag))
+                .map(|glob| glob.glob().glob().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for TagGrouping {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        if spec == "dash-v" {
+            return Ok(TagGrouping::DashV);
+        }
+        if spec == "none" {
+            return Ok(TagGrouping::None);
+        }
+        if let Some(pattern) = spec.strip_prefix("regex:") {
+            let regex = Regex::new(pattern)
+                .map_err(|e| format!("invalid tag grouping regex '{}': {}", pattern, e))?;
+            if !regex.capture_names().any(|name| name == Some("family")) {
+                return Err(format!(
+                    "tag grouping regex '{}' needs a named capture group (?P<family>...)",
+                    pattern
+                ));
+            }
+            return Ok(TagGrouping::Regex(regex));
+        }
+        if let Some(patterns) = spec.strip_prefix("glob:") {
+            let globs = patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| {
+                    Glob::new(p)
+                        .map(|glob| glob.compile_matcher())
+                        .map_err(|e| format!("invalid tag grouping glob '{}': {}", p, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if globs.is_empty() {
+                return Err("tag grouping 'glob:' needs at least one pattern".to_string());
+            }
+            return Ok(TagGrouping::Glob(globs));
+        }
+        Err(format!(
+            "unknown tag grouping '{}' (expected dash-v, none, regex:<pattern> or glob:<pattern>)",
+            spec
+        ))
+    }
+}
+
+impl fmt::Display for TagGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagGrouping::DashV => write!(f, "dash-v"),
+            TagGrouping::None => write!(f, "none"),
+            TagGrouping::Regex(regex) => write!(f, "regex:{}", regex.as_str()),
+            TagGrouping::Glob(globs) => {
+                let patterns: Vec<&str> = globs.iter().map(|g| g.glob().glob()).collect();
+                write!(f, "glob:{}", patterns.join(","))
+            }
+        }
+    }
+}
+
+// Config files store the grouping as its spec string, e.g. "regex:^(?P<family>.+)_v"
+impl Serialize for TagGrouping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TagGrouping {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// Default grouping: the family is everything before the first "-v"
+// (e.g. "backend-v1.2.3" -> "backend"), or empty when there is none
+pub fn extract_tag_attribute(tag: &str) -> String {
+    let lower = tag.to_lowercase();
+    if let Some(idx) = lower.find("-v") {
+        tag[..idx].to_string()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouping(spec: &str) -> TagGrouping {
+        spec.parse().unwrap()
+    }
+
+    #[test]
+    fn dash_v_groups_by_prefix() {
+        let dash_v = TagGrouping::default();
+        assert_eq!(dash_v.family("backend-v1.2.3"), "backend");
+        assert_eq!(dash_v.family("Frontend-V2.0"), "Frontend");
+        assert_eq!(dash_v.family("my-app-v1"), "my-app");
+        assert_eq!(dash_v.family("v1.2.3"), "");
+        assert_eq!(grouping("dash-v").to_string(), "dash-v");
+    }
+
+    #[test]
+    fn none_puts_every_tag_in_one_family() {
+        let none = grouping("none");
+        assert_eq!(none.family("backend-v1.2.3"), "");
+        assert_eq!(none.family("v1.2.3"), "");
+    }
+
+    #[test]
+    fn regex_groups_by_family_capture() {
+        let regex = grouping("regex:^(?P<family>[a-z]+)_v");
+        assert_eq!(regex.family("api_v1.0"), "api");
+        assert_eq!(regex.family("worker_v2.1"), "worker");
+        // Tags the pattern does not match fall back to the shared empty family
+        assert_eq!(regex.family("1.0.0"), "");
+        assert_eq!(regex.to_string(), "regex:^(?P<family>[a-z]+)_v");
+    }
+
+    #[test]
+    fn glob_groups_by_first_matching_pattern() {
+        let glob = grouping("glob: cli-*, *-lts ,*");
+        assert_eq!(glob.family("cli-1.0"), "cli-*");
+        assert_eq!(glob.family("2.0-lts"), "*-lts");
+        assert_eq!(glob.family("2.1"), "*");
+        assert_eq!(grouping("glob:cli-*").family("2.1"), "");
+        assert_eq!(glob.to_string(), "glob:cli-*,*-lts,*");
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        let error = "regex:^(.+)_v".parse::<TagGrouping>().unwrap_err();
+        assert!(error.contains("(?P<family>...)"), "{}", error);
+        assert!("regex:(".parse::<TagGrouping>().is_err());
+        assert!("glob: , ".parse::<TagGrouping>().is_err());
+        let error = "semver".parse::<TagGrouping>().unwrap_err();
+        assert!(
+            error.starts_with("unknown tag grouping 'semver'"),
+            "{}",
+            error
+        );
+    }
+}
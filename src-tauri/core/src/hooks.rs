@@ -0,0 +1,341 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{config_file_path, GitLabConfig},
+    models::GitLabRelease,
+    storage,
+};
+
+// Runs kept in the history
+const MAX_HISTORY: usize = 100;
+// Output kept per stream and run; the rest is cut off
+const MAX_OUTPUT_BYTES: usize = 8 * 1024;
+
+// Hooks run one after another, also across refreshes, so deploy scripts never
+// overlap
+static RUNNING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+// One execution of an `on_release` hook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub instance: String,
+    pub project_path: String,
+    pub tag_name: String,
+    pub command: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    // None when the hook did not exit by itself
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    // Why the hook could not be started, if it could not
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Latest hook runs, newest first, persisted for the history view
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HookHistory {
+    #[serde(default)]
+    pub runs: VecDeque<HookRun>,
+}
+
+impl HookHistory {
+    pub fn file_path() -> PathBuf {
+        storage::data_dir().join("hook-history.json")
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        storage::load_json(path).unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        storage::save_json(path, self)
+    }
+
+    pub fn push(&mut self, run: HookRun) {
+        self.runs.push_front(run);
+        self.runs.truncate(MAX_HISTORY);
+    }
+}
+
+// Run the `on_release` hook of each new release's project and record the runs
+// in `history`, which is persisted to `history_file`
+pub async fn run_release_hooks(
+    config: &GitLabConfig,
+    history: &Mutex<HookHistory>,
+    history_file: &Path,
+    releases: &[GitLabRelease],
+) -> Vec<HookRun> {
+    let _running = RUNNING.lock().await;
+    let mut runs = Vec::new();
+    for release in releases {
+        let project = config.project_settings(&release.instance, &release.project_path);
+        let Some(command) = &project.on_release else {
+            continue;
+        };
+        let run = run_hook(
+            command,
+            release,
+            Duration::from_secs(config.hook_timeout_secs),
+        )
+        .await;
+
+        let mut history = history.lock().await;
+        history.push(run.clone());
+        if let Err(e) = history.save_to(history_file) {
+            eprintln!("Failed to save hook history: {}", e);
+        }
+        drop(history);
+        runs.push(run);
+    }
+    runs
+}
+
+// Run `command` for a release: its fields are passed as GITLAB_* environment
+// variables and as JSON on stdin. The hook is killed after `timeout`.
+pub async fn run_hook(command: &[String], release: &GitLabRelease, timeout: Duration) -> HookRun {
+    let label = format!("{} {}", release.project_path, release.tag_name);
+    println!("Running hook for {}: {}", label, command.join(" "));
+
+    let mut run = HookRun {
+        instance: release.instance.clone(),
+        project_path: release.project_path.clone(),
+        tag_name: release.tag_name.clone(),
+        command: command.to_vec(),
+        started_at: Utc::now(),
+        duration_ms: 0,
+        exit_code: None,
+        timed_out: false,
+        error: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    let started = Instant::now();
+
+    let Some((program, args)) = command.split_first() else {
+        eprintln!("Hook for {} has no command", label);
+        run.error = Some("no command given".to_string());
+        return run;
+    };
+    let mut process = Command::new(program);
+    process
+        .args(args)
+        .env("GITLAB_INSTANCE", &release.instance)
+        .env("GITLAB_PROJECT_PATH", &release.project_path)
+        .env("GITLAB_PROJECT_NAME", &release.project_name)
+        .env("GITLAB_TAG_NAME", &release.tag_name)
+        .env("GITLAB_RELEASE_NAME", &release.name)
+        .env("GITLAB_WEB_URL", &release.web_url)
+        .env("GITLAB_DESCRIPTION", &release.description)
+        .env(
+            "GITLAB_RELEASED_AT",
+            release
+                .released_at
+                .unwrap_or(release.created_at)
+                .to_rfc3339(),
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Its own process group, so a timeout also kills what the hook started,
+    // e.g. the commands of a `sh -c` script
+    #[cfg(unix)]
+    process.process_group(0);
+    // Relative programs such as `./deploy.sh` are found next to config.toml
+    if let Some(dir) = config_file_path().parent().filter(|dir| dir.is_dir()) {
+        process.current_dir(dir);
+    }
+
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Hook for {} could not be started: {}", label, e);
+            run.error = Some(e.to_string());
+            return run;
+        }
+    };
+
+    // The hook may exit without reading its input, so a failed write is fine
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = serde_json::to_vec(release).unwrap_or_default();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&input).await;
+        });
+    }
+
+    // Dropping the child on timeout kills it; on Unix its process group is
+    // killed as well
+    #[cfg(unix)]
+    let pid = child.id();
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            run.exit_code = output.status.code();
+            run.stdout = truncate_output(&output.stdout);
+            run.stderr = truncate_output(&output.stderr);
+        }
+        Ok(Err(e)) => run.error = Some(e.to_string()),
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                kill_process_group(pid);
+            }
+            run.timed_out = true;
+            run.error = Some(format!("timed out after {}s", timeout.as_secs()));
+        }
+    }
+    run.duration_ms = started.elapsed().as_millis() as u64;
+
+    for line in run.stdout.lines() {
+        println!("[hook {}] {}", label, line);
+    }
+    for line in run.stderr.lines() {
+        eprintln!("[hook {}] {}", label, line);
+    }
+    match (&run.error, run.exit_code) {
+        (Some(e), _) => eprintln!("Hook for {} failed: {}", label, e),
+        (None, Some(0)) => println!("Hook for {} finished", label),
+        (None, Some(code)) => eprintln!("Hook for {} exited with code {}", label, code),
+        (None, None) => eprintln!("Hook for {} was killed by a signal", label),
+    }
+    run
+}
+
+#[cfg(unix)]
+fn kill_process_group(pgid: u32) {
+    // SAFETY: kill() has no memory safety requirements; a negative pid
+    // addresses the process group
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+fn truncate_output(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.into_owned();
+    }
+    let mut end = MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[... {} more bytes]", &text[..end], text.len() - end)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn release() -> GitLabRelease {
+        GitLabRelease {
+            instance: "default".to_string(),
+            project_name: "App".to_string(),
+            project_path: "grp/app".to_string(),
+            tag_name: "v1.0.0".to_string(),
+            name: "First".to_string(),
+            description: "Notes".to_string(),
+            created_at: "2026-10-01T10:00:00Z".parse().unwrap(),
+            released_at: None,
+            web_url: "https://gitlab.example.com/grp/app/-/releases/v1.0.0".to_string(),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline: None,
+            environments: Vec::new(),
+        }
+    }
+
+    async fn sh(script: &str, timeout: Duration) -> HookRun {
+        let command = ["sh", "-c", script].map(str::to_string);
+        run_hook(&command, &release(), timeout).await
+    }
+
+    // Gone, or a zombie nobody has reaped yet
+    fn is_dead(pid: &str) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rsplit_once(')')
+                .is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+
+    #[tokio::test]
+    async fn passes_the_release_as_environment_and_stdin() {
+        let run = sh(
+            r#"echo "$GITLAB_PROJECT_PATH $GITLAB_TAG_NAME $GITLAB_RELEASED_AT"; cat; exit 3"#,
+            Duration::from_secs(10),
+        )
+        .await;
+
+        assert_eq!(run.exit_code, Some(3));
+        assert!(!run.timed_out);
+        assert_eq!(run.error, None);
+        let (line, input) = run.stdout.split_once('\n').unwrap();
+        assert_eq!(line, "grp/app v1.0.0 2026-10-01T10:00:00+00:00");
+        let input: serde_json::Value = serde_json::from_str(input).unwrap();
+        assert_eq!(input["tag_name"], "v1.0.0");
+        assert_eq!(input["web_url"], release().web_url);
+    }
+
+    #[tokio::test]
+    async fn kills_the_hook_and_what_it_started_on_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+
+        let started = Instant::now();
+        let run = sh(&script, Duration::from_secs(1)).await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(run.timed_out);
+        assert_eq!(run.exit_code, None);
+        assert_eq!(run.error.as_deref(), Some("timed out after 1s"));
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let pid = pid.trim();
+        for _ in 0..50 {
+            if is_dead(pid) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("process {} started by the hook is still running", pid);
+    }
+
+    #[tokio::test]
+    async fn truncates_long_output() {
+        let run = sh(
+            "head -c 20000 /dev/zero | tr '\\0' a; head -c 9000 /dev/zero | tr '\\0' b >&2",
+            Duration::from_secs(10),
+        )
+        .await;
+
+        assert_eq!(run.exit_code, Some(0));
+        let expected = format!("{}\n[... 11808 more bytes]", "a".repeat(MAX_OUTPUT_BYTES));
+        assert_eq!(run.stdout, expected);
+        let expected = format!("{}\n[... 808 more bytes]", "b".repeat(MAX_OUTPUT_BYTES));
+        assert_eq!(run.stderr, expected);
+    }
+
+    #[tokio::test]
+    async fn reports_hooks_that_cannot_run() {
+        let run = run_hook(&[], &release(), Duration::from_secs(1)).await;
+        assert_eq!(run.error.as_deref(), Some("no command given"));
+
+        let missing = ["/nonexistent/gitlab-monitor-hook".to_string()];
+        let run = run_hook(&missing, &release(), Duration::from_secs(1)).await;
+        assert!(run.error.is_some());
+        assert_eq!(run.exit_code, None);
+    }
+}
@@ -0,0 +1,29 @@
+// Release monitoring without a UI: configuration, the GitLab, GitHub and Gitea
+// clients, filtering, change detection and the background poller. The tray app
+// and the command line client are both built on top of it.
+
+pub mod cache;
+pub mod config;
+pub mod deployments;
+pub mod diagnose;
+pub mod digest;
+pub mod discovery;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod grouping;
+pub mod hooks;
+pub mod models;
+pub mod pipelines;
+pub mod poller;
+pub mod rate_limit;
+pub mod secrets;
+pub mod seen;
+pub mod sources;
+pub mod state;
+pub mod storage;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod validation;
+pub mod version;
+pub mod webhooks;
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::version::ReleaseVersion;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitLabRelease {
+    // Name of the configured instance the release was fetched from
+    #[serde(default)]
+    pub instance: String,
+    pub project_name: String,
+    pub project_path: String,
+    pub tag_name: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    pub released_at: Option<DateTime<Utc>>,
+    pub web_url: String,
+    // Tag family the release was grouped into (set by `filter_latest_releases`)
+    #[serde(default)]
+    pub tag_family: String,
+    // Version parsed from `tag_name`, if it contains one
+    #[serde(default)]
+    pub version: Option<ReleaseVersion>,
+    // GitLab's flag for releases whose `released_at` lies in the future
+    #[serde(default)]
+    pub upcoming_release: bool,
+    // Marked as a pre-release on GitHub or Gitea
+    #[serde(default)]
+    pub prerelease: bool,
+    // Latest pipeline of the release tag, for projects with `pipelines` enabled
+    #[serde(default)]
+    pub pipeline: Option<Pipeline>,
+    // Environments the release tag is currently deployed to, for projects with
+    // `deployments` enabled
+    #[serde(default)]
+    pub environments: Vec<String>,
+}
+
+impl GitLabRelease {
+    // Pre-release by version (e.g. "-rc.1"), announced as upcoming on GitLab or
+    // flagged as a pre-release by the provider
+    pub fn is_prerelease(&self) -> bool {
+        self.upcoming_release
+            || self.prerelease
+            || self.version.as_ref().is_some_and(|v| v.is_prerelease())
+    }
+}
+
+// A GitLab CI pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub id: u64,
+    // As reported by GitLab: created, pending, running, success, failed,
+    // canceled, skipped, manual, scheduled, ...
+    pub status: String,
+    // Branch or tag the pipeline ran for
+    pub ref_name: String,
+    pub sha: String,
+    pub web_url: String,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Pipeline {
+    // Whether the pipeline has come to an end, which is when changes are reported
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "success" | "failed" | "canceled" | "skipped"
+        )
+    }
+}
+
+// A watched pipeline that finished since the previous refresh, e.g. running -> failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineChange {
+    pub instance: String,
+    pub project_path: String,
+    pub project_name: String,
+    // Tag of the release the pipeline belongs to; None for the default branch
+    pub tag_name: Option<String>,
+    pub previous_status: String,
+    pub pipeline: Pipeline,
+}
+
+// A project, or discovery rule, that could not be fetched in a refresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchFailure {
+    pub instance: String,
+    // Project path, or the entry of a discovery rule (e.g. `group:platform/*`)
+    pub project_path: String,
+    pub error: String,
+}
+
+// The deployment currently live in one environment of a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub instance: String,
+    pub project_path: String,
+    pub project_name: String,
+    pub environment: String,
+    pub environment_url: Option<String>,
+    pub id: u64,
+    // Branch or tag that was deployed
+    pub ref_name: String,
+    pub tag: bool,
+    pub sha: String,
+    pub deployed_at: Option<DateTime<Utc>>,
+    // Name and page of the release whose tag was deployed, if any
+    pub release_name: Option<String>,
+    pub release_url: Option<String>,
+}
+
+// A single field that differs between the stored and the fetched release
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatedRelease {
+    pub release: GitLabRelease,
+    pub changes: Vec<FieldChange>,
+}
+
+// A previously seen release that no longer exists on GitLab (deleted or retagged)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedRelease {
+    pub instance: String,
+    pub project_path: String,
+    pub tag_name: String,
+    pub name: String,
+}
+
+// A GitLab project as looked up when adding it in the settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    pub instance: String,
+    pub path: String,
+    pub name: String,
+    pub web_url: String,
+}
+
+// Outcome of a single diagnostic check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+    // Not run because an earlier check of the instance failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    // Instance the check belongs to, empty for configuration-wide checks
+    pub instance: String,
+    // What was checked: "configuration", "connection", "token", "scopes" or a project path
+    pub subject: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+// Result of checking the configuration, token and projects against GitLab
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl DiagnosticReport {
+    pub fn push(&mut self, instance: &str, subject: &str, status: CheckStatus, message: String) {
+        self.checks.push(DiagnosticCheck {
+            instance: instance.to_string(),
+            subject: subject.to_string(),
+            status,
+            message,
+        });
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &DiagnosticCheck> {
+        self.checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Error)
+    }
+}
+
+// Everything that changed between two refreshes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseChangeSet {
+    pub added: Vec<GitLabRelease>,
+    pub updated: Vec<UpdatedRelease>,
+    pub removed: Vec<RemovedRelease>,
+    #[serde(default)]
+    pub pipelines: Vec<PipelineChange>,
+    // Tags newly deployed to an environment
+    #[serde(default)]
+    pub deployed: Vec<Deployment>,
+}
+
+impl ReleaseChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.pipelines.is_empty()
+            && self.deployed.is_empty()
+    }
+
+    // Fold a later change set into this one
+    pub fn extend(&mut self, other: ReleaseChangeSet) {
+        self.added.extend(other.added);
+        self.updated.extend(other.updated);
+        self.removed.extend(other.removed);
+        self.pipelines.extend(other.pipelines);
+        self.deployed.extend(other.deployed);
+    }
+}
@@ -0,0 +1,372 @@
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    config::{GitLabConfig, ProjectConfig},
+    gitlab::{apply_release_channels, filter_latest_releases, GitLabSource, ProjectReleases},
+    models::{GitLabRelease, Pipeline, PipelineChange},
+    sources::{get_json, SourceContext},
+};
+
+type PipelineResult = Result<Option<Pipeline>, Box<dyn std::error::Error + Send + Sync>>;
+
+fn parse_pipeline(value: &serde_json::Value) -> Option<Pipeline> {
+    Some(Pipeline {
+        id: value["id"].as_u64()?,
+        status: value["status"].as_str()?.to_string(),
+        ref_name: value["ref"].as_str().unwrap_or("").to_string(),
+        sha: value["sha"].as_str().unwrap_or("").to_string(),
+        web_url: value["web_url"].as_str().unwrap_or("").to_string(),
+        updated_at: value["updated_at"]
+            .as_str()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&Utc)),
+    })
+}
+
+// Latest pipeline of a tag, or None when it has none
+pub async fn fetch_tag_pipeline(
+    ctx: &SourceContext<'_>,
+    project_path: &str,
+    tag_name: &str,
+) -> PipelineResult {
+    let url = format!(
+        "{}/api/v4/projects/{}/pipelines?ref={}&per_page=1",
+        ctx.instance.base_url,
+        urlencoding::encode(project_path),
+        urlencoding::encode(tag_name)
+    );
+    match get_json(&GitLabSource, ctx, &url).await? {
+        (status, pipelines) if status.is_success() => Ok(pipelines
+            .as_array()
+            .and_then(|pipelines| pipelines.first())
+            .and_then(parse_pipeline)),
+        (status, _) => Err(format!(
+            "Failed to fetch the pipelines of {} {}: {}",
+            project_path, tag_name, status
+        )
+        .into()),
+    }
+}
+
+// Latest pipeline on the default branch, or None when it has none
+pub async fn fetch_branch_pipeline(ctx: &SourceContext<'_>, project_path: &str) -> PipelineResult {
+    let url = format!(
+        "{}/api/v4/projects/{}/pipelines/latest",
+        ctx.instance.base_url,
+        urlencoding::encode(project_path)
+    );
+    match get_json(&GitLabSource, ctx, &url).await? {
+        (status, pipeline) if status.is_success() => Ok(parse_pipeline(&pipeline)),
+        (StatusCode::NOT_FOUND, _) => Ok(None),
+        (status, _) => Err(format!(
+            "Failed to fetch the default branch pipeline of {}: {}",
+            project_path, status
+        )
+        .into()),
+    }
+}
+
+// Fetch the pipelines a project watches. Tag pipelines are only fetched for the
+// releases that are shown (the latest per tag family), which keeps it to a few
+// requests per project. Failures are logged and keep the pipeline of the
+// `previous` fetch, so a failed request neither hides it nor reports a change.
+pub async fn attach_pipelines(
+    ctx: &SourceContext<'_>,
+    project: &ProjectConfig,
+    fetched: &mut ProjectReleases,
+    previous: Option<&ProjectReleases>,
+) {
+    if project.pipelines {
+        let mut shown = fetched.releases.clone();
+        for release in &mut shown {
+            release.instance = project.instance.clone();
+        }
+        apply_release_channels(&mut shown, ctx.config);
+        let tags: HashSet<String> = filter_latest_releases(&shown, ctx.config)
+            .into_iter()
+            .map(|r| r.tag_name)
+            .collect();
+
+        for release in &mut fetched.releases {
+            if !tags.contains(&release.tag_name) {
+                continue;
+            }
+            match fetch_tag_pipeline(ctx, &project.path, &release.tag_name).await {
+                Ok(pipeline) => release.pipeline = pipeline,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    release.pipeline = previous
+                        .into_iter()
+                        .flat_map(|p| &p.releases)
+                        .find(|r| r.tag_name == release.tag_name)
+                        .and_then(|r| r.pipeline.clone());
+                }
+            }
+        }
+    }
+
+    if project.branch_pipeline {
+        match fetch_branch_pipeline(ctx, &project.path).await {
+            Ok(pipeline) => fetched.pipeline = pipeline,
+            Err(e) => {
+                eprintln!("{}", e);
+                fetched.pipeline = previous.and_then(|p| p.pipeline.clone());
+            }
+        }
+    }
+}
+
+// Compare the pipelines of a refresh against the previous one. Only pipelines
+// that were seen before and have since finished with a different status (or a
+// newer pipeline that finished) are reported, so neither the first refresh nor
+// turning pipelines on reports anything.
+pub fn detect_pipeline_changes(
+    previous: &[GitLabRelease],
+    current: &[GitLabRelease],
+    previous_branches: &HashMap<(String, String), Pipeline>,
+    branches: &HashMap<(String, String), Pipeline>,
+    config: &GitLabConfig,
+) -> Vec<PipelineChange> {
+    let changed = |old: &Pipeline, new: &Pipeline| {
+        new.is_finished() && (old.id != new.id || old.status != new.status)
+    };
+    let mut changes = Vec::new();
+
+    let previous_tags: HashMap<(&str, &str, &str), &Pipeline> = previous
+        .iter()
+        .filter_map(|r| {
+            let key = (
+                r.instance.as_str(),
+                r.project_path.as_str(),
+                r.tag_name.as_str(),
+            );
+            r.pipeline.as_ref().map(|pipeline| (key, pipeline))
+        })
+        .collect();
+    for release in current {
+        let Some(pipeline) = &release.pipeline else {
+            continue;
+        };
+        let key = (
+            release.instance.as_str(),
+            release.project_path.as_str(),
+            release.tag_name.as_str(),
+        );
+        if let Some(old) = previous_tags.get(&key) {
+            if changed(old, pipeline) {
+                changes.push(PipelineChange {
+                    instance: release.instance.clone(),
+                    project_path: release.project_path.clone(),
+                    project_name: release.project_name.clone(),
+                    tag_name: Some(release.tag_name.clone()),
+                    previous_status: old.status.clone(),
+                    pipeline: pipeline.clone(),
+                });
+            }
+        }
+    }
+
+    let mut keys: Vec<&(String, String)> = branches.keys().collect();
+    keys.sort();
+    for key in keys {
+        let pipeline = &branches[key];
+        if let Some(old) = previous_branches.get(key) {
+            if changed(old, pipeline) {
+                let (instance, project_path) = key;
+                changes.push(PipelineChange {
+                    instance: instance.clone(),
+                    project_path: project_path.clone(),
+                    project_name: config
+                        .project_settings(instance, project_path)
+                        .display_name(),
+                    tag_name: None,
+                    previous_status: old.status.clone(),
+                    pipeline: pipeline.clone(),
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReleaseCache, config::InstanceConfig, rate_limit::RateLimiter,
+        test_support::HttpStub,
+    };
+    use tokio::sync::Mutex;
+
+    fn pipeline(id: u64, status: &str) -> Pipeline {
+        Pipeline {
+            id,
+            status: status.to_string(),
+            ref_name: "v1.0.0".to_string(),
+            sha: String::new(),
+            web_url: format!("https://gitlab.example.com/grp/app/-/pipelines/{}", id),
+            updated_at: None,
+        }
+    }
+
+    fn release(tag_name: &str, pipeline: Option<Pipeline>) -> GitLabRelease {
+        GitLabRelease {
+            instance: "default".to_string(),
+            project_name: "app".to_string(),
+            project_path: "grp/app".to_string(),
+            tag_name: tag_name.to_string(),
+            name: format!("Release {}", tag_name),
+            description: String::new(),
+            created_at: "2026-10-01T10:00:00Z".parse().unwrap(),
+            released_at: None,
+            web_url: format!("https://gitlab.example.com/grp/app/-/releases/{}", tag_name),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline,
+            environments: Vec::new(),
+        }
+    }
+
+    fn branch(pipeline: Option<Pipeline>) -> HashMap<(String, String), Pipeline> {
+        pipeline
+            .map(|p| (("default".to_string(), "grp/app".to_string()), p))
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn reports_finished_pipelines_that_changed() {
+        // (previous, current, reported previous status)
+        let cases = [
+            (
+                Some(pipeline(1, "running")),
+                Some(pipeline(1, "failed")),
+                Some("running"),
+            ),
+            (
+                Some(pipeline(1, "running")),
+                Some(pipeline(1, "success")),
+                Some("running"),
+            ),
+            (
+                Some(pipeline(1, "success")),
+                Some(pipeline(1, "success")),
+                None,
+            ),
+            (
+                Some(pipeline(1, "success")),
+                Some(pipeline(1, "running")),
+                None,
+            ),
+            (None, Some(pipeline(1, "failed")), None),
+            (
+                Some(pipeline(1, "success")),
+                Some(pipeline(2, "failed")),
+                Some("success"),
+            ),
+            (Some(pipeline(1, "success")), None, None),
+        ];
+        let config = GitLabConfig::empty();
+
+        for (old, new, expected) in cases {
+            let changes = detect_pipeline_changes(
+                &[release("v1.0.0", old.clone())],
+                &[release("v1.0.0", new.clone())],
+                &HashMap::new(),
+                &HashMap::new(),
+                &config,
+            );
+            let reported: Vec<_> = changes.iter().map(|c| c.previous_status.as_str()).collect();
+            assert_eq!(
+                reported,
+                Vec::from_iter(expected),
+                "tag {:?} -> {:?}",
+                old,
+                new
+            );
+            if let Some(change) = changes.first() {
+                assert_eq!(change.tag_name.as_deref(), Some("v1.0.0"));
+                assert_eq!(Some(&change.pipeline), new.as_ref());
+            }
+
+            let changes = detect_pipeline_changes(
+                &[],
+                &[],
+                &branch(old.clone()),
+                &branch(new.clone()),
+                &config,
+            );
+            let reported: Vec<_> = changes.iter().map(|c| c.previous_status.as_str()).collect();
+            assert_eq!(
+                reported,
+                Vec::from_iter(expected),
+                "branch {:?} -> {:?}",
+                old,
+                new
+            );
+            if let Some(change) = changes.first() {
+                assert_eq!(change.tag_name, None);
+                assert_eq!(change.project_name, "app");
+            }
+        }
+    }
+
+    #[test]
+    fn first_seen_releases_do_not_report_their_pipeline() {
+        let changes = detect_pipeline_changes(
+            &[release("v1.0.0", Some(pipeline(1, "success")))],
+            &[
+                release("v1.1.0", Some(pipeline(2, "failed"))),
+                release("v1.0.0", Some(pipeline(1, "success"))),
+            ],
+            &HashMap::new(),
+            &HashMap::new(),
+            &GitLabConfig::empty(),
+        );
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_previous_pipelines_when_the_fetch_fails() {
+        let stub = HttpStub::start(|_| (500, "{}".to_string())).await;
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 0;
+        let instance = InstanceConfig::new("default", &stub.url, "token");
+        let mut project = ProjectConfig::new("default", "grp/app");
+        project.pipelines = true;
+        project.branch_pipeline = true;
+        let client = reqwest::Client::new();
+        let cache = Mutex::new(ReleaseCache::default());
+        let limiter = RateLimiter::new();
+        let ctx = SourceContext {
+            client: &client,
+            config: &config,
+            instance: &instance,
+            cache: &cache,
+            limiter: &limiter,
+        };
+
+        let previous = ProjectReleases {
+            releases: vec![release("v1.0.0", Some(pipeline(1, "running")))],
+            truncated: false,
+            pipeline: Some(pipeline(2, "success")),
+            deployments: Vec::new(),
+        };
+        let mut fetched = ProjectReleases {
+            releases: vec![release("v1.0.0", None)],
+            truncated: false,
+            pipeline: None,
+            deployments: Vec::new(),
+        };
+        attach_pipelines(&ctx, &project, &mut fetched, Some(&previous)).await;
+
+        assert_eq!(fetched.releases[0].pipeline, Some(pipeline(1, "running")));
+        assert_eq!(fetched.pipeline, Some(pipeline(2, "success")));
+        assert!(!stub.requests().is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::{
+    diagnose::diagnose,
+    discovery::update_discovered_projects,
+    gitlab::fetch_releases,
+    models::{DiagnosticReport, GitLabRelease, ReleaseChangeSet},
+    state::AppState,
+};
+
+// What a running poller reports to whoever drives it
+#[derive(Debug, Clone)]
+pub enum PollerEvent {
+    // Result of checking the configuration, token and projects, sent on start.
+    // It is the only event when the configuration is incomplete.
+    Diagnostics(DiagnosticReport),
+    // A refresh finished: the latest release per tag family and what changed
+    // since the previous refresh. `initial` is set for the first one.
+    Refreshed {
+        initial: bool,
+        releases: Vec<GitLabRelease>,
+        changes: ReleaseChangeSet,
+    },
+}
+
+// Background refresh loop over an `AppState`. Each refresh is applied to the
+// state (and its seen state) and reported as a `PollerEvent`; what to do with
+// the changes (tray, notifications, webhooks, printing) is up to the receiver.
+pub struct Poller {
+    state: AppState,
+}
+
+impl Poller {
+    pub fn new(state: AppState) -> Self {
+        Poller { state }
+    }
+
+    // Run the poller in its own task. It stops when the task is aborted or the
+    // receiver is dropped.
+    pub fn spawn(self) -> (JoinHandle<()>, UnboundedReceiver<PollerEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (tokio::spawn(self.run(sender)), receiver)
+    }
+
+    pub async fn run(self, events: UnboundedSender<PollerEvent>) {
+        let state = &self.state;
+
+        // Check if we have valid configuration; the diagnostic report tells the
+        // user what is missing
+        let config = state.current_config().await;
+        if let Some(reason) = config.incomplete_reason() {
+            eprintln!(
+                "GitLab monitoring disabled - configuration incomplete: {}",
+                reason
+            );
+            let _ = events.send(PollerEvent::Diagnostics(diagnose(state).await));
+            return;
+        }
+
+        eprintln!("Starting GitLab releases background task...");
+
+        // Expand group, starred and member entries into projects
+        update_discovered_projects(state).await;
+        let mut discovered_at = Instant::now();
+
+        // Compared against the persisted seen state, so releases
+        // published while the app was closed are flagged too. The token and
+        // projects are validated alongside.
+        let (report, fetched) = tokio::join!(diagnose(state), fetch_releases(state, true));
+        for check in report.errors() {
+            eprintln!("[{}] {}: {}", check.instance, check.subject, check.message);
+        }
+        let (releases, changes) = state.apply_releases(&fetched).await;
+        let initial = [
+            PollerEvent::Diagnostics(report),
+            PollerEvent::Refreshed {
+                initial: true,
+                releases,
+                changes,
+            },
+        ];
+        for event in initial {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+
+        // Auto-refresh; each project is re-fetched once its own poll interval elapsed
+        let mut interval = tokio::time::interval(config.tick_interval());
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if !config.discovery.is_empty()
+                && discovered_at.elapsed() >= Duration::from_secs(config.discover_interval_secs)
+            {
+                update_discovered_projects(state).await;
+                discovered_at = Instant::now();
+            }
+
+            eprintln!("Auto-refreshing GitLab releases...");
+
+            let fetched = fetch_releases(state, false).await;
+            let (releases, changes) = state.apply_releases(&fetched).await;
+            let event = PollerEvent::Refreshed {
+                initial: false,
+                releases,
+                changes,
+            };
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Treat the instance as under pressure once less than this share of the quota is left
+const PRESSURE_RATIO: f64 = 0.1;
+
+#[derive(Debug, Default)]
+struct RateLimitState {
+    // No request may be sent before this instant (set by 429 / Retry-After)
+    paused_until: Option<Instant>,
+    // The instance reported a nearly exhausted quota; slow the poller down until then
+    pressure_until: Option<Instant>,
+}
+
+// Shared view of one instance's rate limit, updated from response headers
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    state: Mutex<RateLimitState>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // How long refreshes should leave the instance alone
+    pub fn pressure_delay(&self) -> Option<Duration> {
+        let state = self.state.lock().ok()?;
+        let now = Instant::now();
+        [state.paused_until, state.pressure_until]
+            .into_iter()
+            .flatten()
+            .filter(|until| *until > now)
+            .max()
+            .map(|until| until - now)
+    }
+
+    fn pause_delay(&self) -> Option<Duration> {
+        let state = self.state.lock().ok()?;
+        let now = Instant::now();
+        state
+            .paused_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    fn pause_for(&self, delay: Duration) {
+        if let Ok(mut state) = self.state.lock() {
+            let until = Instant::now() + delay;
+            if state.paused_until.is_none_or(|current| current < until) {
+                state.paused_until = Some(until);
+            }
+        }
+    }
+
+    // Record `RateLimit-Remaining` / `RateLimit-Limit` / `RateLimit-Reset` headers
+    // (GitLab), or their `X-RateLimit-*` counterparts (GitHub)
+    fn observe(&self, headers: &HeaderMap) {
+        let remaining = rate_limit_header(headers, "remaining");
+        let limit = rate_limit_header(headers, "limit");
+        let (Some(remaining), Some(limit)) = (remaining, limit) else {
+            return;
+        };
+        if limit <= 0.0 || remaining / limit > PRESSURE_RATIO {
+            return;
+        }
+
+        let reset_in = reset_in(headers).unwrap_or(MAX_BACKOFF);
+
+        eprintln!(
+            "Rate limit nearly exhausted ({} of {} left), slowing down for {}s",
+            remaining,
+            limit,
+            reset_in.as_secs()
+        );
+        if let Ok(mut state) = self.state.lock() {
+            state.pressure_until = Some(Instant::now() + reset_in);
+        }
+    }
+}
+
+fn header_number(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn rate_limit_header(headers: &HeaderMap, field: &str) -> Option<f64> {
+    header_number(headers, &format!("ratelimit-{}", field))
+        .or_else(|| header_number(headers, &format!("x-ratelimit-{}", field)))
+}
+
+// Time until the quota resets, from `RateLimit-Reset` / `X-RateLimit-Reset`
+// (seconds since the epoch)
+fn reset_in(headers: &HeaderMap) -> Option<Duration> {
+    rate_limit_header(headers, "reset")
+        .map(|reset| reset as i64 - Utc::now().timestamp())
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs as u64))
+}
+
+// Parse `Retry-After`, which is either a number of seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    (date - Utc::now()).to_std().ok()
+}
+
+// Exponential backoff with "equal jitter": half fixed, half random
+fn backoff(attempt: u32) -> Duration {
+    let exp = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF);
+    let half = exp / 2;
+    half + half.mul_f64(rand::thread_rng().gen::<f64>())
+}
+
+// Whether a response says the quota is used up: a 429, or the 403 GitHub
+// answers with once its primary limit is reached (`X-RateLimit-Remaining: 0`)
+// or for its secondary limits (with `Retry-After`)
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && (rate_limit_header(headers, "remaining") == Some(0.0)
+                || headers.contains_key(RETRY_AFTER)))
+}
+
+// Send a request, retrying rate-limited and 5xx responses and transport errors
+// up to `max_retries` times. `Retry-After`, or for rate limits the quota reset
+// time, is honoured when present, otherwise the delay grows exponentially with
+// jitter. A rate limit holds back every request through `limiter`; when it
+// lasts longer than `MAX_BACKOFF` the response is returned as is, and later
+// requests fail until the limit is over.
+pub async fn send_with_retry(
+    request: RequestBuilder,
+    limiter: &RateLimiter,
+    max_retries: u32,
+) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    let mut attempt = 0;
+    loop {
+        if let Some(delay) = limiter.pause_delay() {
+            if delay > MAX_BACKOFF {
+                return Err(format!("Rate limited for another {}s", delay.as_secs()).into());
+            }
+            tokio::time::sleep(delay).await;
+        }
+
+        let this_try = request
+            .try_clone()
+            .ok_or("Request body cannot be retried")?;
+
+        let delay = match this_try.send().await {
+            Ok(response) => {
+                limiter.observe(response.headers());
+                let status = response.status();
+                let rate_limited = is_rate_limited(status, response.headers());
+                if !rate_limited && !status.is_server_error() {
+                    return Ok(response);
+                }
+
+                let delay = retry_after(response.headers())
+                    .or_else(|| rate_limited.then(|| reset_in(response.headers())).flatten())
+                    .unwrap_or_else(|| backoff(attempt));
+                if rate_limited {
+                    // Hold back every request to the instance, not just this one
+                    limiter.pause_for(delay);
+                }
+                if attempt >= max_retries || delay > MAX_BACKOFF {
+                    return Ok(response);
+                }
+                // Only the host is logged: webhook URLs carry their secret in the path
+                eprintln!(
+                    "{} returned {}, retrying in {:.1}s ({}/{})",
+                    response.url().host_str().unwrap_or("server"),
+                    status,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    max_retries
+                );
+                delay
+            }
+            Err(e) if attempt < max_retries && (e.is_timeout() || e.is_connect()) => {
+                let delay = backoff(attempt);
+                let host = e
+                    .url()
+                    .and_then(|url| url.host_str())
+                    .unwrap_or("server")
+                    .to_string();
+                eprintln!(
+                    "Request to {} failed ({}), retrying in {:.1}s ({}/{})",
+                    host,
+                    e.without_url(),
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    max_retries
+                );
+                delay
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{HttpStub, StubResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // GitHub's answer once the primary quota is used up, resetting in `secs`
+    fn quota_exhausted(secs: i64) -> StubResponse {
+        StubResponse::new(403, r#"{"message": "API rate limit exceeded"}"#)
+            .header("X-RateLimit-Limit", "60")
+            .header("X-RateLimit-Remaining", "0")
+            .header(
+                "X-RateLimit-Reset",
+                &(Utc::now().timestamp() + secs).to_string(),
+            )
+    }
+
+    async fn get(stub: &HttpStub, limiter: &RateLimiter) -> Result<StatusCode, String> {
+        let request = reqwest::Client::new().get(&stub.url);
+        send_with_retry(request, limiter, 2)
+            .await
+            .map(|response| response.status())
+            .map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_github_quota_to_reset() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let stub = HttpStub::start(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                quota_exhausted(1)
+            } else {
+                StubResponse::new(200, "[]")
+            }
+        })
+        .await;
+
+        assert_eq!(get(&stub, &RateLimiter::new()).await, Ok(StatusCode::OK));
+        assert_eq!(stub.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pauses_the_instance_until_a_distant_reset() {
+        let stub = HttpStub::start(|_| quota_exhausted(3600)).await;
+        let limiter = RateLimiter::new();
+
+        assert_eq!(get(&stub, &limiter).await, Ok(StatusCode::FORBIDDEN));
+        let delay = limiter.pressure_delay().unwrap();
+        assert!(delay > Duration::from_secs(3500), "{:?}", delay);
+
+        // Later requests are not sent until the quota resets
+        let error = get(&stub, &limiter).await.unwrap_err();
+        assert!(error.starts_with("Rate limited for another "), "{}", error);
+        assert_eq!(stub.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_forbidden_responses() {
+        let stub = HttpStub::start(|_| StubResponse::new(403, "{}")).await;
+        let limiter = RateLimiter::new();
+
+        assert_eq!(get(&stub, &limiter).await, Ok(StatusCode::FORBIDDEN));
+        assert_eq!(stub.requests().len(), 1);
+        assert!(limiter.pressure_delay().is_none());
+    }
+
+    #[tokio::test]
+    async fn honours_retry_after_on_secondary_limits() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let stub = HttpStub::start(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                StubResponse::new(403, "{}").header("Retry-After", "0")
+            } else {
+                StubResponse::new(200, "[]")
+            }
+        })
+        .await;
+
+        assert_eq!(get(&stub, &RateLimiter::new()).await, Ok(StatusCode::OK));
+        assert_eq!(stub.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_too_many_requests_after_retry_after() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let stub = HttpStub::start(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                StubResponse::new(429, "{}").header("Retry-After", "0")
+            } else {
+                StubResponse::new(200, "[]")
+            }
+        })
+        .await;
+
+        assert_eq!(get(&stub, &RateLimiter::new()).await, Ok(StatusCode::OK));
+        assert_eq!(stub.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn returns_server_errors_once_the_retries_are_used_up() {
+        let stub =
+            HttpStub::start(|_| StubResponse::new(503, "{}").header("Retry-After", "0")).await;
+        let limiter = RateLimiter::new();
+
+        assert_eq!(
+            get(&stub, &limiter).await,
+            Ok(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        // The first attempt and max_retries (2) retries
+        assert_eq!(stub.requests().len(), 3);
+        // Server errors do not hold back other requests
+        assert!(limiter.pressure_delay().is_none());
+
+        let request = reqwest::Client::new().get(&stub.url);
+        let response = send_with_retry(request, &limiter, 0).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(stub.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn retries_when_the_server_cannot_be_reached() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Nothing listens on the port at first; a server comes up before the retry
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let request = reqwest::Client::new().get(format!("http://{}", address));
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n[]")
+                .await
+                .unwrap();
+        });
+
+        let response = send_with_retry(request, &RateLimiter::new(), 1)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        server.await.unwrap();
+
+        // Without retries the connection error is returned
+        let request = reqwest::Client::new().get(format!("http://{}", address));
+        assert!(send_with_retry(request, &RateLimiter::new(), 0)
+            .await
+            .is_err());
+    }
+}
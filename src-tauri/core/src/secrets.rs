@@ -0,0 +1,460 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{
+    config::{GitLabConfig, TokenSource, DEFAULT_INSTANCE},
+    storage,
+};
+
+// Service name the API tokens are stored under, one entry per instance name
+const SECRET_SERVICE: &str = "gitlab-monitor";
+// AES-GCM nonce length
+const NONCE_LEN: usize = 12;
+
+type SecretError = Box<dyn std::error::Error + Send + Sync>;
+
+// Storage for API tokens, keyed by instance name
+pub trait SecretStore: Send + Sync {
+    // Backend name shown to the user
+    fn name(&self) -> &'static str;
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError>;
+    fn set(&self, key: &str, secret: &str) -> Result<(), SecretError>;
+    // Removing a secret that does not exist is not an error
+    fn delete(&self, key: &str) -> Result<(), SecretError>;
+}
+
+// The platform credential store: the Secret Service (GNOME Keyring, KWallet)
+// on Linux, the Keychain on macOS and the Credential Manager on Windows
+pub struct KeyringStore;
+
+impl KeyringStore {
+    // False when e.g. no Secret Service daemon runs on a headless Linux machine
+    pub fn is_available() -> bool {
+        match keyring::Entry::new(SECRET_SERVICE, "availability-check")
+            .and_then(|entry| entry.get_password())
+        {
+            Ok(_) | Err(keyring::Error::NoEntry) => true,
+            Err(e) => {
+                eprintln!("OS keyring not available: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "OS keyring"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        match keyring::Entry::new(SECRET_SERVICE, key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), SecretError> {
+        keyring::Entry::new(SECRET_SERVICE, key)?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), SecretError> {
+        match keyring::Entry::new(SECRET_SERVICE, key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// Fallback when there is no OS keyring: all secrets in one AES-256-GCM
+// encrypted file. The key lives in a separate owner-only file, so this keeps
+// tokens out of plaintext files and config backups, but unlike the keyring it
+// does not protect them from other programs running as the same user.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    // Held across each read-modify-write, so concurrent updates are not lost
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: &Path) -> Self {
+        EncryptedFileStore {
+            path: dir.join("secrets.enc"),
+            key_path: dir.join("secrets.key"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn cipher(&self, create_key: bool) -> Result<Option<Aes256Gcm>, SecretError> {
+        let key = match std::fs::read(&self.key_path) {
+            Ok(key) => key,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && create_key => {
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                storage::save_private(&self.key_path, &key)?;
+                key
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| format!("{} is not a valid key file", self.key_path.display()))?;
+        Ok(Some(cipher))
+    }
+
+    fn read_all(&self) -> Result<HashMap<String, String>, SecretError> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let cipher = self
+            .cipher(false)?
+            .ok_or_else(|| format!("key file {} is missing", self.key_path.display()))?;
+        if data.len() < NONCE_LEN {
+            return Err(format!("{} is corrupt", self.path.display()).into());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format!("failed to decrypt {}", self.path.display()))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn write_all(&self, secrets: &HashMap<String, String>) -> Result<(), SecretError> {
+        let cipher = self
+            .cipher(true)?
+            .ok_or("secrets key could not be created")?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                serde_json::to_vec(secrets)?.as_slice(),
+            )
+            .map_err(|_| "failed to encrypt secrets")?;
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        storage::save_private(&self.path, &data)
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.read_all()?.remove(key))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), SecretError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.read_all()?;
+        secrets.insert(key.to_string(), secret.to_string());
+        self.write_all(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), SecretError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.read_all()?;
+        if secrets.remove(key).is_some() {
+            self.write_all(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+// Non-persistent store, for tests and throwaway setups
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    secrets: Mutex<HashMap<String, String>>,
+}
+
+impl SecretStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        Ok(self.secrets.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), SecretError> {
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), SecretError> {
+        self.secrets.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+// The OS keyring when it is reachable, otherwise the encrypted file in the
+// data directory. Chosen once per run.
+pub fn secret_store() -> &'static dyn SecretStore {
+    static STORE: Lazy<Box<dyn SecretStore>> =
+        Lazy::new(|| select_store(KeyringStore::is_available(), &storage::data_dir()));
+    STORE.as_ref()
+}
+
+fn select_store(keyring_available: bool, dir: &Path) -> Box<dyn SecretStore> {
+    if keyring_available {
+        Box::new(KeyringStore)
+    } else {
+        eprintln!("Storing API tokens in an encrypted file instead");
+        Box::new(EncryptedFileStore::new(dir))
+    }
+}
+
+// Move plaintext `api_token` keys of a config.toml (top level and
+// `[[instances]]`) into `store` and remove them from the file, keeping the rest
+// of it, comments included. Returns the instances whose token was moved.
+pub fn migrate_plaintext_tokens(
+    path: &Path,
+    store: &dyn SecretStore,
+) -> Result<Vec<String>, SecretError> {
+    let mut document: toml_edit::DocumentMut = std::fs::read_to_string(path)?.parse()?;
+    let mut moved = Vec::new();
+    if let Some(token) = take_token(document.as_table_mut()) {
+        store.set(DEFAULT_INSTANCE, &token)?;
+        moved.push(DEFAULT_INSTANCE.to_string());
+    }
+    if let Some(instances) = document
+        .get_mut("instances")
+        .and_then(|i| i.as_array_of_tables_mut())
+    {
+        for instance in instances.iter_mut() {
+            let Some(name) = instance.get("name").and_then(|n| n.as_str()) else {
+                continue;
+            };
+            let name = name.trim().to_string();
+            if let Some(token) = take_token(instance) {
+                store.set(&name, &token)?;
+                moved.push(name);
+            }
+        }
+    }
+    if !moved.is_empty() {
+        storage::save_private(path, document.to_string().as_bytes())?;
+    }
+    Ok(moved)
+}
+
+// Remove a non-empty string `api_token` from a table and return it
+fn take_token(table: &mut toml_edit::Table) -> Option<String> {
+    let token = table.get("api_token")?.as_str()?.trim().to_string();
+    if token.is_empty() {
+        return None;
+    }
+    // Comments above the key describe what follows it, so they move to the next key
+    let comments = table
+        .key("api_token")
+        .and_then(|key| key.leaf_decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .unwrap_or("")
+        .to_string();
+    let next = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .skip_while(|key| key != "api_token")
+        .nth(1);
+    table.remove("api_token");
+    if let Some(mut key) = next.and_then(|next| table.key_mut(&next)) {
+        let decor = key.leaf_decor_mut();
+        let own = decor.prefix().and_then(|p| p.as_str()).unwrap_or("");
+        let prefix = format!("{}{}", comments, own);
+        decor.set_prefix(prefix);
+    }
+    Some(token)
+}
+
+// Fill in the tokens of instances that have none configured from `store`
+pub fn apply_stored_tokens(config: &mut GitLabConfig, store: &dyn SecretStore) {
+    for instance in config
+        .instances
+        .iter_mut()
+        .filter(|i| i.api_token.is_empty())
+    {
+        match store.get(&instance.name) {
+            Ok(Some(token)) => {
+                instance.api_token = token;
+                instance.token_source = TokenSource::SecretStore;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!(
+                "Failed to read the API token of {} from the {}: {}",
+                instance.name,
+                store.name(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InstanceConfig;
+    use std::sync::Arc;
+
+    fn check_set_get_delete(store: &dyn SecretStore) {
+        assert_eq!(store.get("default").unwrap(), None);
+        store.set("default", "glpat-one").unwrap();
+        store.set("internal", "glpat-two").unwrap();
+        assert_eq!(store.get("default").unwrap().as_deref(), Some("glpat-one"));
+        store.set("default", "glpat-three").unwrap();
+        assert_eq!(
+            store.get("default").unwrap().as_deref(),
+            Some("glpat-three")
+        );
+        store.delete("default").unwrap();
+        assert_eq!(store.get("default").unwrap(), None);
+        assert_eq!(store.get("internal").unwrap().as_deref(), Some("glpat-two"));
+        // Deleting a missing secret is not an error
+        store.delete("default").unwrap();
+    }
+
+    #[test]
+    fn memory_store_sets_gets_and_deletes() {
+        check_set_get_delete(&MemoryStore::default());
+    }
+
+    #[test]
+    fn encrypted_file_store_sets_gets_and_deletes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        check_set_get_delete(&EncryptedFileStore::new(dir));
+
+        // Persisted encrypted, readable by a later instance
+        let data = std::fs::read(dir.join("secrets.enc")).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("glpat-two"));
+        let reopened = EncryptedFileStore::new(dir);
+        assert_eq!(
+            reopened.get("internal").unwrap().as_deref(),
+            Some("glpat-two")
+        );
+
+        // Without its key the file cannot be read
+        std::fs::remove_file(dir.join("secrets.key")).unwrap();
+        assert!(reopened.get("internal").is_err());
+    }
+
+    #[test]
+    fn encrypted_file_store_keeps_concurrent_updates() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = Arc::new(EncryptedFileStore::new(dir));
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    store
+                        .set(&format!("instance-{}", i), &format!("token-{}", i))
+                        .unwrap()
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let reopened = EncryptedFileStore::new(dir);
+        for i in 0..8 {
+            assert_eq!(
+                reopened.get(&format!("instance-{}", i)).unwrap(),
+                Some(format!("token-{}", i))
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_encrypted_file_without_a_keyring() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = select_store(false, dir);
+        assert_eq!(store.name(), "encrypted file");
+        store.set("default", "glpat-one").unwrap();
+        assert!(dir.join("secrets.enc").is_file());
+        assert!(dir.join("secrets.key").is_file());
+    }
+
+    #[test]
+    fn stored_tokens_fill_in_missing_ones() {
+        let store = MemoryStore::default();
+        store.set("default", "from-store").unwrap();
+        store.set("internal", "unused").unwrap();
+        let mut config = GitLabConfig::empty();
+        config.instances = vec![
+            InstanceConfig::new("default", "https://gitlab.com", ""),
+            InstanceConfig::new("internal", "https://gitlab.internal", "from-file"),
+        ];
+        apply_stored_tokens(&mut config, &store);
+        assert_eq!(config.instances[0].api_token, "from-store");
+        assert_eq!(config.instances[0].token_source, TokenSource::SecretStore);
+        assert_eq!(config.instances[1].api_token, "from-file");
+    }
+
+    #[test]
+    fn plaintext_tokens_move_to_the_store() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            r#"# Monitored projects
+api_token = "glpat-default"
+base_url = "https://gitlab.com"
+
+[[projects]]
+path = "grp/app" # the main app
+
+[[instances]]
+name = "internal"
+base_url = "https://gitlab.internal"
+api_token = "glpat-internal"
+
+[[instances]]
+name = "public"
+base_url = "https://gitlab.example.com"
+api_token = ""
+"#,
+        )
+        .unwrap();
+
+        let store = MemoryStore::default();
+        let moved = migrate_plaintext_tokens(&path, &store).unwrap();
+        assert_eq!(moved, ["default", "internal"]);
+        assert_eq!(
+            store.get("default").unwrap().as_deref(),
+            Some("glpat-default")
+        );
+        assert_eq!(
+            store.get("internal").unwrap().as_deref(),
+            Some("glpat-internal")
+        );
+        assert_eq!(store.get("public").unwrap(), None);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("glpat-"), "{}", contents);
+        assert!(contents.contains("# Monitored projects"));
+        assert!(contents.contains("path = \"grp/app\" # the main app"));
+        let file: crate::config::ConfigFile = toml::from_str(&contents).unwrap();
+        assert_eq!(file.instances.len(), 2);
+
+        // Nothing left to move
+        assert!(migrate_plaintext_tokens(&path, &store).unwrap().is_empty());
+    }
+}
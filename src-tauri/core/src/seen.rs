@@ -0,0 +1,298 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::{config::DEFAULT_INSTANCE, models::GitLabRelease, storage};
+
+// A release we have already shown to the user, with a snapshot of the fields
+// used to detect edits. Files written before the snapshot existed lack it; a
+// missing field is None, compares as unchanged and is filled in on `record`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenRelease {
+    #[serde(default = "default_instance")]
+    pub instance: String,
+    pub project_path: String,
+    pub tag_name: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Some(None) is a snapshot of a release without a release date
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub released_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default)]
+    pub prerelease: bool,
+}
+
+fn default_instance() -> String {
+    DEFAULT_INSTANCE.to_string()
+}
+
+// Tell a field stored as null apart from a missing one (which is `default`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+// A project that has been fetched before. Older files list bare project paths,
+// which all belong to the default instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SeenProject {
+    Path(String),
+    OnInstance { instance: String, path: String },
+}
+
+impl SeenProject {
+    fn into_key(self) -> (String, String) {
+        match self {
+            SeenProject::Path(path) => (default_instance(), path),
+            SeenProject::OnInstance { instance, path } => (instance, path),
+        }
+    }
+}
+
+// On-disk layout of the seen state
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SeenFile {
+    #[serde(default)]
+    projects: Vec<SeenProject>,
+    #[serde(default)]
+    releases: Vec<SeenRelease>,
+}
+
+// Releases seen so far, keyed by (instance, project_path, tag_name) and
+// persisted to the platform data directory so releases published while the app
+// was closed are still reported as new on the next start. `projects` lists every project that
+// has been fetched successfully at least once.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(from = "SeenFile", into = "SeenFile")]
+pub struct SeenReleases {
+    projects: HashSet<(String, String)>,
+    releases: HashMap<(String, String, String), SeenRelease>,
+}
+
+impl From<SeenFile> for SeenReleases {
+    fn from(file: SeenFile) -> Self {
+        let releases = file
+            .releases
+            .into_iter()
+            .map(|r| {
+                let key = (
+                    r.instance.clone(),
+                    r.project_path.clone(),
+                    r.tag_name.clone(),
+                );
+                (key, r)
+            })
+            .collect();
+        SeenReleases {
+            projects: file
+                .projects
+                .into_iter()
+                .map(SeenProject::into_key)
+                .collect(),
+            releases,
+        }
+    }
+}
+
+impl From<SeenReleases> for SeenFile {
+    fn from(seen: SeenReleases) -> Self {
+        let mut projects: Vec<(String, String)> = seen.projects.into_iter().collect();
+        projects.sort();
+        let projects = projects
+            .into_iter()
+            .map(|(instance, path)| SeenProject::OnInstance { instance, path })
+            .collect();
+        let mut releases: Vec<SeenRelease> = seen.releases.into_values().collect();
+        releases.sort_by(|a, b| {
+            a.instance
+                .cmp(&b.instance)
+                .then_with(|| a.project_path.cmp(&b.project_path))
+                .then_with(|| a.tag_name.cmp(&b.tag_name))
+        });
+        SeenFile { projects, releases }
+    }
+}
+
+impl SeenReleases {
+    pub fn file_path() -> PathBuf {
+        storage::data_dir().join("seen-releases.json")
+    }
+
+    // Load the seen state from disk, starting empty on first run
+    pub fn load() -> Self {
+        Self::load_from(&Self::file_path())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.save_to(&Self::file_path())
+    }
+
+    // Same as `load`, for a seen state kept somewhere else (the CLI's --state)
+    pub fn load_from(path: &Path) -> Self {
+        storage::load_json(path).unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        storage::save_json(path, self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.releases.is_empty()
+    }
+
+    pub fn contains(&self, instance: &str, project_path: &str, tag_name: &str) -> bool {
+        self.get(instance, project_path, tag_name).is_some()
+    }
+
+    pub fn get(&self, instance: &str, project_path: &str, tag_name: &str) -> Option<&SeenRelease> {
+        self.releases.get(&(
+            instance.to_string(),
+            project_path.to_string(),
+            tag_name.to_string(),
+        ))
+    }
+
+    // Whether this project has been fetched before (false for newly added projects)
+    pub fn has_project(&self, instance: &str, project_path: &str) -> bool {
+        self.projects
+            .contains(&(instance.to_string(), project_path.to_string()))
+    }
+
+    pub fn mark_project(&mut self, instance: &str, project_path: &str) {
+        self.projects
+            .insert((instance.to_string(), project_path.to_string()));
+    }
+
+    pub fn project_releases<'a>(
+        &'a self,
+        instance: &'a str,
+        project_path: &'a str,
+    ) -> impl Iterator<Item = &'a SeenRelease> + 'a {
+        self.releases
+            .values()
+            .filter(move |seen| seen.instance == instance && seen.project_path == project_path)
+    }
+
+    // Mark releases as seen, refreshing `last_seen_at` and the stored snapshot
+    // for known ones
+    pub fn record(&mut self, releases: &[GitLabRelease]) {
+        let now = Utc::now();
+        for release in releases {
+            let seen = self
+                .releases
+                .entry((
+                    release.instance.clone(),
+                    release.project_path.clone(),
+                    release.tag_name.clone(),
+                ))
+                .or_insert_with(|| SeenRelease {
+                    instance: release.instance.clone(),
+                    project_path: release.project_path.clone(),
+                    tag_name: release.tag_name.clone(),
+                    first_seen_at: now,
+                    last_seen_at: now,
+                    name: None,
+                    description: None,
+                    released_at: None,
+                    prerelease: false,
+                });
+            seen.last_seen_at = now;
+            seen.name = Some(release.name.clone());
+            seen.description = Some(release.description.clone());
+            seen.released_at = Some(release.released_at);
+            seen.prerelease = release.is_prerelease();
+        }
+    }
+
+    pub fn forget(&mut self, instance: &str, project_path: &str, tag_name: &str) {
+        self.releases.remove(&(
+            instance.to_string(),
+            project_path.to_string(),
+            tag_name.to_string(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(project_path: &str, tag_name: &str) -> GitLabRelease {
+        GitLabRelease {
+            instance: "work".to_string(),
+            project_name: "app".to_string(),
+            project_path: project_path.to_string(),
+            tag_name: tag_name.to_string(),
+            name: format!("Release {}", tag_name),
+            description: "Notes".to_string(),
+            created_at: "2026-10-01T10:00:00Z".parse().unwrap(),
+            released_at: None,
+            web_url: String::new(),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline: None,
+            environments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn survives_a_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen-releases.json");
+        let mut seen = SeenReleases::default();
+        seen.record(&[release("grp/app", "v1.0.0"), release("grp/app", "v1.1.0")]);
+        seen.mark_project("work", "grp/app");
+        seen.mark_project("work", "grp/empty");
+        seen.save_to(&path).unwrap();
+
+        let loaded = SeenReleases::load_from(&path);
+        assert!(loaded.has_project("work", "grp/app"));
+        assert!(loaded.has_project("work", "grp/empty"));
+        assert!(!loaded.has_project("default", "grp/app"));
+        let original = seen.get("work", "grp/app", "v1.1.0").unwrap();
+        let restored = loaded.get("work", "grp/app", "v1.1.0").unwrap();
+        assert_eq!(restored.first_seen_at, original.first_seen_at);
+        assert_eq!(restored.name.as_deref(), Some("Release v1.1.0"));
+        assert_eq!(restored.description.as_deref(), Some("Notes"));
+        // A snapshot of a release without a date is kept apart from no snapshot
+        assert_eq!(restored.released_at, Some(None));
+        assert_eq!(loaded.project_releases("work", "grp/app").count(), 2);
+    }
+
+    #[test]
+    fn starts_empty_without_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(SeenReleases::load_from(&dir.path().join("missing.json")).is_empty());
+    }
+
+    #[test]
+    fn reads_files_listing_bare_project_paths() {
+        let seen: SeenReleases = serde_json::from_str(
+            r#"{"projects": ["grp/app"], "releases": [{
+                "project_path": "grp/app",
+                "tag_name": "v1.0.0",
+                "first_seen_at": "2026-09-01T10:00:00Z",
+                "last_seen_at": "2026-09-01T10:00:00Z"
+            }]}"#,
+        )
+        .unwrap();
+        assert!(seen.has_project(DEFAULT_INSTANCE, "grp/app"));
+        let release = seen.get(DEFAULT_INSTANCE, "grp/app", "v1.0.0").unwrap();
+        assert_eq!(release.released_at, None);
+    }
+}
@@ -0,0 +1,426 @@
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{
+    cache::{CachedPage, ReleaseCache},
+    config::{GitLabConfig, InstanceConfig},
+    gitea::GiteaSource,
+    github::GitHubSource,
+    gitlab::{header_string, next_page_url, GitLabSource, ProjectReleases},
+    models::{GitLabRelease, ProjectInfo},
+    rate_limit::{send_with_retry, RateLimiter},
+};
+
+type SourceResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Kind of server an instance is, which decides the API its releases are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Provider {
+    #[default]
+    GitLab,
+    // github.com or GitHub Enterprise Server
+    GitHub,
+    // Gitea and its fork Forgejo
+    Gitea,
+}
+
+impl Provider {
+    // API URL used when an instance does not set `base_url`; self-hosted-only
+    // providers have none
+    pub fn default_base_url(self) -> Option<&'static str> {
+        match self {
+            Provider::GitLab => Some("https://gitlab.com"),
+            Provider::GitHub => Some("https://api.github.com"),
+            Provider::Gitea => None,
+        }
+    }
+
+    // Fetch every release of a project from this provider, newest first
+    pub async fn fetch_releases(
+        self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> SourceResult<ProjectReleases> {
+        match self {
+            Provider::GitLab => GitLabSource.fetch_releases(ctx, project_path).await,
+            Provider::GitHub => GitHubSource.fetch_releases(ctx, project_path).await,
+            Provider::Gitea => GiteaSource.fetch_releases(ctx, project_path).await,
+        }
+    }
+
+    // Check that a project exists on this provider and can be read
+    pub async fn lookup_project(
+        self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> SourceResult<ProjectInfo> {
+        match self {
+            Provider::GitLab => GitLabSource.lookup_project(ctx, project_path).await,
+            Provider::GitHub => GitHubSource.lookup_project(ctx, project_path).await,
+            Provider::Gitea => GiteaSource.lookup_project(ctx, project_path).await,
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "gitlab" => Ok(Provider::GitLab),
+            "github" => Ok(Provider::GitHub),
+            "gitea" | "forgejo" => Ok(Provider::Gitea),
+            other => Err(format!(
+                "unknown provider '{}' (expected gitlab, github or gitea)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provider::GitLab => write!(f, "gitlab"),
+            Provider::GitHub => write!(f, "github"),
+            Provider::Gitea => write!(f, "gitea"),
+        }
+    }
+}
+
+impl Serialize for Provider {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Provider {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// What a source needs to talk to one instance
+pub struct SourceContext<'a> {
+    pub client: &'a reqwest::Client,
+    pub config: &'a GitLabConfig,
+    pub instance: &'a InstanceConfig,
+    pub cache: &'a AsyncMutex<ReleaseCache>,
+    pub limiter: &'a RateLimiter,
+}
+
+// A server API releases are read from. Each `Provider` has one.
+pub trait ReleaseSource: Sync {
+    // Add the instance's credentials (and any headers the API requires)
+    fn authorize(&self, request: RequestBuilder, instance: &InstanceConfig) -> RequestBuilder;
+
+    // Fetch every release of a project, newest first, up to `max_pages` pages
+    fn fetch_releases(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> impl Future<Output = SourceResult<ProjectReleases>> + Send;
+
+    // Check that a project exists and the token can read it
+    fn lookup_project(
+        &self,
+        ctx: &SourceContext<'_>,
+        project_path: &str,
+    ) -> impl Future<Output = SourceResult<ProjectInfo>> + Send;
+}
+
+// GET a JSON document. The body is only parsed for successful responses,
+// otherwise it is null.
+pub async fn get_json<S: ReleaseSource>(
+    source: &S,
+    ctx: &SourceContext<'_>,
+    url: &str,
+) -> SourceResult<(StatusCode, serde_json::Value)> {
+    let request = ctx
+        .client
+        .get(url)
+        .timeout(Duration::from_secs(ctx.config.request_timeout_secs));
+    let request = source.authorize(request, ctx.instance);
+    let response = send_with_retry(request, ctx.limiter, ctx.config.max_retries).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Ok((status, serde_json::Value::Null));
+    }
+    Ok((status, response.json().await?))
+}
+
+// Turn the answer of a project lookup into an error message, or the project
+pub fn lookup_result(
+    ctx: &SourceContext<'_>,
+    project_path: &str,
+    answer: (StatusCode, serde_json::Value),
+) -> SourceResult<serde_json::Value> {
+    match answer {
+        (status, project) if status.is_success() => Ok(project),
+        (StatusCode::NOT_FOUND, _) => Err(format!(
+            "Project {} was not found on {} (or the token cannot access it)",
+            project_path, ctx.instance.name
+        )
+        .into()),
+        (StatusCode::UNAUTHORIZED, _) => {
+            Err(format!("The API token of {} was rejected", ctx.instance.name).into())
+        }
+        (status, _) => Err(format!("Failed to look up {}: {}", project_path, status).into()),
+    }
+}
+
+// A release page the server answered with an error status
+#[derive(Debug)]
+pub struct PageRejected {
+    pub project_path: String,
+    pub status: StatusCode,
+    pub first_page: bool,
+}
+
+impl fmt::Display for PageRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to fetch releases for {}: {}",
+            self.project_path, self.status
+        )
+    }
+}
+
+impl std::error::Error for PageRejected {}
+
+// Fetch a release list paginated with `Link` headers, starting at `url`.
+// Pages are requested conditionally and served from the cache when the server
+// answers 304 Not Modified; `parse` converts one page.
+pub async fn fetch_linked_pages<S, P>(
+    source: &S,
+    ctx: &SourceContext<'_>,
+    project_path: &str,
+    mut url: String,
+    parse: P,
+) -> SourceResult<ProjectReleases>
+where
+    S: ReleaseSource,
+    P: Fn(&str, &str, Vec<serde_json::Value>) -> SourceResult<Vec<GitLabRelease>>,
+{
+    let mut releases = Vec::new();
+    let mut pages = 0;
+    let mut truncated = false;
+
+    loop {
+        let cached = ctx.cache.lock().await.get(&url).cloned();
+
+        let mut request = ctx
+            .client
+            .get(&url)
+            .timeout(Duration::from_secs(ctx.config.request_timeout_secs));
+        request = source.authorize(request, ctx.instance);
+        if let Some(page) = &cached {
+            if let Some(etag) = &page.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &page.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = send_with_retry(request, ctx.limiter, ctx.config.max_retries).await?;
+
+        let status = response.status();
+        let next_url = if status == StatusCode::NOT_MODIFIED {
+            // Unchanged since the last refresh - reuse the cached page
+            let page = cached
+                .ok_or_else(|| format!("Got 304 for {} without a cached response", project_path))?;
+            releases.extend(page.releases);
+            page.next_url
+        } else {
+            if !status.is_success() {
+                return Err(Box::new(PageRejected {
+                    project_path: project_path.to_string(),
+                    status,
+                    first_page: pages == 0,
+                }));
+            }
+
+            let headers = response.headers();
+            let etag = header_string(headers, ETAG);
+            let last_modified = header_string(headers, LAST_MODIFIED);
+            let next_url = next_page_url(headers, &url);
+
+            let releases_json: Vec<serde_json::Value> = response.json().await?;
+            let page_releases = parse(&ctx.instance.name, project_path, releases_json)?;
+
+            if etag.is_some() || last_modified.is_some() {
+                ctx.cache.lock().await.insert(
+                    &url,
+                    CachedPage {
+                        etag,
+                        last_modified,
+                        next_url: next_url.clone(),
+                        releases: page_releases.clone(),
+                    },
+                );
+            }
+            releases.extend(page_releases);
+            next_url
+        };
+        pages += 1;
+
+        match next_url {
+            Some(next) if pages < ctx.config.max_pages => url = next,
+            Some(_) => {
+                eprintln!(
+                    "Stopped fetching releases for {} after {} pages (GITLAB_MAX_PAGES)",
+                    project_path, pages
+                );
+                truncated = true;
+                break;
+            }
+            None => break,
+        }
+    }
+
+    // The rest of the app expects the newest release first, which these APIs
+    // only roughly guarantee
+    releases.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+    Ok(ProjectReleases {
+        releases,
+        truncated,
+        pipeline: None,
+        deployments: Vec::new(),
+    })
+}
+
+// Split `owner/repo`, the only project path form GitHub and Gitea have
+pub fn owner_and_repo(project_path: &str) -> SourceResult<(&str, &str)> {
+    match project_path.split('/').collect::<Vec<_>>()[..] {
+        [owner, repo] if !owner.is_empty() && !repo.is_empty() => Ok((owner, repo)),
+        _ => Err(format!("'{}' is not an owner/repository path", project_path).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        github::parse_releases,
+        test_support::{HttpStub, StubResponse},
+    };
+
+    const ETAG_PAGE: &str = "\"first\"";
+    const LAST_MODIFIED_PAGE: &str = "Thu, 01 Oct 2026 10:00:00 GMT";
+
+    // Two pages: the first validated by an ETag and linking to the second, the
+    // second by Last-Modified. Both answer 304 when revalidated.
+    async fn two_page_stub() -> HttpStub {
+        HttpStub::start(|request| {
+            if request.path.contains("page=2") {
+                if request.header("if-modified-since") == Some(LAST_MODIFIED_PAGE) {
+                    return StubResponse::new(304, "");
+                }
+                StubResponse::new(
+                    200,
+                    r#"[{"tag_name": "v1.0.0", "created_at": "2026-10-01T10:00:00Z"}]"#,
+                )
+                .header("Last-Modified", LAST_MODIFIED_PAGE)
+            } else {
+                if request.header("if-none-match") == Some(ETAG_PAGE) {
+                    return StubResponse::new(304, "");
+                }
+                StubResponse::new(
+                    200,
+                    r#"[{"tag_name": "v1.1.0", "created_at": "2026-10-02T10:00:00Z"}]"#,
+                )
+                .header("ETag", ETAG_PAGE)
+                .header("Link", "</releases?page=2>; rel=\"next\"")
+            }
+        })
+        .await
+    }
+
+    async fn fetch(stub: &HttpStub, cache: &AsyncMutex<ReleaseCache>) -> SourceResult<Vec<String>> {
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 0;
+        let instance = InstanceConfig::new("github", &stub.url, "");
+        let client = reqwest::Client::new();
+        let limiter = RateLimiter::new();
+        let ctx = SourceContext {
+            client: &client,
+            config: &config,
+            instance: &instance,
+            cache,
+            limiter: &limiter,
+        };
+        let url = format!("{}/releases", stub.url);
+        let fetched =
+            fetch_linked_pages(&GitHubSource, &ctx, "octo/app", url, parse_releases).await?;
+        Ok(fetched.releases.into_iter().map(|r| r.tag_name).collect())
+    }
+
+    #[tokio::test]
+    async fn revalidates_cached_pages() {
+        let stub = two_page_stub().await;
+        let cache = AsyncMutex::new(ReleaseCache::default());
+
+        assert_eq!(fetch(&stub, &cache).await.unwrap(), ["v1.1.0", "v1.0.0"]);
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(
+            |r| r.header("if-none-match").is_none() && r.header("if-modified-since").is_none()
+        ));
+
+        // Both pages answer 304: their releases, and the link from the first page
+        // to the second, come from the cache
+        assert_eq!(fetch(&stub, &cache).await.unwrap(), ["v1.1.0", "v1.0.0"]);
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[2].path, "/releases");
+        assert_eq!(requests[2].header("if-none-match"), Some(ETAG_PAGE));
+        assert_eq!(requests[3].path, "/releases?page=2");
+        assert_eq!(
+            requests[3].header("if-modified-since"),
+            Some(LAST_MODIFIED_PAGE)
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_without_validators_are_not_cached() {
+        let stub = HttpStub::start(|_| {
+            StubResponse::new(
+                200,
+                r#"[{"tag_name": "v1.0.0", "created_at": "2026-10-01T10:00:00Z"}]"#,
+            )
+        })
+        .await;
+        let cache = AsyncMutex::new(ReleaseCache::default());
+
+        fetch(&stub, &cache).await.unwrap();
+        fetch(&stub, &cache).await.unwrap();
+        let url = format!("{}/releases", stub.url);
+        assert!(cache.lock().await.get(&url).is_none());
+        assert!(stub
+            .requests()
+            .iter()
+            .all(|r| r.header("if-none-match").is_none()));
+    }
+
+    #[tokio::test]
+    async fn a_304_without_a_cached_page_is_an_error() {
+        let stub = HttpStub::start(|_| StubResponse::new(304, "")).await;
+        let cache = AsyncMutex::new(ReleaseCache::default());
+
+        let error = fetch(&stub, &cache).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Got 304 for octo/app without a cached response"
+        );
+    }
+}
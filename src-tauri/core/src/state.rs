@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::{
+    cache::ReleaseCache,
+    config::{GitLabConfig, InstanceConfig},
+    deployments::detect_new_deployments,
+    digest::DigestQueue,
+    gitlab::{detect_release_changes, latest_releases, FetchedReleases, ProjectSnapshot},
+    hooks::HookHistory,
+    models::{Deployment, GitLabRelease, Pipeline, ReleaseChangeSet},
+    pipelines::detect_pipeline_changes,
+    rate_limit::RateLimiter,
+    seen::SeenReleases,
+};
+
+// Where an `AppState` keeps what it persists between runs
+#[derive(Debug, Clone)]
+pub struct StateFiles {
+    pub seen: PathBuf,
+    pub release_cache: PathBuf,
+    pub digest_queue: PathBuf,
+    pub hook_history: PathBuf,
+}
+
+impl StateFiles {
+    // The tray app's files in the platform data and cache directories
+    pub fn app() -> Self {
+        StateFiles {
+            seen: SeenReleases::file_path(),
+            release_cache: ReleaseCache::file_path(),
+            digest_queue: DigestQueue::file_path(),
+            hook_history: HookHistory::file_path(),
+        }
+    }
+
+    // Every file in `dir`, e.g. a temporary directory for tests
+    pub fn in_dir(dir: &Path) -> Self {
+        StateFiles {
+            seen: dir.join("seen-releases.json"),
+            release_cache: dir.join("releases-cache.json"),
+            digest_queue: dir.join("digest-queue.json"),
+            hook_history: dir.join("hook-history.json"),
+        }
+    }
+}
+
+// Shared state of a running monitor. Cloning is cheap and the clones share
+// everything, so a clone can be handed to a background task such as the `Poller`.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    // Swapped as a whole when the configuration file is reloaded
+    pub config: Arc<RwLock<GitLabConfig>>,
+    pub releases: Arc<Mutex<Vec<GitLabRelease>>>,
+    // Latest default branch pipeline per (instance, project path) watching it
+    pub branch_pipelines: Arc<Mutex<HashMap<(String, String), Pipeline>>>,
+    // Current deployment per environment of the projects tracking them
+    pub deployments: Arc<Mutex<Vec<Deployment>>>,
+    pub seen_releases: Arc<Mutex<SeenReleases>>,
+    // Where `seen_releases` is persisted
+    pub seen_file: PathBuf,
+    // HTTP client per instance name, with the settings it was built from
+    pub clients: Arc<Mutex<HashMap<String, (InstanceConfig, reqwest::Client)>>>,
+    pub has_new_releases: Arc<Mutex<bool>>,
+    // Changes accumulated since the user last looked at the window
+    pub pending_changes: Arc<Mutex<ReleaseChangeSet>>,
+    pub release_cache: Arc<Mutex<ReleaseCache>>,
+    // Where `release_cache` is persisted
+    pub cache_file: PathBuf,
+    // Rate limit state per instance name, so one instance's limits never hold
+    // back requests to another
+    pub rate_limiters: Arc<Mutex<HashMap<String, Arc<RateLimiter>>>>,
+    // Last successful fetch per (instance, project path)
+    pub project_snapshots: Arc<Mutex<HashMap<(String, String), ProjectSnapshot>>>,
+    // Background refresh task, restarted whenever the configuration changes
+    pub poller: Arc<Mutex<Option<JoinHandle<()>>>>,
+    // New releases waiting for the end of quiet hours to be notified
+    pub held_notifications: Arc<Mutex<Vec<GitLabRelease>>>,
+    // Release page of the last desktop notification, opened from the tray menu
+    pub last_notified_url: Arc<Mutex<Option<String>>>,
+    // New releases waiting for the next email digest
+    pub digest_queue: Arc<Mutex<DigestQueue>>,
+    // Where `digest_queue` is persisted
+    pub digest_file: PathBuf,
+    // Latest runs of the `on_release` hooks
+    pub hook_history: Arc<Mutex<HookHistory>>,
+    // Where `hook_history` is persisted
+    pub hook_history_file: PathBuf,
+    // Why the configuration could not be (re)loaded, reported by the diagnostics
+    pub config_error: Arc<Mutex<Option<String>>>,
+}
+
+impl AppState {
+    // Fresh state for `config`, with the persisted seen state, cache, digest
+    // queue and hook history loaded from disk
+    pub fn new(config: GitLabConfig) -> Self {
+        Self::with_files(config, StateFiles::app())
+    }
+
+    // Same as `new`, with the persisted state kept in `files`
+    pub fn with_files(config: GitLabConfig, files: StateFiles) -> Self {
+        AppState {
+            config: Arc::new(RwLock::new(config)),
+            releases: Arc::new(Mutex::new(Vec::new())),
+            branch_pipelines: Arc::new(Mutex::new(HashMap::new())),
+            deployments: Arc::new(Mutex::new(Vec::new())),
+            seen_releases: Arc::new(Mutex::new(SeenReleases::load_from(&files.seen))),
+            seen_file: files.seen,
+            clients: Arc::new(Mutex::new(Default::default())),
+            has_new_releases: Arc::new(Mutex::new(false)),
+            pending_changes: Arc::new(Mutex::new(Default::default())),
+            release_cache: Arc::new(Mutex::new(ReleaseCache::load_from(&files.release_cache))),
+            cache_file: files.release_cache,
+            rate_limiters: Arc::new(Mutex::new(HashMap::new())),
+            project_snapshots: Arc::new(Mutex::new(Default::default())),
+            poller: Arc::new(Mutex::new(None)),
+            held_notifications: Arc::new(Mutex::new(Vec::new())),
+            last_notified_url: Arc::new(Mutex::new(None)),
+            digest_queue: Arc::new(Mutex::new(DigestQueue::load_from(&files.digest_queue))),
+            digest_file: files.digest_queue,
+            hook_history: Arc::new(Mutex::new(HookHistory::load_from(&files.hook_history))),
+            hook_history_file: files.hook_history,
+            config_error: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Rate limiter of an instance, created on first use
+    pub async fn rate_limiter(&self, instance: &str) -> Arc<RateLimiter> {
+        self.rate_limiters
+            .lock()
+            .await
+            .entry(instance.to_string())
+            .or_default()
+            .clone()
+    }
+
+    // Snapshot of the current configuration, unaffected by later reloads
+    pub async fn current_config(&self) -> GitLabConfig {
+        self.config.read().await.clone()
+    }
+
+    // Store a refresh and work out what changed since the last one. Returns the
+    // latest release per tag family (for display) and the change set.
+    // Projects fetched for the first time only seed the seen state, so neither
+    // the very first run nor a newly added project flags its whole history.
+    pub async fn apply_releases(
+        &self,
+        fetched: &FetchedReleases,
+    ) -> (Vec<GitLabRelease>, ReleaseChangeSet) {
+        let config = self.current_config().await;
+        let mut seen = self.seen_releases.lock().await;
+        let mut changes = detect_release_changes(
+            &fetched.releases,
+            &seen,
+            &fetched.complete_projects,
+            &config,
+        );
+
+        for removed in &changes.removed {
+            seen.forget(&removed.instance, &removed.project_path, &removed.tag_name);
+        }
+        seen.record(&fetched.releases);
+        for release in &fetched.releases {
+            seen.mark_project(&release.instance, &release.project_path);
+        }
+        for (instance, project_path) in &fetched.complete_projects {
+            seen.mark_project(instance, project_path);
+        }
+        if let Err(e) = seen.save_to(&self.seen_file) {
+            eprintln!("Failed to save seen releases: {}", e);
+        }
+        drop(seen);
+
+        let latest = latest_releases(&fetched.releases, &config);
+        let mut current = self.releases.lock().await;
+        let mut branch_pipelines = self.branch_pipelines.lock().await;
+        changes.pipelines = detect_pipeline_changes(
+            &current,
+            &latest,
+            &branch_pipelines,
+            &fetched.branch_pipelines,
+            &config,
+        );
+        *current = latest.clone();
+        *branch_pipelines = fetched.branch_pipelines.clone();
+        drop(branch_pipelines);
+        drop(current);
+
+        let mut deployments = self.deployments.lock().await;
+        changes.deployed = detect_new_deployments(&deployments, &fetched.deployments);
+        *deployments = fetched.deployments.clone();
+        drop(deployments);
+
+        // Only projects with notifications enabled flag the tray
+        let notify_changes = notifiable_changes(&config, &changes);
+        if !notify_changes.is_empty() {
+            let mut has_new = self.has_new_releases.lock().await;
+            *has_new = true;
+            drop(has_new);
+
+            let mut pending = self.pending_changes.lock().await;
+            pending.extend(notify_changes);
+        }
+
+        (latest, changes)
+    }
+
+    // Forget about pending changes once the user has seen them
+    pub async fn clear_pending_changes(&self) {
+        let mut has_new = self.has_new_releases.lock().await;
+        *has_new = false;
+        drop(has_new);
+
+        let mut pending = self.pending_changes.lock().await;
+        *pending = ReleaseChangeSet::default();
+    }
+}
+
+// The part of a change set belonging to projects with `notify` enabled
+pub fn notifiable_changes(config: &GitLabConfig, changes: &ReleaseChangeSet) -> ReleaseChangeSet {
+    let notify = |instance: &str, path: &str| config.project_settings(instance, path).notify;
+    ReleaseChangeSet {
+        added: changes
+            .added
+            .iter()
+            .filter(|r| notify(&r.instance, &r.project_path))
+            .cloned()
+            .collect(),
+        updated: changes
+            .updated
+            .iter()
+            .filter(|u| notify(&u.release.instance, &u.release.project_path))
+            .cloned()
+            .collect(),
+        removed: changes
+            .removed
+            .iter()
+            .filter(|r| notify(&r.instance, &r.project_path))
+            .cloned()
+            .collect(),
+        pipelines: changes
+            .pipelines
+            .iter()
+            .filter(|p| notify(&p.instance, &p.project_path))
+            .cloned()
+            .collect(),
+        deployed: changes
+            .deployed
+            .iter()
+            .filter(|d| notify(&d.instance, &d.project_path))
+            .cloned()
+            .collect(),
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
+const APP_DIR_NAME: &str = "gitlab-monitor";
+
+// Per-user cache directory for this app (e.g. ~/.cache/gitlab-monitor)
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+// Per-user data directory for this app (e.g. ~/.local/share/gitlab-monitor)
+pub fn data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .or_else(dirs::data_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+// Read a JSON file, returning `None` when it is missing or unreadable
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let data = std::fs::read(path).ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Ignoring unreadable file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Temp file a write goes to before it is renamed over `path`. Named after the
+// whole file name, so e.g. secrets.enc and secrets.key never share one.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+// Write a JSON file atomically (write to a temp file, then rename over the target)
+pub fn save_json<T: Serialize>(
+    path: &Path,
+    value: &T,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = tmp_path(path);
+    std::fs::write(&tmp_path, serde_json::to_vec(value)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// Write a file only the current user can read, atomically like `save_json`
+pub fn save_private(
+    path: &Path,
+    data: &[u8],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = tmp_path(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp_path)?;
+    std::io::Write::write_all(&mut file, data)?;
+    drop(file);
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
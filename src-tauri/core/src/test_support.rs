@@ -0,0 +1,248 @@
+// Helpers shared by the unit tests
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// A request received by `HttpStub`
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    // Path and query, e.g. `/api/v4/projects?starred=true`
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+// Answer of `HttpStub`; a `(status, body)` pair converts into one
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    // JSON body
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn new(status: u16, body: &str) -> Self {
+        StubResponse {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl From<(u16, String)> for StubResponse {
+    fn from((status, body): (u16, String)) -> Self {
+        StubResponse::new(status, &body)
+    }
+}
+
+type Responder = dyn Fn(&StubRequest) -> StubResponse + Send + Sync;
+
+// Minimal HTTP/1.1 server on a local port that records every request and
+// answers with what `respond` returns
+pub struct HttpStub {
+    pub url: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+}
+
+impl HttpStub {
+    pub async fn start<T: Into<StubResponse>>(
+        respond: impl Fn(&StubRequest) -> T + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let respond: Arc<Responder> = Arc::new(move |request| respond(request).into());
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, &recorded, respond.as_ref()).await;
+                });
+            }
+        });
+        HttpStub { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    recorded: &Mutex<Vec<StubRequest>>,
+    respond: &Responder,
+) -> std::io::Result<()> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let head_end = loop {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let length: usize = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    while data.len() < head_end + length {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read]);
+    }
+    let body = String::from_utf8_lossy(&data[head_end..]).to_string();
+
+    let request = StubRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let response = respond(&request);
+    recorded.lock().unwrap().push(request);
+
+    let mut head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    let response = format!("{}\r\n{}", head, response.body);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// A message received by `SmtpSink`
+#[derive(Debug, Clone, Default)]
+pub struct SmtpMessage {
+    // Address given by MAIL FROM and the RCPT TO addresses
+    pub from: String,
+    pub to: Vec<String>,
+    // Headers and body as sent after DATA
+    pub data: String,
+    // Initial response of AUTH PLAIN, if the client logged in
+    pub auth: Option<String>,
+}
+
+// Minimal SMTP server on a local port that accepts and records every message
+pub struct SmtpSink {
+    pub port: u16,
+    messages: Arc<Mutex<Vec<SmtpMessage>>>,
+}
+
+impl SmtpSink {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = messages.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    let _ = receive(stream, &recorded).await;
+                });
+            }
+        });
+        SmtpSink { port, messages }
+    }
+
+    pub fn messages(&self) -> Vec<SmtpMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+async fn receive(stream: TcpStream, recorded: &Mutex<Vec<SmtpMessage>>) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer.write_all(b"220 localhost ESMTP sink\r\n").await?;
+
+    let mut message = SmtpMessage::default();
+    while let Some(line) = lines.next_line().await? {
+        let command = line.to_ascii_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") {
+            b"250-localhost\r\n250 AUTH PLAIN\r\n"
+        } else if command.starts_with("AUTH PLAIN") {
+            message.auth = line.split_whitespace().nth(2).map(str::to_string);
+            b"235 Authenticated\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            message.from = address(&line);
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            message.to.push(address(&line));
+            b"250 OK\r\n"
+        } else if command == "DATA" {
+            writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await?;
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                message.data.push_str(&line);
+                message.data.push('\n');
+            }
+            recorded.lock().unwrap().push(std::mem::take(&mut message));
+            b"250 Queued\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else {
+            b"250 OK\r\n"
+        };
+        writer.write_all(reply).await?;
+    }
+    Ok(())
+}
+
+// `user@example.org` from `MAIL FROM:<user@example.org>`
+fn address(line: &str) -> String {
+    line.split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_string())
+        .unwrap_or_default()
+}
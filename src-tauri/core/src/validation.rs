@@ -0,0 +1,657 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{
+    config::{
+        ConfigFile, EmailFile, GitLabConfig, InstanceConfig, InstanceFile, NotificationSettings,
+        ProjectConfig, ProjectDefaultsFile, ProjectFile, TokenSource, WebhookFile,
+        DEFAULT_DISCOVER_INTERVAL_SECS, DEFAULT_HOOK_TIMEOUT_SECS, DEFAULT_INSTANCE,
+        DEFAULT_MAX_CONCURRENCY, DEFAULT_MAX_PAGES, DEFAULT_MAX_RETRIES,
+        DEFAULT_NOTIFICATION_BATCH_SIZE, DEFAULT_PER_PAGE, DEFAULT_POLL_INTERVAL_SECS,
+        DEFAULT_REFRESH_TIMEOUT_SECS, DEFAULT_REQUEST_TIMEOUT_SECS, MAX_PER_PAGE,
+        MIN_POLL_INTERVAL_SECS,
+    },
+    digest::{EmailSettings, SmtpTls},
+    discovery::{DiscoveryRule, ProjectSource},
+    sources::{owner_and_repo, Provider},
+    webhooks::{WebhookConfig, WebhookKind},
+};
+
+impl ConfigFile {
+    // Check values serde cannot check and build the runtime configuration.
+    // Errors name the offending key, e.g. "instances[1].projects[2].poll_interval".
+    pub fn into_config(self) -> Result<GitLabConfig, String> {
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if per_page == 0 || per_page > MAX_PER_PAGE {
+            return Err(format!(
+                "per_page must be between 1 and {}, got {}",
+                MAX_PER_PAGE, per_page
+            ));
+        }
+        let max_pages = self.max_pages.unwrap_or(DEFAULT_MAX_PAGES);
+        if max_pages == 0 {
+            return Err("max_pages must be at least 1".to_string());
+        }
+        let max_concurrency = self.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        if max_concurrency == 0 {
+            return Err("max_concurrency must be at least 1".to_string());
+        }
+        let request_timeout_secs = self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+        if request_timeout_secs == 0 {
+            return Err("request_timeout must be at least 1 second".to_string());
+        }
+        let refresh_timeout_secs = self.refresh_timeout.unwrap_or(DEFAULT_REFRESH_TIMEOUT_SECS);
+        if refresh_timeout_secs == 0 {
+            return Err("refresh_timeout must be at least 1 second".to_string());
+        }
+        let hook_timeout_secs = self.hook_timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS);
+        if hook_timeout_secs == 0 {
+            return Err("hook_timeout must be at least 1 second".to_string());
+        }
+
+        let poll_interval_secs = check_poll_interval(
+            "poll_interval",
+            self.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+        )?;
+        let defaults = self.defaults;
+        let default_poll_interval = match defaults.poll_interval {
+            Some(value) => check_poll_interval("defaults.poll_interval", value)?,
+            None => poll_interval_secs,
+        };
+
+        let notifications = NotificationSettings {
+            enabled: self.notifications.enabled.unwrap_or(true),
+            quiet_hours: self.notifications.quiet_hours,
+            batch_size: self
+                .notifications
+                .batch_size
+                .unwrap_or(DEFAULT_NOTIFICATION_BATCH_SIZE),
+        };
+
+        let webhooks = self
+            .webhooks
+            .into_iter()
+            .enumerate()
+            .map(|(index, webhook)| parse_webhook(&format!("webhooks[{}]", index), webhook))
+            .collect::<Result<Vec<_>, _>>()?;
+        let email = self
+            .email
+            .map(|email| parse_email("email", email))
+            .transpose()?;
+
+        let discover_interval_secs = check_poll_interval(
+            "discover_interval",
+            self.discover_interval
+                .unwrap_or(DEFAULT_DISCOVER_INTERVAL_SECS),
+        )?;
+
+        // Top-level projects on another provider than the default instance are
+        // watched on the instance named after their provider, e.g. "github"
+        let provider = self.provider.unwrap_or_default();
+        let mut instance_files = self.instances;
+        let mut default_projects = Vec::new();
+        for (index, project) in self.projects.into_iter().enumerate() {
+            let project_provider = project.provider.unwrap_or(provider);
+            if project_provider == provider {
+                default_projects.push(project);
+                continue;
+            }
+            let name = project_provider.to_string();
+            match instance_files.iter_mut().find(|i| i.name.trim() == name) {
+                Some(instance) if instance.provider.unwrap_or_default() == project_provider => {
+                    instance.projects.push(project)
+                }
+                Some(_) => {
+                    return Err(format!(
+                        "projects[{}].provider: instance '{}' is not a {} instance",
+                        index, name, project_provider
+                    ))
+                }
+                None if project_provider.default_base_url().is_none() => {
+                    return Err(format!(
+                        "projects[{}].provider: {} projects must be listed under an [[instances]] entry with its base_url",
+                        index, project_provider
+                    ))
+                }
+                None => instance_files.push(InstanceFile {
+                    name,
+                    provider: Some(project_provider),
+                    base_url: None,
+                    api_token: None,
+                    ca_cert: None,
+                    accept_invalid_certs: None,
+                    projects: vec![project],
+                }),
+            }
+        }
+
+        let mut instances = Vec::new();
+        let mut projects = Vec::new();
+        let mut discovery = Vec::new();
+
+        if !default_projects.is_empty() {
+            // Without a token here or in GITLAB_API_TOKEN the secret store is used
+            let api_token = match self.api_token {
+                Some(token) if !token.trim().is_empty() => Some((token, TokenSource::ConfigFile)),
+                _ => std::env::var("GITLAB_API_TOKEN")
+                    .ok()
+                    .filter(|token| !token.trim().is_empty())
+                    .map(|token| (token, TokenSource::Environment)),
+            };
+            instances.push(parse_instance(
+                "",
+                DEFAULT_INSTANCE,
+                provider,
+                self.base_url,
+                api_token,
+                self.ca_cert,
+                self.accept_invalid_certs,
+            )?);
+            let (listed, rules) = parse_projects(
+                "",
+                DEFAULT_INSTANCE,
+                provider,
+                default_projects,
+                &defaults,
+                default_poll_interval,
+            )?;
+            projects.extend(listed);
+            discovery.extend(rules);
+        }
+
+        for (index, instance) in instance_files.into_iter().enumerate() {
+            let prefix = format!("instances[{}].", index);
+            let name = instance.name.trim().to_string();
+            if name.is_empty() {
+                return Err(format!("{}name must not be empty", prefix));
+            }
+            if instances.iter().any(|i: &InstanceConfig| i.name == name) {
+                return Err(format!(
+                    "{}name: instance '{}' is configured more than once{}",
+                    prefix,
+                    name,
+                    if name == DEFAULT_INSTANCE {
+                        " (top-level [[projects]] belong to the \"default\" instance)"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            let api_token = instance
+                .api_token
+                .filter(|token| !token.trim().is_empty())
+                .map(|token| (token, TokenSource::ConfigFile));
+
+            let provider = instance.provider.unwrap_or_default();
+            instances.push(parse_instance(
+                &prefix,
+                &name,
+                provider,
+                instance.base_url,
+                api_token,
+                instance.ca_cert,
+                instance.accept_invalid_certs,
+            )?);
+            let (listed, rules) = parse_projects(
+                &prefix,
+                &name,
+                provider,
+                instance.projects,
+                &defaults,
+                default_poll_interval,
+            )?;
+            projects.extend(listed);
+            discovery.extend(rules);
+        }
+
+        Ok(GitLabConfig {
+            instances,
+            projects,
+            discovery,
+            discover_interval_secs,
+            per_page,
+            max_pages,
+            keyset_pagination: self.keyset_pagination.unwrap_or(true),
+            max_concurrency,
+            request_timeout_secs,
+            refresh_timeout_secs,
+            max_retries: self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            poll_interval_secs,
+            notifications,
+            webhooks,
+            email,
+            hook_timeout_secs,
+        })
+    }
+}
+
+fn check_poll_interval(key: &str, value: u64) -> Result<u64, String> {
+    if value < MIN_POLL_INTERVAL_SECS {
+        return Err(format!(
+            "{} must be at least {} seconds, got {}",
+            key, MIN_POLL_INTERVAL_SECS, value
+        ));
+    }
+    Ok(value)
+}
+
+// Validate the connection settings of one instance; `prefix` is the key path
+// of the table they come from ("" for the top level)
+fn parse_instance(
+    prefix: &str,
+    name: &str,
+    provider: Provider,
+    base_url: Option<String>,
+    api_token: Option<(String, TokenSource)>,
+    ca_cert: Option<PathBuf>,
+    accept_invalid_certs: Option<bool>,
+) -> Result<InstanceConfig, String> {
+    let base_url = base_url
+        .or_else(|| provider.default_base_url().map(str::to_string))
+        .ok_or_else(|| format!("{}base_url is required for {} instances", prefix, provider))?;
+    if reqwest::Url::parse(&base_url).is_err() {
+        return Err(format!(
+            "{}base_url is not a valid URL: '{}'",
+            prefix, base_url
+        ));
+    }
+    if let Some(path) = &ca_cert {
+        if !path.is_file() {
+            return Err(format!(
+                "{}ca_cert: file not found: {}",
+                prefix,
+                path.display()
+            ));
+        }
+    }
+    let (api_token, token_source) = api_token.unwrap_or_default();
+    Ok(InstanceConfig {
+        provider,
+        token_source,
+        ca_cert,
+        accept_invalid_certs: accept_invalid_certs.unwrap_or(false),
+        ..InstanceConfig::new(name, &base_url, &api_token)
+    })
+}
+
+// Validate a webhook; `key` names it in errors
+pub fn parse_webhook(key: &str, webhook: WebhookFile) -> Result<WebhookConfig, String> {
+    let url = webhook.url.trim().trim_end_matches('/').to_string();
+    let Ok(parsed) = reqwest::Url::parse(&url) else {
+        return Err(format!("{}.url is not a valid URL", key));
+    };
+    let room_id = webhook.room_id.filter(|r| !r.trim().is_empty());
+    let access_token = webhook.access_token.filter(|t| !t.trim().is_empty());
+    if webhook.kind == WebhookKind::Matrix {
+        if room_id.is_none() || access_token.is_none() {
+            return Err(format!(
+                "{}: matrix webhooks need room_id and access_token",
+                key
+            ));
+        }
+    } else if room_id.is_some() || access_token.is_some() {
+        return Err(format!(
+            "{}: room_id and access_token only apply to matrix webhooks",
+            key
+        ));
+    }
+    // The URL itself is secret for most services, so only its host names it
+    let name = webhook
+        .name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| format!("{} ({})", webhook.kind, parsed.host_str().unwrap_or("?")));
+    Ok(WebhookConfig {
+        name,
+        kind: webhook.kind,
+        url,
+        room_id,
+        access_token,
+    })
+}
+
+// Validate the digest settings; `key` names them in errors. The password may
+// also come from GITLAB_SMTP_PASSWORD.
+pub fn parse_email(key: &str, email: EmailFile) -> Result<EmailSettings, String> {
+    let smtp_host = email.smtp_host.trim().to_string();
+    if smtp_host.is_empty() {
+        return Err(format!("{}: smtp_host must not be empty", key));
+    }
+    let from = email
+        .from
+        .trim()
+        .parse()
+        .map_err(|e| format!("{}: invalid from address '{}': {}", key, email.from, e))?;
+    if email.to.is_empty() {
+        return Err(format!("{}: at least one recipient is needed in to", key));
+    }
+    let to = email
+        .to
+        .iter()
+        .map(|to| {
+            to.trim()
+                .parse()
+                .map_err(|e| format!("{}: invalid recipient '{}': {}", key, to, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let password = email
+        .password
+        .or_else(|| std::env::var("GITLAB_SMTP_PASSWORD").ok())
+        .filter(|p| !p.is_empty());
+    let username = email.username.filter(|u| !u.trim().is_empty());
+    if username.is_some() != password.is_some() {
+        return Err(format!(
+            "{}: username and password must be given together",
+            key
+        ));
+    }
+    let tls = email.tls.unwrap_or(SmtpTls::StartTls);
+    Ok(EmailSettings {
+        smtp_host,
+        smtp_port: email.smtp_port.unwrap_or_else(|| tls.default_port()),
+        tls,
+        username,
+        password,
+        from,
+        to,
+        schedule: email.schedule.unwrap_or_default(),
+    })
+}
+
+// Validate the `projects` list of an instance, applying `[defaults]`. Group,
+// starred and member entries become discovery rules.
+fn parse_projects(
+    prefix: &str,
+    instance: &str,
+    provider: Provider,
+    entries: Vec<ProjectFile>,
+    defaults: &ProjectDefaultsFile,
+    default_poll_interval: u64,
+) -> Result<(Vec<ProjectConfig>, Vec<DiscoveryRule>), String> {
+    let mut paths = HashSet::new();
+    let mut projects = Vec::new();
+    let mut discovery = Vec::new();
+    for (index, project) in entries.into_iter().enumerate() {
+        let key = format!("{}projects[{}]", prefix, index);
+        let path = project.path.trim().trim_matches('/').to_string();
+        if path.is_empty() {
+            return Err(format!("{}.path must not be empty", key));
+        }
+        if !paths.insert(path.clone()) {
+            return Err(format!(
+                "{}.path: project '{}' is configured more than once",
+                key, path
+            ));
+        }
+        let pipelines = project.pipelines.or(defaults.pipelines).unwrap_or(false);
+        let branch_pipeline = project
+            .branch_pipeline
+            .or(defaults.branch_pipeline)
+            .unwrap_or(false);
+        let deployments = project
+            .deployments
+            .or(defaults.deployments)
+            .unwrap_or(false);
+        if project.provider.is_some_and(|p| p != provider) {
+            return Err(format!(
+                "{}.provider: the project is listed under a {} instance",
+                key, provider
+            ));
+        }
+        if provider != Provider::GitLab {
+            if ProjectSource::is_rule(&path) {
+                return Err(format!(
+                    "{}.path: group:, starred and member entries are only supported on GitLab",
+                    key
+                ));
+            }
+            owner_and_repo(&path).map_err(|e| format!("{}.path: {}", key, e))?;
+            // `[defaults]` may turn them on for the GitLab projects only
+            if project.pipelines == Some(true) || project.branch_pipeline == Some(true) {
+                return Err(format!("{}: pipelines are only supported on GitLab", key));
+            }
+            if project.deployments == Some(true) {
+                return Err(format!("{}: deployments are only supported on GitLab", key));
+            }
+        }
+        let poll_interval_secs = match project.poll_interval {
+            Some(value) => check_poll_interval(&format!("{}.poll_interval", key), value)?,
+            None => default_poll_interval,
+        };
+        // An empty list turns a hook from `[defaults]` off for this project
+        let on_release = project
+            .on_release
+            .or_else(|| defaults.on_release.clone())
+            .filter(|command| !command.is_empty());
+        if on_release
+            .as_ref()
+            .is_some_and(|command| command[0].trim().is_empty())
+        {
+            return Err(format!("{}.on_release: the program must not be empty", key));
+        }
+
+        let settings = ProjectConfig {
+            display_name: project.display_name.filter(|n| !n.trim().is_empty()),
+            tag_grouping: project
+                .tag_grouping
+                .or_else(|| defaults.tag_grouping.clone())
+                .unwrap_or_default(),
+            latest_by: project.latest_by.or(defaults.latest_by).unwrap_or_default(),
+            channel: project.channel.or(defaults.channel).unwrap_or_default(),
+            notify: project.notify.or(defaults.notify).unwrap_or(true),
+            desktop_notify: project
+                .desktop_notify
+                .or(defaults.desktop_notify)
+                .unwrap_or(true),
+            poll_interval_secs,
+            on_release,
+            pipelines: pipelines && provider == Provider::GitLab,
+            branch_pipeline: branch_pipeline && provider == Provider::GitLab,
+            deployments: deployments && provider == Provider::GitLab,
+            ..ProjectConfig::new(instance, &path)
+        };
+
+        if ProjectSource::is_rule(&path) {
+            let rule = DiscoveryRule::new(
+                settings,
+                project.include.unwrap_or_default(),
+                project.exclude.unwrap_or_default(),
+            )
+            .map_err(|e| format!("{}.path: {}", key, e))?;
+            discovery.push(rule);
+        } else if project.include.is_some() || project.exclude.is_some() {
+            return Err(format!(
+                "{}: include and exclude only apply to group:, starred and member entries",
+                key
+            ));
+        } else {
+            projects.push(settings);
+        }
+    }
+    Ok((projects, discovery))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::ReleaseChannel;
+
+    fn error(toml: &str) -> String {
+        let file: ConfigFile = toml::from_str(toml).expect("valid TOML");
+        file.into_config().expect_err("invalid configuration")
+    }
+
+    #[test]
+    fn valid_file_builds_the_configuration() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            api_token = "secret"
+            poll_interval = 120
+
+            [defaults]
+            channel = "stable"
+
+            [[projects]]
+            path = "grp/app"
+            display_name = "App"
+
+            [[projects]]
+            path = "group:platform"
+            include = ["platform/*"]
+            "#,
+        )
+        .unwrap();
+        let config = config.into_config().unwrap();
+        assert_eq!(config.instances.len(), 1);
+        assert_eq!(config.instances[0].api_token, "secret");
+        assert_eq!(config.projects.len(), 1);
+        assert_eq!(config.projects[0].display_name(), "App");
+        assert_eq!(config.projects[0].channel, ReleaseChannel::Stable);
+        assert_eq!(config.projects[0].poll_interval_secs, 120);
+        assert_eq!(config.discovery.len(), 1);
+    }
+
+    #[test]
+    fn global_limits_name_their_key() {
+        assert_eq!(
+            error("per_page = 0\n[[projects]]\npath = \"a/b\""),
+            "per_page must be between 1 and 100, got 0"
+        );
+        assert_eq!(
+            error("poll_interval = 5\n[[projects]]\npath = \"a/b\""),
+            "poll_interval must be at least 10 seconds, got 5"
+        );
+        assert_eq!(
+            error("[defaults]\npoll_interval = 1\n[[projects]]\npath = \"a/b\""),
+            "defaults.poll_interval must be at least 10 seconds, got 1"
+        );
+        assert_eq!(
+            error("max_concurrency = 0\n[[projects]]\npath = \"a/b\""),
+            "max_concurrency must be at least 1"
+        );
+    }
+
+    #[test]
+    fn missing_projects_leave_the_configuration_incomplete() {
+        // Valid, so the window can save it after removing the last project
+        let file: ConfigFile = toml::from_str("api_token = \"x\"").unwrap();
+        let config = file.into_config().unwrap();
+        assert_eq!(
+            config.incomplete_reason().as_deref(),
+            Some("no projects are configured")
+        );
+    }
+
+    #[test]
+    fn project_errors_point_at_the_entry() {
+        assert_eq!(
+            error(
+                r#"
+                [[projects]]
+                path = "a/b"
+
+                [[projects]]
+                path = "/a/b/"
+                "#
+            ),
+            "projects[1].path: project 'a/b' is configured more than once"
+        );
+        assert_eq!(
+            error("[[projects]]\npath = \"a/b\"\ninclude = [\"x/*\"]"),
+            "projects[0]: include and exclude only apply to group:, starred and member entries"
+        );
+        assert_eq!(
+            error("[[projects]]\npath = \"a/b\"\non_release = [\" \"]"),
+            "projects[0].on_release: the program must not be empty"
+        );
+    }
+
+    #[test]
+    fn instance_errors_include_the_instance_index() {
+        let nested = r#"
+            [[projects]]
+            path = "a/b"
+
+            [[instances]]
+            name = "internal"
+            base_url = "https://gitlab.internal"
+
+            [[instances.projects]]
+            path = "c/d"
+
+            [[instances.projects]]
+            path = "e/f"
+            poll_interval = 1
+            "#;
+        assert_eq!(
+            error(nested),
+            "instances[0].projects[1].poll_interval must be at least 10 seconds, got 1"
+        );
+        assert_eq!(
+            error("[[instances]]\nname = \"forge\"\nprovider = \"gitea\"\n[[instances.projects]]\npath = \"a/b\""),
+            "instances[0].base_url is required for gitea instances"
+        );
+        assert_eq!(
+            error("[[instances]]\nname = \"x\"\nbase_url = \"not a url\"\n[[instances.projects]]\npath = \"a/b\""),
+            "instances[0].base_url is not a valid URL: 'not a url'"
+        );
+        assert!(error(
+            "[[projects]]\npath = \"a/b\"\n[[instances]]\nname = \"default\"\n[[instances.projects]]\npath = \"c/d\""
+        )
+        .starts_with("instances[0].name: instance 'default' is configured more than once"));
+    }
+
+    #[test]
+    fn provider_restrictions_name_the_project() {
+        assert_eq!(
+            error("[[instances]]\nname = \"hub\"\nprovider = \"github\"\n[[instances.projects]]\npath = \"owner/repo\"\npipelines = true"),
+            "instances[0].projects[0]: pipelines are only supported on GitLab"
+        );
+        assert_eq!(
+            error("[[instances]]\nname = \"hub\"\nprovider = \"github\"\n[[instances.projects]]\npath = \"group:platform\""),
+            "instances[0].projects[0].path: group:, starred and member entries are only supported on GitLab"
+        );
+        assert!(
+            error("[[projects]]\npath = \"owner/repo\"\nprovider = \"gitea\"").starts_with(
+                "projects[0].provider: gitea projects must be listed under an [[instances]] entry"
+            )
+        );
+    }
+
+    #[test]
+    fn webhook_and_email_errors_name_their_table() {
+        assert_eq!(
+            error("[[projects]]\npath = \"a/b\"\n[[webhooks]]\nkind = \"matrix\"\nurl = \"https://matrix.example\""),
+            "webhooks[0]: matrix webhooks need room_id and access_token"
+        );
+        assert_eq!(
+            error("[[projects]]\npath = \"a/b\"\n[[webhooks]]\nkind = \"slack\"\nurl = \"hooks\""),
+            "webhooks[0].url is not a valid URL"
+        );
+        assert_eq!(
+            error("[[projects]]\npath = \"a/b\"\n[email]\nsmtp_host = \"smtp.example\"\nfrom = \"monitor@example.com\"\nto = []"),
+            "email: at least one recipient is needed in to"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_values_are_rejected_by_the_parser() {
+        let error =
+            toml::from_str::<ConfigFile>("[[projects]]\npath = \"a/b\"\npoll_intervall = 60")
+                .unwrap_err()
+                .to_string();
+        assert!(
+            error.contains("unknown field `poll_intervall`"),
+            "{}",
+            error
+        );
+        let error =
+            toml::from_str::<ConfigFile>("[[projects]]\npath = \"a/b\"\nchannel = \"beta\"")
+                .unwrap_err()
+                .to_string();
+        assert!(
+            error.contains("unknown release channel 'beta'"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// Version at the end of a tag, e.g. "v1.2", "backend-v1.2.3", "api_v2.0",
+// "release/1.2.3-rc.1" or "1.4.0rc1". It must start the tag or follow a
+// non-alphanumeric separator so "app2-1.0.0" parses as 1.0.0, not 2.
+static VERSION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?:^|[^0-9A-Za-z.])[vV]?(?P<major>\d+)(?:\.(?P<minor>\d+))?(?:\.(?P<patch>\d+))?(?:-(?P<pre>[0-9A-Za-z][0-9A-Za-z.-]*)|\.?(?P<loose_pre>[A-Za-z][0-9A-Za-z.-]*))?(?:\+(?P<build>[0-9A-Za-z.-]+))?$",
+    )
+    .expect("valid version regex")
+});
+
+// A version parsed out of a release tag. Missing minor/patch components are 0.
+// Equality follows the ordering, which like semver precedence ignores `build`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    // Pre-release identifiers, e.g. ["rc", "1"] for "-rc.1"
+    pub pre: Vec<String>,
+    pub build: Option<String>,
+}
+
+impl ReleaseVersion {
+    pub fn parse(tag: &str) -> Option<Self> {
+        let caps = VERSION_RE.captures(tag.trim())?;
+        let number = |name: &str| -> Option<u64> {
+            match caps.name(name) {
+                Some(m) => m.as_str().parse().ok(),
+                None => Some(0),
+            }
+        };
+        let pre = caps
+            .name("pre")
+            .or_else(|| caps.name("loose_pre"))
+            .map(|m| {
+                m.as_str()
+                    .split(['.', '-'])
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(ReleaseVersion {
+            major: number("major")?,
+            minor: number("minor")?,
+            patch: number("patch")?,
+            pre,
+            build: caps.name("build").map(|m| m.as_str().to_string()),
+        })
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+// Semver precedence for one pre-release identifier: numeric identifiers compare
+// numerically and sort before alphanumeric ones
+fn compare_identifier(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+impl Ord for ReleaseVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A release without pre-release identifiers ranks above any pre-release
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self
+                    .pre
+                    .iter()
+                    .zip(&other.pre)
+                    .map(|(a, b)| compare_identifier(a, b))
+                    .find(|ord| *ord != Ordering::Equal)
+                    .unwrap_or_else(|| self.pre.len().cmp(&other.pre.len())),
+            })
+    }
+}
+
+impl PartialEq for ReleaseVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ReleaseVersion {}
+
+impl PartialOrd for ReleaseVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ReleaseVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+// Which release of a tag family counts as the latest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatestBy {
+    // Most recently created release (default)
+    #[default]
+    Date,
+    // Highest parsed version; releases without a version rank lowest
+    Version,
+}
+
+impl FromStr for LatestBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "date" => Ok(LatestBy::Date),
+            "version" => Ok(LatestBy::Version),
+            other => Err(format!(
+                "unknown latest selection '{}' (expected date or version)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LatestBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatestBy::Date => write!(f, "date"),
+            LatestBy::Version => write!(f, "version"),
+        }
+    }
+}
+
+// Both selection enums are written to the config file as their plain names
+impl Serialize for LatestBy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LatestBy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// Which releases of a project are monitored at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReleaseChannel {
+    // Stable releases and pre-releases (default)
+    #[default]
+    All,
+    // Stable releases only
+    Stable,
+    // Pre-releases only
+    Prerelease,
+}
+
+impl ReleaseChannel {
+    pub fn admits(&self, prerelease: bool) -> bool {
+        match self {
+            ReleaseChannel::All => true,
+            ReleaseChannel::Stable => !prerelease,
+            ReleaseChannel::Prerelease => prerelease,
+        }
+    }
+}
+
+impl FromStr for ReleaseChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "all" => Ok(ReleaseChannel::All),
+            "stable" => Ok(ReleaseChannel::Stable),
+            "prerelease" => Ok(ReleaseChannel::Prerelease),
+            other => Err(format!(
+                "unknown release channel '{}' (expected all, stable or prerelease)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReleaseChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReleaseChannel::All => write!(f, "all"),
+            ReleaseChannel::Stable => write!(f, "stable"),
+            ReleaseChannel::Prerelease => write!(f, "prerelease"),
+        }
+    }
+}
+
+impl Serialize for ReleaseChannel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ReleaseChannel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(tag: &str) -> ReleaseVersion {
+        ReleaseVersion::parse(tag).unwrap_or_else(|| panic!("{} has no version", tag))
+    }
+
+    #[test]
+    fn parses_versions_out_of_tags() {
+        let v = version("v1.2");
+        assert_eq!((v.major, v.minor, v.patch), (1, 2, 0));
+        assert!(v.pre.is_empty());
+
+        let v = version("1.2.3-rc.1");
+        assert_eq!((v.major, v.minor, v.patch), (1, 2, 3));
+        assert_eq!(v.pre, ["rc", "1"]);
+
+        let v = version("1.2.3rc1");
+        assert_eq!((v.major, v.minor, v.patch), (1, 2, 3));
+        assert_eq!(v.pre, ["rc1"]);
+        assert!(v.is_prerelease());
+
+        let v = version("1.2.3+build.5");
+        assert_eq!(v.build.as_deref(), Some("build.5"));
+        assert!(!v.is_prerelease());
+        assert_eq!(v.to_string(), "1.2.3+build.5");
+
+        assert_eq!(version("backend-v2.0.1").to_string(), "2.0.1");
+        assert_eq!(version("release/1.2.3-beta-2").pre, ["beta", "2"]);
+        assert_eq!(version("app2-1.0.0").to_string(), "1.0.0");
+    }
+
+    #[test]
+    fn tags_without_a_version_do_not_parse() {
+        assert_eq!(ReleaseVersion::parse("latest"), None);
+        assert_eq!(ReleaseVersion::parse("nightly-build"), None);
+    }
+
+    #[test]
+    fn orders_by_semver_precedence() {
+        assert!(version("1.2.3") > version("1.2.3-rc.1"));
+        assert!(version("1.2.3-rc.2") > version("1.2.3-rc.1"));
+        assert!(version("1.2.3-rc.10") > version("1.2.3-rc.9"));
+        assert!(version("1.2.3-rc.1") > version("1.2.3-rc"));
+        assert!(version("1.2.3-beta") > version("1.2.3-1"));
+        assert!(version("1.10") > version("1.9.9"));
+    }
+
+    #[test]
+    fn equality_agrees_with_ordering() {
+        let a = version("1.2.3+linux");
+        let b = version("1.2.3+windows");
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(a, b);
+        assert_eq!(version("1.2.3-rc.01"), version("1.2.3-rc.1"));
+        assert_ne!(version("1.2.3"), version("1.2.3-rc.1"));
+    }
+}
@@ -0,0 +1,617 @@
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{
+    config::GitLabConfig,
+    models::{Deployment, GitLabRelease, PipelineChange, ReleaseChangeSet},
+    rate_limit::{send_with_retry, RateLimiter},
+};
+
+// Releases, pipelines and deployments listed by name in a chat message; the
+// rest are counted
+const MAX_LISTED: usize = 20;
+
+// Kept apart from the GitLab limiter so a rate-limited chat service does not
+// hold back release fetching
+static WEBHOOK_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::new);
+static WEBHOOK_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+// Payload format of a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookKind {
+    // Slack incoming webhook (Block Kit message)
+    Slack,
+    // Microsoft Teams incoming webhook or workflow (Adaptive Card)
+    Teams,
+    // Matrix room, through the client-server API
+    Matrix,
+    // The change set as plain JSON, for custom integrations
+    Json,
+}
+
+impl FromStr for WebhookKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "slack" => Ok(WebhookKind::Slack),
+            "teams" => Ok(WebhookKind::Teams),
+            "matrix" => Ok(WebhookKind::Matrix),
+            "json" => Ok(WebhookKind::Json),
+            other => Err(format!(
+                "unknown webhook kind '{}' (expected slack, teams, matrix or json)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WebhookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookKind::Slack => write!(f, "slack"),
+            WebhookKind::Teams => write!(f, "teams"),
+            WebhookKind::Matrix => write!(f, "matrix"),
+            WebhookKind::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl Serialize for WebhookKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for WebhookKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// An endpoint that is told about release changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    // Used in log messages
+    pub name: String,
+    pub kind: WebhookKind,
+    // Webhook URL; the homeserver URL for Matrix
+    pub url: String,
+    // Matrix only: room to post to and the access token of the posting user
+    pub room_id: Option<String>,
+    pub access_token: Option<String>,
+}
+
+// Announce a change set on every configured webhook. A failing webhook is
+// logged and does not keep the others from being called.
+pub async fn send_webhooks(config: &GitLabConfig, changes: &ReleaseChangeSet) {
+    for webhook in &config.webhooks {
+        if let Err(e) = send_webhook(config, webhook, changes).await {
+            eprintln!("Webhook {} failed: {}", webhook.name, e);
+        }
+    }
+}
+
+pub async fn send_webhook(
+    config: &GitLabConfig,
+    webhook: &WebhookConfig,
+    changes: &ReleaseChangeSet,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(payload) = webhook_payload(webhook.kind, changes) else {
+        return Ok(());
+    };
+
+    let request = match webhook.kind {
+        WebhookKind::Matrix => {
+            let room_id = webhook.room_id.as_deref().unwrap_or_default();
+            // The transaction id makes retries of the same message idempotent
+            let txn_id = format!(
+                "gitlab-monitor-{}-{}",
+                Utc::now().timestamp_millis(),
+                rand::thread_rng().gen::<u32>()
+            );
+            WEBHOOK_CLIENT
+                .put(format!(
+                    "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                    webhook.url,
+                    urlencoding::encode(room_id),
+                    txn_id
+                ))
+                .bearer_auth(webhook.access_token.as_deref().unwrap_or_default())
+        }
+        _ => WEBHOOK_CLIENT.post(&webhook.url),
+    };
+    let request = request
+        .json(&payload)
+        .timeout(Duration::from_secs(config.request_timeout_secs));
+
+    let response = send_with_retry(request, &WEBHOOK_LIMITER, config.max_retries)
+        .await
+        .map_err(without_url)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{} answered {}", webhook.kind, status).into());
+    }
+    println!("Webhook {} notified", webhook.name);
+    Ok(())
+}
+
+// Body to send for a change set, or None when the webhook has nothing to say.
+// Chat webhooks only announce new releases, finished pipelines and deployed
+// releases; JSON webhooks get every change.
+pub fn webhook_payload(kind: WebhookKind, changes: &ReleaseChangeSet) -> Option<Value> {
+    if kind == WebhookKind::Json {
+        if changes.is_empty() {
+            return None;
+        }
+        return Some(json!({
+            "event": "release_changes",
+            "sent_at": Utc::now(),
+            "added": changes.added,
+            "updated": changes.updated,
+            "removed": changes.removed,
+            "pipelines": changes.pipelines,
+            "deployed": changes.deployed,
+        }));
+    }
+
+    let items = chat_items(changes);
+    if items.is_empty() {
+        return None;
+    }
+    let title = chat_title(changes);
+    let listed = &items[..items.len().min(MAX_LISTED)];
+    let more = (items.len() > MAX_LISTED).then(|| format!("and {} more", items.len() - MAX_LISTED));
+
+    Some(match kind {
+        WebhookKind::Slack => {
+            let mut blocks = vec![json!({
+                "type": "header",
+                "text": { "type": "plain_text", "text": title },
+            })];
+            blocks.extend(listed.iter().map(|item| {
+                json!({
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!(
+                            "*<{}|{}>*\n{}",
+                            item.url,
+                            slack_escape(&item.heading),
+                            slack_escape(&item.text)
+                        ),
+                    },
+                })
+            }));
+            if let Some(more) = &more {
+                blocks.push(json!({
+                    "type": "context",
+                    "elements": [{ "type": "plain_text", "text": more }],
+                }));
+            }
+            json!({ "text": plain_summary(&title, listed, &more), "blocks": blocks })
+        }
+        WebhookKind::Teams => {
+            let mut body = vec![json!({
+                "type": "TextBlock",
+                "text": title,
+                "weight": "Bolder",
+                "size": "Medium",
+            })];
+            body.extend(listed.iter().map(|item| {
+                json!({
+                    "type": "TextBlock",
+                    "text": format!("[{}]({}): {}", item.heading, item.url, item.text),
+                    "wrap": true,
+                })
+            }));
+            if let Some(more) = &more {
+                body.push(json!({ "type": "TextBlock", "text": more, "isSubtle": true }));
+            }
+            json!({
+                "type": "message",
+                "attachments": [{
+                    "contentType": "application/vnd.microsoft.card.adaptive",
+                    "content": {
+                        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                        "type": "AdaptiveCard",
+                        "version": "1.4",
+                        "body": body,
+                    },
+                }],
+            })
+        }
+        WebhookKind::Matrix => {
+            let items: Vec<String> = listed
+                .iter()
+                .map(|item| {
+                    format!(
+                        "<li><a href=\"{}\">{}</a>: {}</li>",
+                        html_escape(&item.url),
+                        html_escape(&item.heading),
+                        html_escape(&item.text)
+                    )
+                })
+                .collect();
+            let mut html = format!("<strong>{}</strong><ul>{}</ul>", title, items.join(""));
+            if let Some(more) = &more {
+                html.push_str(&format!("<p>{}</p>", more));
+            }
+            json!({
+                // Notices do not trigger bots
+                "msgtype": "m.notice",
+                "body": plain_summary(&title, listed, &more),
+                "format": "org.matrix.custom.html",
+                "formatted_body": html,
+            })
+        }
+        WebhookKind::Json => unreachable!(),
+    })
+}
+
+// A set of changes to check webhooks with
+pub fn sample_changes() -> ReleaseChangeSet {
+    ReleaseChangeSet {
+        added: vec![GitLabRelease {
+            instance: "default".to_string(),
+            project_name: "gitlab-monitor".to_string(),
+            project_path: "example/gitlab-monitor".to_string(),
+            tag_name: "v1.0.0".to_string(),
+            name: "Test notification".to_string(),
+            description: "Sent from GitLab Releases Monitor to test the webhook".to_string(),
+            created_at: Utc::now(),
+            released_at: Some(Utc::now()),
+            web_url: "https://gitlab.com".to_string(),
+            tag_family: String::new(),
+            version: None,
+            upcoming_release: false,
+            prerelease: false,
+            pipeline: None,
+            environments: Vec::new(),
+        }],
+        ..ReleaseChangeSet::default()
+    }
+}
+
+// Webhook URLs carry their secret, so they are kept out of error messages
+fn without_url(
+    e: Box<dyn std::error::Error + Send + Sync>,
+) -> Box<dyn std::error::Error + Send + Sync> {
+    match e.downcast::<reqwest::Error>() {
+        Ok(e) => Box::new(e.without_url()),
+        Err(e) => e,
+    }
+}
+
+fn release_title(release: &GitLabRelease) -> String {
+    if release.name.is_empty() {
+        release.tag_name.clone()
+    } else {
+        release.name.clone()
+    }
+}
+
+// One entry of a chat message: a linked heading followed by a short text
+struct ChatItem {
+    url: String,
+    heading: String,
+    text: String,
+}
+
+// New releases, then finished pipelines, then deployments
+fn chat_items(changes: &ReleaseChangeSet) -> Vec<ChatItem> {
+    let releases = changes.added.iter().map(|release| ChatItem {
+        url: release.web_url.clone(),
+        heading: format!("{} {}", release.project_name, release.tag_name),
+        text: release_title(release),
+    });
+    let pipelines = changes.pipelines.iter().map(|change| ChatItem {
+        url: change.pipeline.web_url.clone(),
+        heading: format!("{} {}", change.project_name, change.pipeline.ref_name),
+        text: pipeline_summary(change),
+    });
+    let deployed = changes.deployed.iter().map(|deployment| ChatItem {
+        url: deployment
+            .environment_url
+            .clone()
+            .or_else(|| deployment.release_url.clone())
+            .unwrap_or_default(),
+        heading: format!("{} {}", deployment.project_name, deployment.ref_name),
+        text: deployment_summary(deployment),
+    });
+    releases.chain(pipelines).chain(deployed).collect()
+}
+
+fn chat_title(changes: &ReleaseChangeSet) -> String {
+    match (
+        changes.added.len(),
+        changes.pipelines.len(),
+        changes.deployed.len(),
+    ) {
+        (1, 0, 0) => "New release".to_string(),
+        (added, 0, 0) => format!("{} new releases", added),
+        (0, 1, 0) => "Pipeline finished".to_string(),
+        (0, pipelines, 0) => format!("{} pipelines finished", pipelines),
+        (0, 0, 1) => "Release deployed".to_string(),
+        (0, 0, deployed) => format!("{} releases deployed", deployed),
+        (added, pipelines, deployed) => {
+            let mut parts = Vec::new();
+            if added > 0 {
+                parts.push(format!("{} new release(s)", added));
+            }
+            if pipelines > 0 {
+                parts.push(format!("{} pipeline(s) finished", pipelines));
+            }
+            if deployed > 0 {
+                parts.push(format!("{} deployment(s)", deployed));
+            }
+            parts.join(", ")
+        }
+    }
+}
+
+// E.g. "Pipeline failed (was running)"
+pub fn pipeline_summary(change: &PipelineChange) -> String {
+    format!(
+        "Pipeline {} (was {})",
+        change.pipeline.status, change.previous_status
+    )
+}
+
+// E.g. "Release 1.2 deployed to production"
+pub fn deployment_summary(deployment: &Deployment) -> String {
+    match &deployment.release_name {
+        Some(name) => format!("Release {} deployed to {}", name, deployment.environment),
+        None => format!("Deployed to {}", deployment.environment),
+    }
+}
+
+// Fallback text for clients that do not render the rich message
+fn plain_summary(title: &str, items: &[ChatItem], more: &Option<String>) -> String {
+    let mut lines = vec![format!("{}:", title)];
+    lines.extend(
+        items
+            .iter()
+            .map(|item| format!("{}: {} {}", item.heading, item.text, item.url)),
+    );
+    lines.extend(more.clone());
+    lines.join("\n")
+}
+
+// Slack treats these three characters as markup
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn html_escape(text: &str) -> String {
+    slack_escape(text).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{HttpStub, StubRequest, StubResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn webhook(kind: WebhookKind, url: &str) -> WebhookConfig {
+        WebhookConfig {
+            name: "test".to_string(),
+            kind,
+            url: url.to_string(),
+            room_id: None,
+            access_token: None,
+        }
+    }
+
+    fn changes(names: &[&str]) -> ReleaseChangeSet {
+        let template = sample_changes().added.remove(0);
+        ReleaseChangeSet {
+            added: names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| GitLabRelease {
+                    tag_name: format!("v1.{}.0", i),
+                    name: name.to_string(),
+                    ..template.clone()
+                })
+                .collect(),
+            ..ReleaseChangeSet::default()
+        }
+    }
+
+    // Send `changes` to a stub answering 200 and return the request it got
+    async fn delivered(kind: WebhookKind, changes: &ReleaseChangeSet) -> StubRequest {
+        let stub = HttpStub::start(|_| (200, "{}".to_string())).await;
+        let mut webhook = webhook(kind, &stub.url);
+        webhook.room_id = Some("!room:example.org".to_string());
+        webhook.access_token = Some("secret".to_string());
+        send_webhook(&GitLabConfig::empty(), &webhook, changes)
+            .await
+            .unwrap();
+        let mut requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        requests.remove(0)
+    }
+
+    #[tokio::test]
+    async fn posts_slack_blocks() {
+        let request = delivered(WebhookKind::Slack, &changes(&["Fixes <b> & more"])).await;
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+
+        let payload = request.json();
+        assert!(payload["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("New release:"));
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[0]["text"]["text"], "New release");
+        assert_eq!(blocks[1]["type"], "section");
+        assert_eq!(blocks[1]["text"]["type"], "mrkdwn");
+        assert_eq!(
+            blocks[1]["text"]["text"],
+            "*<https://gitlab.com|gitlab-monitor v1.0.0>*\nFixes &lt;b&gt; &amp; more"
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_teams_adaptive_cards() {
+        let request = delivered(WebhookKind::Teams, &changes(&["First", "Second"])).await;
+        assert_eq!(request.method, "POST");
+
+        let payload = request.json();
+        assert_eq!(payload["type"], "message");
+        let attachment = &payload["attachments"][0];
+        assert_eq!(
+            attachment["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        assert_eq!(attachment["content"]["type"], "AdaptiveCard");
+        let body = attachment["content"]["body"].as_array().unwrap();
+        assert_eq!(body.len(), 3);
+        assert_eq!(body[0]["text"], "2 new releases");
+        assert_eq!(
+            body[1]["text"],
+            "[gitlab-monitor v1.0.0](https://gitlab.com): First"
+        );
+        assert_eq!(
+            body[2]["text"],
+            "[gitlab-monitor v1.1.0](https://gitlab.com): Second"
+        );
+    }
+
+    #[tokio::test]
+    async fn puts_matrix_notices_into_the_room() {
+        let request = delivered(WebhookKind::Matrix, &changes(&["<script>"])).await;
+        assert_eq!(request.method, "PUT");
+        assert!(request.path.starts_with(
+            "/_matrix/client/v3/rooms/%21room%3Aexample.org/send/m.room.message/gitlab-monitor-"
+        ));
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+
+        let payload = request.json();
+        assert_eq!(payload["msgtype"], "m.notice");
+        assert_eq!(payload["format"], "org.matrix.custom.html");
+        assert_eq!(
+            payload["formatted_body"],
+            "<strong>New release</strong><ul><li><a href=\"https://gitlab.com\">gitlab-monitor v1.0.0</a>: &lt;script&gt;</li></ul>"
+        );
+        assert!(payload["body"].as_str().unwrap().contains("<script>"));
+    }
+
+    #[tokio::test]
+    async fn posts_the_change_set_as_json() {
+        let request = delivered(WebhookKind::Json, &changes(&["First"])).await;
+        assert_eq!(request.method, "POST");
+
+        let payload = request.json();
+        assert_eq!(payload["event"], "release_changes");
+        assert!(payload["sent_at"].is_string());
+        assert_eq!(payload["added"][0]["tag_name"], "v1.0.0");
+        assert_eq!(payload["added"][0]["name"], "First");
+        for key in ["updated", "removed", "pipelines", "deployed"] {
+            assert_eq!(payload[key], json!([]), "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_nothing_without_changes() {
+        let stub = HttpStub::start(|_| (200, "{}".to_string())).await;
+        for kind in [WebhookKind::Slack, WebhookKind::Json] {
+            send_webhook(
+                &GitLabConfig::empty(),
+                &webhook(kind, &stub.url),
+                &ReleaseChangeSet::default(),
+            )
+            .await
+            .unwrap();
+        }
+        assert!(stub.requests().is_empty());
+    }
+
+    #[test]
+    fn counts_releases_beyond_the_listed_ones() {
+        let names: Vec<String> = (0..MAX_LISTED + 5).map(|i| format!("r{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let payload = webhook_payload(WebhookKind::Slack, &changes(&names)).unwrap();
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), MAX_LISTED + 2);
+        assert_eq!(blocks[0]["text"]["text"], "25 new releases");
+        assert_eq!(blocks[MAX_LISTED + 1]["elements"][0]["text"], "and 5 more");
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let stub = HttpStub::start(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                StubResponse::new(503, "{}").header("Retry-After", "0")
+            } else {
+                StubResponse::new(200, "{}")
+            }
+        })
+        .await;
+
+        send_webhook(
+            &GitLabConfig::empty(),
+            &webhook(WebhookKind::Slack, &stub.url),
+            &changes(&["First"]),
+        )
+        .await
+        .unwrap();
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 3);
+        // Every attempt carries the same message
+        assert!(requests.iter().all(|r| r.body == requests[0].body));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let stub =
+            HttpStub::start(|_| StubResponse::new(500, "{}").header("Retry-After", "0")).await;
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 1;
+
+        let error = send_webhook(
+            &config,
+            &webhook(WebhookKind::Teams, &stub.url),
+            &changes(&["First"]),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "teams answered 500 Internal Server Error"
+        );
+        assert_eq!(stub.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let stub = HttpStub::start(|_| (404, "{}".to_string())).await;
+        let error = send_webhook(
+            &GitLabConfig::empty(),
+            &webhook(WebhookKind::Json, &stub.url),
+            &changes(&["First"]),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.to_string(), "json answered 404 Not Found");
+        assert_eq!(stub.requests().len(), 1);
+    }
+}
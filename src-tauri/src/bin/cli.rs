@@ -0,0 +1,532 @@
+// Headless command line client: the tray app's release fetching, for scripts,
+// cron jobs and CI. Logs go to stderr, so stdout only carries the output.
+//
+// Exit codes: 0 on success (`check`: no new releases), 1 when `check` found new
+// releases, 2 on errors, including projects that could not be fetched (the
+// output then lacks their releases).
+
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use gitlab_monitor_core::{
+    config::{load_config, load_toml_config, GitLabConfig, MIN_POLL_INTERVAL_SECS},
+    discovery::update_discovered_projects,
+    gitlab::{detect_new_releases, fetch_releases, filter_latest_releases, latest_releases},
+    models::{Deployment, FetchFailure, GitLabRelease},
+    poller::{Poller, PollerEvent},
+    secrets::{apply_stored_tokens, secret_store},
+    seen::SeenReleases,
+    state::{AppState, StateFiles},
+    storage,
+    webhooks::{deployment_summary, pipeline_summary},
+};
+
+#[derive(Parser)]
+#[command(
+    name = "gitlab-monitor-cli",
+    version,
+    about = "Check GitLab projects for new releases"
+)]
+struct Cli {
+    /// TOML configuration file to use instead of the app's config.toml or .env
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Print releases as JSON
+    #[arg(long, global = true, conflicts_with = "table")]
+    json: bool,
+    /// Print releases as a table (the default)
+    #[arg(long, global = true)]
+    table: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every release of the configured projects, newest first
+    List,
+    /// Show the latest release of each project (per tag family)
+    Latest,
+    /// Show releases published since the last check; exits with 1 if there are any
+    Check {
+        /// File remembering the releases seen so far
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+    },
+    /// Show which ref is deployed to each environment (projects with `deployments = true`)
+    Deployments,
+    /// Keep polling and print new releases as they are published
+    Watch {
+        /// Seconds between polls, for every project (default: the configured poll intervals)
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(MIN_POLL_INTERVAL_SECS..))]
+        interval: Option<u64>,
+        /// File remembering the releases seen so far
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Table,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<ExitCode, Box<dyn std::error::Error + Send + Sync>> {
+    let config = match &cli.config {
+        Some(path) => {
+            let mut config = load_toml_config(path).map_err(|e| e.to_string())?;
+            apply_stored_tokens(&mut config, secret_store());
+            config
+        }
+        None => load_config().map_err(|e| e.to_string())?,
+    };
+    if let Some(reason) = config.incomplete_reason() {
+        return Err(format!("Configuration incomplete: {}", reason).into());
+    }
+    let format = if cli.json {
+        Format::Json
+    } else {
+        Format::Table
+    };
+
+    // Whatever was fetched is printed before failures are reported
+    let (failures, new_releases) = match cli.command {
+        Command::List => {
+            let (state, mut failures) = one_shot_state(config).await;
+            let fetched = fetch_releases(&state, true).await;
+            failures.extend(fetched.failures);
+            let mut releases = fetched.releases;
+            releases.sort_by_key(|r| std::cmp::Reverse(released_at(r)));
+            print_releases(&releases, format)?;
+            (failures, false)
+        }
+        Command::Latest => {
+            let (state, mut failures) = one_shot_state(config).await;
+            let fetched = fetch_releases(&state, true).await;
+            failures.extend(fetched.failures);
+            let releases = latest_releases(&fetched.releases, &state.current_config().await);
+            print_releases(&releases, format)?;
+            (failures, false)
+        }
+        Command::Deployments => {
+            let (state, mut failures) = one_shot_state(config).await;
+            let fetched = fetch_releases(&state, true).await;
+            failures.extend(fetched.failures);
+            print_deployments(&fetched.deployments, format)?;
+            (failures, false)
+        }
+        Command::Check { state: state_file } => {
+            let (state, mut failures) = one_shot_state(config).await;
+            let state_file = state_file.unwrap_or_else(default_state_file);
+            let mut seen = SeenReleases::load_from(&state_file);
+            let (new, fetch_failures) = check_new_releases(&state, &mut seen).await;
+            failures.extend(fetch_failures);
+            seen.save_to(&state_file)?;
+            print_releases(&new, format)?;
+            (failures, !new.is_empty())
+        }
+        Command::Watch {
+            interval,
+            state: state_file,
+        } => {
+            let state_file = state_file.unwrap_or_else(default_state_file);
+            watch(config, state_file, interval, format).await;
+            (Vec::new(), false)
+        }
+    };
+
+    exit_code(&failures, new_releases)
+}
+
+// 1 when `check` found new releases, an error (exit code 2) when projects
+// could not be fetched
+fn exit_code(
+    failures: &[FetchFailure],
+    new_releases: bool,
+) -> Result<ExitCode, Box<dyn std::error::Error + Send + Sync>> {
+    if !failures.is_empty() {
+        let failed: Vec<String> = failures
+            .iter()
+            .map(|f| format!("{} on {}", f.project_path, f.instance))
+            .collect();
+        return Err(format!(
+            "{} project(s) could not be fetched: {}",
+            failures.len(),
+            failed.join(", ")
+        )
+        .into());
+    }
+    if new_releases {
+        return Ok(ExitCode::from(1));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// State for a single fetch, with group, starred and member entries expanded,
+// and the entries that could not be expanded
+async fn one_shot_state(config: GitLabConfig) -> (AppState, Vec<FetchFailure>) {
+    let state = AppState::with_files(config, cli_files(None));
+    let failures = update_discovered_projects(&state).await;
+    (state, failures)
+}
+
+// Kept apart from the tray app's seen state, so the app and the CLI do not
+// take new releases away from each other
+fn default_state_file() -> PathBuf {
+    storage::data_dir().join("cli-seen-releases.json")
+}
+
+// The CLI has its own release cache too: a run with another --config would
+// otherwise prune the app's cached pages
+fn cli_files(seen: Option<PathBuf>) -> StateFiles {
+    StateFiles {
+        seen: seen.unwrap_or_else(default_state_file),
+        release_cache: storage::cache_dir().join("cli-releases-cache.json"),
+        ..StateFiles::app()
+    }
+}
+
+// Fetch the projects and return the releases not in `seen`, recording them,
+// and the projects that could not be fetched. Projects fetched for the first
+// time only seed `seen`, so the first check does not report every release ever
+// published.
+async fn check_new_releases(
+    state: &AppState,
+    seen: &mut SeenReleases,
+) -> (Vec<GitLabRelease>, Vec<FetchFailure>) {
+    let fetched = fetch_releases(state, true).await;
+    let config = state.current_config().await;
+
+    let new: Vec<GitLabRelease> = detect_new_releases(&fetched.releases, seen)
+        .into_iter()
+        .filter(|r| seen.has_project(&r.instance, &r.project_path))
+        .collect();
+
+    seen.record(&fetched.releases);
+    for release in &fetched.releases {
+        seen.mark_project(&release.instance, &release.project_path);
+    }
+    for (instance, project_path) in &fetched.complete_projects {
+        seen.mark_project(instance, project_path);
+    }
+
+    // Several new releases of one tag family are reported as the latest only
+    let mut new = filter_latest_releases(&new, &config);
+    new.sort_by_key(|r| std::cmp::Reverse(released_at(r)));
+    (new, fetched.failures)
+}
+
+// Print new releases, finished pipelines and deployed releases as the poller
+// finds them, until interrupted
+async fn watch(
+    mut config: GitLabConfig,
+    state_file: PathBuf,
+    interval: Option<u64>,
+    format: Format,
+) {
+    if let Some(secs) = interval {
+        config.poll_interval_secs = secs;
+        for project in config
+            .projects
+            .iter_mut()
+            .chain(config.discovery.iter_mut().map(|rule| &mut rule.settings))
+        {
+            project.poll_interval_secs = secs;
+        }
+    }
+    eprintln!(
+        "Watching {} project(s), polling every {}s",
+        config.projects.len() + config.discovery.len(),
+        config.tick_interval().as_secs()
+    );
+
+    let state = AppState::with_files(config, cli_files(Some(state_file)));
+    let (_task, mut events) = Poller::new(state).spawn();
+    while let Some(event) = events.recv().await {
+        let PollerEvent::Refreshed { changes, .. } = event else {
+            continue;
+        };
+        match format {
+            // One object per line, so the output can be consumed as a stream
+            Format::Json => {
+                let lines = changes
+                    .added
+                    .iter()
+                    .map(serde_json::to_string)
+                    .chain(changes.pipelines.iter().map(serde_json::to_string))
+                    .chain(changes.deployed.iter().map(serde_json::to_string));
+                for line in lines {
+                    match line {
+                        Ok(line) => println!("{}", line),
+                        Err(e) => eprintln!("Failed to serialize change: {}", e),
+                    }
+                }
+            }
+            Format::Table => {
+                if !changes.added.is_empty() {
+                    print_table(&changes.added);
+                }
+                for change in &changes.pipelines {
+                    println!(
+                        "{} {}: {}  {}",
+                        change.project_name,
+                        change.pipeline.ref_name,
+                        pipeline_summary(change),
+                        change.pipeline.web_url
+                    );
+                }
+                for deployment in &changes.deployed {
+                    println!(
+                        "{} {}: {}  {}",
+                        deployment.project_name,
+                        deployment.ref_name,
+                        deployment_summary(deployment),
+                        deployment
+                            .environment_url
+                            .as_deref()
+                            .or(deployment.release_url.as_deref())
+                            .unwrap_or("")
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn released_at(release: &GitLabRelease) -> chrono::DateTime<chrono::Utc> {
+    release.released_at.unwrap_or(release.created_at)
+}
+
+fn print_releases(
+    releases: &[GitLabRelease],
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(releases)?),
+        Format::Table if releases.is_empty() => eprintln!("No releases"),
+        Format::Table => print_table(releases),
+    }
+    Ok(())
+}
+
+fn print_table(releases: &[GitLabRelease]) {
+    let mut rows = vec![[
+        "PROJECT".to_string(),
+        "TAG".to_string(),
+        "RELEASED".to_string(),
+        "NAME".to_string(),
+        "URL".to_string(),
+    ]];
+    rows.extend(releases.iter().map(|release| {
+        [
+            release.project_name.clone(),
+            release.tag_name.clone(),
+            released_at(release)
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            release.name.clone(),
+            release.web_url.clone(),
+        ]
+    }));
+    print_columns(&rows);
+}
+
+fn print_deployments(
+    deployments: &[Deployment],
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(deployments)?),
+        Format::Table if deployments.is_empty() => eprintln!("No deployments"),
+        Format::Table => {
+            let mut rows = vec![[
+                "PROJECT".to_string(),
+                "ENVIRONMENT".to_string(),
+                "REF".to_string(),
+                "DEPLOYED".to_string(),
+                "RELEASE".to_string(),
+            ]];
+            rows.extend(deployments.iter().map(|deployment| {
+                [
+                    deployment.project_name.clone(),
+                    deployment.environment.clone(),
+                    deployment.ref_name.clone(),
+                    deployment
+                        .deployed_at
+                        .map(|at| {
+                            at.with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or_default(),
+                    deployment.release_name.clone().unwrap_or_default(),
+                ]
+            }));
+            print_columns(&rows);
+        }
+    }
+    Ok(())
+}
+
+// Print rows as left-aligned columns, the first row being the header
+fn print_columns<const N: usize>(rows: &[[String; N]]) {
+    for line in format_columns(rows) {
+        println!("{}", line);
+    }
+}
+
+fn format_columns<const N: usize>(rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gitlab_monitor_core::config::{InstanceConfig, ProjectConfig};
+    use gitlab_monitor_core::test_support::HttpStub;
+    use std::sync::{Arc, Mutex};
+
+    fn release_json(tag_name: &str, day: u32) -> serde_json::Value {
+        serde_json::json!({
+            "tag_name": tag_name,
+            "name": format!("Release {}", tag_name),
+            "description": "",
+            "created_at": format!("2026-10-{:02}T10:00:00Z", day),
+            "released_at": format!("2026-10-{:02}T10:00:00Z", day),
+            "_links": {"self": format!("https://gitlab.example.com/grp/app/-/releases/{}", tag_name)},
+        })
+    }
+
+    // A GitLab instance serving `releases` for every project, and a state
+    // keeping its files in `dir`
+    async fn setup(
+        dir: &std::path::Path,
+        releases: &Arc<Mutex<Vec<serde_json::Value>>>,
+        projects: &[&str],
+    ) -> (HttpStub, AppState) {
+        let served = releases.clone();
+        let stub = HttpStub::start(move |request| {
+            if request.path.contains("grp%2Fbroken") {
+                return (404, "{}".to_string());
+            }
+            let releases = served.lock().unwrap().clone();
+            (200, serde_json::Value::Array(releases).to_string())
+        })
+        .await;
+
+        let mut config = GitLabConfig::empty();
+        config.max_retries = 0;
+        config
+            .instances
+            .push(InstanceConfig::new("default", &stub.url, "token"));
+        for path in projects {
+            config.projects.push(ProjectConfig::new("default", path));
+        }
+        (stub, AppState::with_files(config, StateFiles::in_dir(dir)))
+    }
+
+    #[tokio::test]
+    async fn check_reports_only_releases_published_since_the_last_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("cli-seen.json");
+        let releases = Arc::new(Mutex::new(vec![release_json("v1.0.0", 1)]));
+        let (_stub, state) = setup(dir.path(), &releases, &["grp/app"]).await;
+
+        // The first check only seeds the state file
+        let mut seen = SeenReleases::load_from(&state_file);
+        let (new, failures) = check_new_releases(&state, &mut seen).await;
+        assert!(new.is_empty());
+        assert!(failures.is_empty());
+        seen.save_to(&state_file).unwrap();
+        assert_eq!(
+            exit_code(&failures, !new.is_empty()).unwrap(),
+            ExitCode::SUCCESS
+        );
+
+        releases
+            .lock()
+            .unwrap()
+            .insert(0, release_json("v1.1.0", 2));
+        let mut seen = SeenReleases::load_from(&state_file);
+        let (new, failures) = check_new_releases(&state, &mut seen).await;
+        let tags: Vec<&str> = new.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(tags, ["v1.1.0"]);
+        assert_eq!(
+            exit_code(&failures, !new.is_empty()).unwrap(),
+            ExitCode::from(1)
+        );
+        seen.save_to(&state_file).unwrap();
+
+        // Nothing new the next time
+        let mut seen = SeenReleases::load_from(&state_file);
+        let (new, _) = check_new_releases(&state, &mut seen).await;
+        assert!(new.is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_reports_projects_that_could_not_be_fetched() {
+        let dir = tempfile::tempdir().unwrap();
+        let releases = Arc::new(Mutex::new(vec![release_json("v1.0.0", 1)]));
+        let (_stub, state) = setup(dir.path(), &releases, &["grp/app", "grp/broken"]).await;
+
+        let mut seen = SeenReleases::default();
+        let (_, failures) = check_new_releases(&state, &mut seen).await;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].project_path, "grp/broken");
+        assert!(seen.has_project("default", "grp/app"));
+
+        let error = exit_code(&failures, true).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1 project(s) could not be fetched: grp/broken on default"
+        );
+    }
+
+    #[test]
+    fn columns_are_aligned_to_the_widest_cell() {
+        let rows = [
+            ["PROJECT", "TAG", "NAME"].map(str::to_string),
+            ["api", "v1.10.0", "Big release"].map(str::to_string),
+            ["web-frontend", "v2", ""].map(str::to_string),
+        ];
+        assert_eq!(
+            format_columns(&rows),
+            [
+                "PROJECT       TAG      NAME",
+                "api           v1.10.0  Big release",
+                "web-frontend  v2",
+            ]
+        );
+    }
+}
@@ -1,10 +1,28 @@
 use crate::{
-    gitlab::{detect_new_releases, fetch_all_releases},
+    config::{move_config_tokens, ConfigFile, TokenSource},
+    diagnose::diagnose,
+    digest::send_digest,
+    discovery::ProjectSource,
+    gitlab::{fetch_releases, lookup_project},
+    hooks::HookRun,
+    models::{DiagnosticReport, ProjectInfo},
+    poller::{announce_changes, reload_config},
+    secrets::secret_store,
     state::AppState,
-    tray::update_tray_icon,
+    webhooks::{sample_changes, send_webhook},
 };
+use serde::Serialize;
 use tauri::{Manager, Runtime};
 
+// Token state of one configured instance, for the token dialog
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStatus {
+    pub instance: String,
+    pub source: TokenSource,
+    // Backend tokens set from the app are stored in
+    pub store: String,
+}
+
 #[tauri::command]
 pub async fn get_releases(
     state: tauri::State<'_, AppState>,
@@ -18,30 +36,10 @@ pub async fn refresh_releases(
     state: tauri::State<'_, AppState>,
     app: tauri::AppHandle,
 ) -> Result<Vec<crate::models::GitLabRelease>, String> {
-    match fetch_all_releases(&state).await {
-        Ok(new_releases) => {
-            let previous_releases = state.previous_releases.lock().await;
-            let new_items = detect_new_releases(&new_releases, &previous_releases);
-            drop(previous_releases);
-
-            let mut prev = state.previous_releases.lock().await;
-            *prev = new_releases.clone();
-            drop(prev);
-
-            let mut releases = state.releases.lock().await;
-            *releases = new_releases.clone();
-            drop(releases);
-
-            if !new_items.is_empty() {
-                let mut has_new = state.has_new_releases.lock().await;
-                *has_new = true;
-                drop(has_new);
-                let _ = update_tray_icon(&app, true);
-            }
-            Ok(new_releases)
-        }
-        Err(e) => Err(format!("Failed to refresh releases: {}", e)),
-    }
+    let fetched = fetch_releases(&state, true).await;
+    let (new_releases, changes) = state.apply_releases(&fetched).await;
+    announce_changes(&app, &changes).await;
+    Ok(new_releases)
 }
 
 #[tauri::command]
@@ -54,8 +52,7 @@ pub async fn open_release_url(url: String) -> Result<(), String> {
 
 #[tauri::command]
 pub async fn mark_releases_as_seen(state: tauri::State<'_, AppState>) -> Result<(), String> {
-    let mut has_new = state.has_new_releases.lock().await;
-    *has_new = false;
+    state.clear_pending_changes().await;
     Ok(())
 }
 
@@ -86,3 +83,188 @@ pub fn hide_main_window<R: Runtime>(app: tauri::AppHandle<R>) {
         .hide()
         .expect("Can't Hide Window");
 }
+
+#[tauri::command]
+pub async fn get_token_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TokenStatus>, String> {
+    let config = state.current_config().await;
+    let store = secret_store().name().to_string();
+    Ok(config
+        .instances
+        .iter()
+        .map(|instance| TokenStatus {
+            instance: instance.name.clone(),
+            source: instance.token_source,
+            store: store.clone(),
+        })
+        .collect())
+}
+
+// Store the API token of an instance in the secret store and restart
+// monitoring with it
+#[tauri::command]
+pub async fn set_api_token(
+    app: tauri::AppHandle,
+    instance: String,
+    token: String,
+) -> Result<(), String> {
+    let token = token.trim();
+    if instance.trim().is_empty() || token.is_empty() {
+        return Err("Instance and token must not be empty".to_string());
+    }
+    secret_store()
+        .set(instance.trim(), token)
+        .map_err(|e| format!("Failed to store API token: {}", e))?;
+    reload_config(&app).await
+}
+
+#[tauri::command]
+pub async fn clear_api_token(app: tauri::AppHandle, instance: String) -> Result<(), String> {
+    secret_store()
+        .delete(instance.trim())
+        .map_err(|e| format!("Failed to remove API token: {}", e))?;
+    reload_config(&app).await
+}
+
+// Move the plaintext tokens of config.toml into the secret store and restart
+// monitoring with them. Returns the instances whose token was moved.
+#[tauri::command]
+pub async fn move_tokens_to_store(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let moved =
+        move_config_tokens().map_err(|e| format!("Failed to move the API tokens: {}", e))?;
+    // The reload also keeps the config watcher from reloading the rewritten file again
+    reload_config(&app).await?;
+    Ok(moved)
+}
+
+// Current settings in config.toml layout, without API tokens
+#[tauri::command]
+pub async fn get_settings() -> Result<ConfigFile, String> {
+    ConfigFile::load()
+        .map(ConfigFile::without_tokens)
+        .map_err(|e| format!("Failed to load settings: {}", e))
+}
+
+// Replace the settings, keeping the API tokens and `on_release` hooks already
+// in the file, and restart monitoring with them. Instances moved to another
+// server lose their token, the stored one included.
+#[tauri::command]
+pub async fn update_settings(app: tauri::AppHandle, settings: ConfigFile) -> Result<(), String> {
+    let mut moved = Vec::new();
+    save_settings(|file| {
+        let mut settings = settings;
+        moved = settings.keep_tokens_from(file);
+        settings.keep_hooks_from(file);
+        *file = settings;
+        Ok(())
+    })?;
+    for instance in moved {
+        if let Err(e) = secret_store().delete(&instance) {
+            eprintln!("Failed to remove the API token of {}: {}", instance, e);
+        }
+    }
+    reload_config(&app).await
+}
+
+#[tauri::command]
+pub async fn add_project(
+    app: tauri::AppHandle,
+    instance: String,
+    path: String,
+) -> Result<(), String> {
+    save_settings(|file| file.add_project(&instance, &path))?;
+    reload_config(&app).await
+}
+
+#[tauri::command]
+pub async fn remove_project(
+    app: tauri::AppHandle,
+    instance: String,
+    path: String,
+) -> Result<(), String> {
+    save_settings(|file| file.remove_project(&instance, &path))?;
+    reload_config(&app).await
+}
+
+// Look a project up on GitLab before adding it. Group, starred and member
+// entries are only checked for syntax; they are resolved by the poller.
+#[tauri::command]
+pub async fn validate_project(
+    state: tauri::State<'_, AppState>,
+    instance: String,
+    path: String,
+) -> Result<ProjectInfo, String> {
+    if ProjectSource::is_rule(&path) {
+        let source = path.parse::<ProjectSource>()?;
+        return Ok(ProjectInfo {
+            instance,
+            path: source.to_string(),
+            name: source.to_string(),
+            web_url: String::new(),
+        });
+    }
+    lookup_project(&state, &instance, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Check the token and every configured project against GitLab
+#[tauri::command]
+pub async fn run_diagnostics(
+    state: tauri::State<'_, AppState>,
+) -> Result<DiagnosticReport, String> {
+    Ok(diagnose(&state).await)
+}
+
+// Send a sample release to every configured webhook
+#[tauri::command]
+pub async fn test_webhooks(state: tauri::State<'_, AppState>) -> Result<usize, String> {
+    let config = state.current_config().await;
+    if config.webhooks.is_empty() {
+        return Err("No webhooks configured".to_string());
+    }
+    let changes = sample_changes();
+    let mut failures = Vec::new();
+    for webhook in &config.webhooks {
+        if let Err(e) = send_webhook(&config, webhook, &changes).await {
+            failures.push(format!("{}: {}", webhook.name, e));
+        }
+    }
+    if !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+    Ok(config.webhooks.len())
+}
+
+// Email a digest of a sample release to the digest recipients
+#[tauri::command]
+pub async fn send_test_digest(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let config = state.current_config().await;
+    let Some(email) = &config.email else {
+        return Err("No email digest configured".to_string());
+    };
+    send_digest(
+        email,
+        &sample_changes().added,
+        None,
+        config.request_timeout_secs,
+    )
+    .await
+    .map_err(|e| format!("Failed to send digest: {}", e))
+}
+
+// Latest `on_release` hook runs, newest first
+#[tauri::command]
+pub async fn get_hook_history(state: tauri::State<'_, AppState>) -> Result<Vec<HookRun>, String> {
+    let history = state.hook_history.lock().await;
+    Ok(history.runs.iter().cloned().collect())
+}
+
+// Load the settings, apply `edit` and write them to config.toml
+fn save_settings(edit: impl FnOnce(&mut ConfigFile) -> Result<(), String>) -> Result<(), String> {
+    let mut file = ConfigFile::load().map_err(|e| format!("Failed to load settings: {}", e))?;
+    edit(&mut file)?;
+    file.save()
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
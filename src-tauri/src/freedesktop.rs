@@ -0,0 +1,92 @@
+// Notifications through the freedesktop notification service (Linux), which,
+// unlike the notification plugin, reports back when a notification is clicked
+
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use zbus::{proxy, zvariant::Value, Connection};
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+// Action sent when the notification itself is clicked
+const DEFAULT_ACTION: &str = "default";
+
+// Notification servers that keep notifications around do not always report
+// them closed, so clicks are not waited for longer than this
+const CLICK_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Show a notification and run `on_click` when it is clicked. Returns once the
+// notification is shown; the click is waited for in the background.
+pub async fn show(
+    title: &str,
+    body: &str,
+    on_click: impl FnOnce() + Send + 'static,
+) -> zbus::Result<()> {
+    let connection = Connection::session().await?;
+    let proxy = NotificationsProxy::new(&connection).await?;
+    // Subscribe first so a quick click is not missed
+    let mut invoked = proxy.receive_action_invoked().await?;
+    let mut closed = proxy.receive_notification_closed().await?;
+    let id = proxy
+        .notify(
+            "GitLab Releases Monitor",
+            0,
+            "",
+            title,
+            body,
+            &[DEFAULT_ACTION, "Open"],
+            HashMap::new(),
+            -1,
+        )
+        .await?;
+
+    let wait_for_click = async move {
+        loop {
+            tokio::select! {
+                Some(signal) = invoked.next() => {
+                    if signal
+                        .args()
+                        .is_ok_and(|args| args.id == id && args.action_key == DEFAULT_ACTION)
+                    {
+                        on_click();
+                        return;
+                    }
+                }
+                Some(signal) = closed.next() => {
+                    if signal.args().is_ok_and(|args| args.id == id) {
+                        return;
+                    }
+                }
+                else => return,
+            }
+        }
+    };
+    tauri::async_runtime::spawn(async move {
+        let _ = tokio::time::timeout(CLICK_TIMEOUT, wait_for_click).await;
+    });
+    Ok(())
+}
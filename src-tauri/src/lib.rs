@@ -1,65 +1,91 @@
 // Reorganised crate root – delegates logic to sub-modules for clarity.
-
+// The monitoring itself lives in the Tauri-free `gitlab_monitor_core` crate;
+// its modules are re-exported so they can be used as `crate::config` etc.
+// Everything that needs Tauri sits behind the `desktop` feature so the command
+// line client can be built without it.
+
+pub use gitlab_monitor_core::{
+    cache, config, deployments, diagnose, digest, discovery, gitea, github, gitlab, grouping,
+    hooks, models, pipelines, rate_limit, secrets, seen, sources, state, storage, validation,
+    version, webhooks,
+};
+
+#[cfg(feature = "desktop")]
 pub mod commands;
-pub mod config;
-pub mod gitlab;
-pub mod models;
-pub mod state;
+#[cfg(all(feature = "desktop", target_os = "linux"))]
+pub mod freedesktop;
+#[cfg(feature = "desktop")]
+pub mod notifications;
+#[cfg(feature = "desktop")]
+pub mod poller;
+#[cfg(feature = "desktop")]
 pub mod tray;
 
+#[cfg(feature = "desktop")]
 use commands::*;
+#[cfg(feature = "desktop")]
 use config::load_config;
-use gitlab::fetch_all_releases;
+#[cfg(feature = "desktop")]
+use poller::{restart_poller, watch_config};
+#[cfg(feature = "desktop")]
 use state::AppState;
+#[cfg(feature = "desktop")]
 use tauri_plugin_global_shortcut::{Builder as ShortcutBuilder, ShortcutState};
-use tray::{install_tray, update_tray_icon};
+#[cfg(feature = "desktop")]
+use tray::install_tray;
 
-use std::sync::Arc;
-use tauri::{Emitter, Manager, WindowEvent};
-use tokio::sync::Mutex;
+#[cfg(feature = "desktop")]
+use tauri::{Manager, WindowEvent};
 
+#[cfg(feature = "desktop")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             get_releases,
             refresh_releases,
             open_release_url,
             mark_releases_as_seen,
             show_main_window,
-            hide_main_window
+            hide_main_window,
+            get_token_status,
+            set_api_token,
+            clear_api_token,
+            move_tokens_to_store,
+            get_settings,
+            update_settings,
+            add_project,
+            remove_project,
+            validate_project,
+            run_diagnostics,
+            test_webhooks,
+            send_test_digest,
+            get_hook_history
         ])
         .setup(|app| {
             // Load configuration
+            let mut load_error = None;
             let config = match load_config() {
                 Ok(config) => config,
                 Err(e) => {
+                    load_error = Some(format!("The configuration could not be loaded: {}", e));
                     eprintln!("Configuration Error:");
                     eprintln!("{}", e);
                     eprintln!("");
                     eprintln!("The application will start but GitLab monitoring will not work until configuration is complete.");
-                    
+
                     // Create a dummy config so the app can start
-                    config::GitLabConfig {
-                        api_token: String::new(),
-                        base_url: "https://gitlab.com".to_string(),
-                        projects: Vec::new(),
-                    }
+                    config::GitLabConfig::empty()
                 }
             };
 
-            // HTTP client
-            let client = reqwest::Client::new();
-
-            // Application state
+            // Application state; the diagnostics report a load error
             let state = AppState {
-                config,
-                releases: Arc::new(Mutex::new(Vec::new())),
-                previous_releases: Arc::new(Mutex::new(Vec::new())),
-                client,
-                has_new_releases: Arc::new(Mutex::new(false)),
+                config_error: std::sync::Arc::new(tokio::sync::Mutex::new(load_error)),
+                ..AppState::new(config)
             };
             app.manage(state);
 
@@ -104,66 +130,13 @@ pub fn run() {
                 });
             }
 
-            // Background refresh task - only start if we have valid configuration
+            // Background refresh task - only runs while the configuration is valid,
+            // and is restarted whenever the configuration file changes
             let app_handle_bg = app.handle().clone();
             tokio::spawn(async move {
                 tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-                
-                if let Some(state) = app_handle_bg.try_state::<AppState>() {
-                    // Check if we have valid configuration
-                    if state.config.api_token.is_empty() || state.config.projects.is_empty() {
-                        println!("GitLab monitoring disabled - configuration incomplete");
-                        let _ = app_handle_bg.emit("config-error", "Configuration incomplete. Please edit the .env file with your GitLab settings.");
-                        return;
-                    }
-                    
-                    println!("Starting GitLab releases background task...");
-
-                    if let Ok(initial_releases) = fetch_all_releases(&state).await {
-                        let mut prev = state.previous_releases.lock().await;
-                        *prev = initial_releases.clone();
-                        drop(prev);
-
-                        let mut releases = state.releases.lock().await;
-                        *releases = initial_releases.clone();
-                        drop(releases);
-
-                        let _ = app_handle_bg.emit("releases-loaded", &initial_releases);
-                    }
-
-                    // Auto-refresh every minute
-                    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
-                    interval.tick().await;
-
-                    loop {
-                        interval.tick().await;
-                        println!("Auto-refreshing GitLab releases...");
-
-                        if let Ok(new_releases) = fetch_all_releases(&state).await {
-                            let previous_releases = state.previous_releases.lock().await;
-                            let new_items =
-                                gitlab::detect_new_releases(&new_releases, &previous_releases);
-                            drop(previous_releases);
-
-                            let mut prev = state.previous_releases.lock().await;
-                            *prev = new_releases.clone();
-                            drop(prev);
-
-                            let mut releases = state.releases.lock().await;
-                            *releases = new_releases.clone();
-                            drop(releases);
-
-                            if !new_items.is_empty() {
-                                let mut has_new = state.has_new_releases.lock().await;
-                                *has_new = true;
-                                drop(has_new);
-                                let _ = update_tray_icon(&app_handle_bg, true);
-                            }
-
-                            let _ = app_handle_bg.emit("releases-updated", &new_releases);
-                        }
-                    }
-                }
+                restart_poller(&app_handle_bg).await;
+                watch_config(app_handle_bg);
             });
 
             Ok(())
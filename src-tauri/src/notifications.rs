@@ -0,0 +1,161 @@
+use chrono::Local;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+
+#[cfg(target_os = "linux")]
+use crate::freedesktop;
+use crate::{
+    models::{Deployment, GitLabRelease, PipelineChange},
+    state::AppState,
+    tray::show_main_window,
+    webhooks::{deployment_summary, pipeline_summary},
+};
+
+// Releases listed by name in a summary notification
+const SUMMARY_LINES: usize = 5;
+
+// Show desktop notifications for the new releases of a refresh. Releases that
+// arrive during quiet hours are held back and announced once they are over, so
+// this is also called after refreshes without new releases.
+pub async fn notify_new_releases<R: Runtime>(app: &AppHandle<R>, added: &[GitLabRelease]) {
+    let state = app.state::<AppState>();
+    let config = state.current_config().await;
+    let settings = &config.notifications;
+
+    let mut held = state.held_notifications.lock().await;
+    if !settings.enabled {
+        held.clear();
+        return;
+    }
+    held.extend(
+        added
+            .iter()
+            .filter(|r| {
+                let project = config.project_settings(&r.instance, &r.project_path);
+                project.notify && project.desktop_notify
+            })
+            .cloned(),
+    );
+    if held.is_empty()
+        || settings
+            .quiet_hours
+            .is_some_and(|quiet| quiet.contains(Local::now().time()))
+    {
+        return;
+    }
+    let releases = std::mem::take(&mut *held);
+    drop(held);
+
+    // Where clicks on notifications are not reported (see `show_clickable`),
+    // the tray menu offers to open the release announced last
+    *state.last_notified_url.lock().await = Some(releases[0].web_url.clone());
+
+    if releases.len() > settings.batch_size {
+        show_summary(app, &releases);
+    } else {
+        for release in &releases {
+            show_release(app, release);
+        }
+    }
+}
+
+// Show a desktop notification per finished pipeline and per release deployed
+// to an environment. Unlike new releases they are not held back during quiet
+// hours but dropped, as the status is stale by then.
+pub async fn notify_status_changes<R: Runtime>(
+    app: &AppHandle<R>,
+    pipelines: &[PipelineChange],
+    deployed: &[Deployment],
+) {
+    let state = app.state::<AppState>();
+    let config = state.current_config().await;
+    let settings = &config.notifications;
+    if !settings.enabled
+        || settings
+            .quiet_hours
+            .is_some_and(|quiet| quiet.contains(Local::now().time()))
+    {
+        return;
+    }
+    let wanted = |instance: &str, project_path: &str| {
+        let project = config.project_settings(instance, project_path);
+        project.notify && project.desktop_notify
+    };
+    for change in pipelines {
+        if wanted(&change.instance, &change.project_path) {
+            let title = format!("{} {}", change.project_name, change.pipeline.ref_name);
+            show(app, &title, &pipeline_summary(change));
+        }
+    }
+    for deployment in deployed {
+        if wanted(&deployment.instance, &deployment.project_path) {
+            let title = format!("{} {}", deployment.project_name, deployment.ref_name);
+            show(app, &title, &deployment_summary(deployment));
+        }
+    }
+}
+
+fn show_release<R: Runtime>(app: &AppHandle<R>, release: &GitLabRelease) {
+    let title = format!("{} {}", release.project_name, release.tag_name);
+    let body = if release.name.is_empty() || release.name == release.tag_name {
+        "New release".to_string()
+    } else {
+        release.name.clone()
+    };
+    let url = release.web_url.clone();
+    show_clickable(app, &title, &body, move || {
+        if let Err(e) = open::that(&url) {
+            eprintln!("Failed to open URL: {}", e);
+        }
+    });
+}
+
+fn show_summary<R: Runtime>(app: &AppHandle<R>, releases: &[GitLabRelease]) {
+    let title = format!("{} new releases", releases.len());
+    let mut lines: Vec<String> = releases
+        .iter()
+        .take(SUMMARY_LINES)
+        .map(|r| format!("{} {}", r.project_name, r.tag_name))
+        .collect();
+    if releases.len() > SUMMARY_LINES {
+        lines.push(format!("and {} more", releases.len() - SUMMARY_LINES));
+    }
+    let handle = app.clone();
+    show_clickable(app, &title, &lines.join("\n"), move || {
+        show_main_window(&handle)
+    });
+}
+
+// Show a notification that runs `on_click` when it is clicked. Only the
+// freedesktop notification service (Linux) reports clicks back to the app;
+// elsewhere, or when the service cannot be reached, a plain notification is
+// shown instead.
+fn show_clickable<R: Runtime>(
+    app: &AppHandle<R>,
+    title: &str,
+    body: &str,
+    on_click: impl FnOnce() + Send + 'static,
+) {
+    #[cfg(target_os = "linux")]
+    {
+        let app = app.clone();
+        let (title, body) = (title.to_string(), body.to_string());
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = freedesktop::show(&title, &body, on_click).await {
+                eprintln!("Failed to show notification through D-Bus: {}", e);
+                show(&app, &title, &body);
+            }
+        });
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = on_click;
+        show(app, title, body);
+    }
+}
+
+fn show<R: Runtime>(app: &AppHandle<R>, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
@@ -0,0 +1,170 @@
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use gitlab_monitor_core::poller::{Poller, PollerEvent};
+
+use crate::{
+    config::{load_config, watched_config_paths},
+    digest::{queue_for_digest, send_due_digest},
+    hooks::run_release_hooks,
+    models::ReleaseChangeSet,
+    notifications::{notify_new_releases, notify_status_changes},
+    state::{notifiable_changes, AppState},
+    tray::update_tray_changes,
+    webhooks::send_webhooks,
+};
+
+// How often the configuration files are checked for modifications
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// Editors often save in several steps; give them time to finish before parsing
+const CONFIG_SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+// Contents of the configuration files as of the last (re)load. Comparing
+// contents rather than modification times keeps a save from the settings UI,
+// which reloads right away, from being picked up a second time by the watcher.
+static LOADED_CONFIG: Lazy<Mutex<Vec<Option<Vec<u8>>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Start the background refresh task, stopping the one that is already running.
+// Its events are forwarded to the window, the tray and the other integrations.
+pub async fn restart_poller<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    let mut poller = state.poller.lock().await;
+    if let Some(previous) = poller.take() {
+        previous.abort();
+    }
+    let (task, events) = Poller::new(state.inner().clone()).spawn();
+    *poller = Some(task);
+    tokio::spawn(forward_events(app.clone(), events));
+}
+
+async fn forward_events<R: Runtime>(app: AppHandle<R>, mut events: UnboundedReceiver<PollerEvent>) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    while let Some(event) = events.recv().await {
+        match event {
+            PollerEvent::Diagnostics(report) => {
+                let _ = app.emit("diagnostics", &report);
+            }
+            PollerEvent::Refreshed {
+                initial,
+                releases,
+                changes,
+            } => {
+                announce_changes(&app, &changes).await;
+                send_due_digest(&state).await;
+                let event = if initial {
+                    "releases-loaded"
+                } else {
+                    "releases-updated"
+                };
+                let _ = app.emit(event, &releases);
+            }
+        }
+    }
+}
+
+// Report the changes of a refresh (new releases, finished pipelines and
+// deployments) to the tray, the window, desktop notifications, webhooks and
+// `on_release` hooks, and queue new releases for the email digest
+pub async fn announce_changes<R: Runtime>(app: &AppHandle<R>, changes: &ReleaseChangeSet) {
+    let state = app.state::<AppState>();
+    if !changes.is_empty() {
+        let pending = state.pending_changes.lock().await;
+        let _ = update_tray_changes(app, &pending);
+        drop(pending);
+        let _ = app.emit("releases-changed", changes);
+
+        // Webhooks may be retried for a while; do not hold up the refresh
+        let config = state.current_config().await;
+        if !config.webhooks.is_empty() {
+            let config = config.clone();
+            let changes = changes.clone();
+            tokio::spawn(async move { send_webhooks(&config, &changes).await });
+        }
+        if config.email.is_some() {
+            let added = notifiable_changes(&config, changes).added;
+            queue_for_digest(&state, &added).await;
+        }
+
+        // Hooks may run for minutes, so they run in the background as well
+        let has_hooks = changes.added.iter().any(|r| {
+            config
+                .project_settings(&r.instance, &r.project_path)
+                .on_release
+                .is_some()
+        });
+        if has_hooks {
+            let app = app.clone();
+            let added = changes.added.clone();
+            let history = state.hook_history.clone();
+            let history_file = state.hook_history_file.clone();
+            tokio::spawn(async move {
+                let runs = run_release_hooks(&config, &history, &history_file, &added).await;
+                let _ = app.emit("hooks-finished", &runs);
+            });
+        }
+    }
+    notify_new_releases(app, &changes.added).await;
+    notify_status_changes(app, &changes.pipelines, &changes.deployed).await;
+}
+
+// Watch the configuration files and reload whenever one of them changes
+pub fn watch_config<R: Runtime>(app: AppHandle<R>) {
+    tokio::spawn(async move {
+        *LOADED_CONFIG.lock().unwrap() = config_contents();
+        let mut interval = tokio::time::interval(CONFIG_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if config_contents() == *LOADED_CONFIG.lock().unwrap() {
+                continue;
+            }
+
+            tokio::time::sleep(CONFIG_SETTLE_DELAY).await;
+            // Errors are shown in the window by `reload_config`
+            let _ = reload_config(&app).await;
+        }
+    });
+}
+
+fn config_contents() -> Vec<Option<Vec<u8>>> {
+    watched_config_paths()
+        .iter()
+        .map(|path| std::fs::read(path).ok())
+        .collect()
+}
+
+// Re-read the configuration and restart monitoring with it. An invalid
+// configuration is reported, returned and the current one stays in effect.
+pub async fn reload_config<R: Runtime>(app: &AppHandle<R>) -> Result<(), String> {
+    println!("Configuration changed, reloading...");
+    *LOADED_CONFIG.lock().unwrap() = config_contents();
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            let message = format!("Failed to reload configuration: {}", e);
+            eprintln!("{}", message);
+            *app.state::<AppState>().config_error.lock().await = Some(message.clone());
+            let _ = app.emit("config-error", message.clone());
+            return Err(message);
+        }
+    };
+
+    let state = app.state::<AppState>();
+    let projects: Vec<String> = config.projects.iter().map(|p| p.path.clone()).collect();
+    state
+        .project_snapshots
+        .lock()
+        .await
+        .retain(|(instance, path), _| config.project(instance, path).is_some());
+    *state.config.write().await = config;
+    *state.config_error.lock().await = None;
+
+    restart_poller(app).await;
+    println!("Configuration reloaded ({} project(s))", projects.len());
+    let _ = app.emit("config-reloaded", &projects);
+    Ok(())
+}
@@ -6,7 +6,7 @@ use tauri::{
     Manager, Runtime,
 };
 
-use crate::state::AppState;
+use crate::{models::ReleaseChangeSet, state::AppState};
 
 // Static tray icons
 static TRAY_ICON_NORMAL: Lazy<tauri::image::Image<'static>> =
@@ -25,8 +25,15 @@ fn load_png_icon(data: &[u8]) -> tauri::image::Image<'static> {
 
 pub fn create_tray_menu<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<Menu<R>> {
     let show_item = MenuItem::with_id(app, "show", "Show GitLab Releases", true, None::<&str>)?;
+    let latest_item = MenuItem::with_id(
+        app,
+        "open-latest",
+        "Open Latest Notified Release",
+        true,
+        None::<&str>,
+    )?;
     let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-    let menu = Menu::with_items(app, &[&show_item, &quit_item])?;
+    let menu = Menu::with_items(app, &[&show_item, &latest_item, &quit_item])?;
     Ok(menu)
 }
 
@@ -52,6 +59,42 @@ pub fn update_tray_icon<R: Runtime>(
     Ok(())
 }
 
+// Short human readable summary such as "2 new, 1 updated"
+pub fn change_summary(changes: &ReleaseChangeSet) -> String {
+    let mut parts = Vec::new();
+    if !changes.added.is_empty() {
+        parts.push(format!("{} new", changes.added.len()));
+    }
+    if !changes.updated.is_empty() {
+        parts.push(format!("{} updated", changes.updated.len()));
+    }
+    if !changes.removed.is_empty() {
+        parts.push(format!("{} removed", changes.removed.len()));
+    }
+    if !changes.pipelines.is_empty() {
+        parts.push(format!("{} pipeline(s) finished", changes.pipelines.len()));
+    }
+    if !changes.deployed.is_empty() {
+        parts.push(format!("{} deployed", changes.deployed.len()));
+    }
+    parts.join(", ")
+}
+
+// Reflect pending release changes in the tray icon and tooltip
+pub fn update_tray_changes<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    changes: &ReleaseChangeSet,
+) -> tauri::Result<()> {
+    update_tray_icon(app, !changes.is_empty())?;
+    if !changes.is_empty() {
+        if let Some(tray) = app.tray_by_id("gitlab-monitor-tray") {
+            let tooltip = format!("🔵 GitLab Releases Monitor - {}", change_summary(changes));
+            let _ = tray.set_tooltip(Some(tooltip));
+        }
+    }
+    Ok(())
+}
+
 // Event handlers
 pub fn handle_tray_event<R: Runtime>(app: &tauri::AppHandle<R>, event: TrayIconEvent) {
     match event {
@@ -62,17 +105,16 @@ pub fn handle_tray_event<R: Runtime>(app: &tauri::AppHandle<R>, event: TrayIconE
                     let _ = window.show();
                     let _ = window.set_focus();
 
-                    if let Some(state) = app.try_state::<AppState>() {
-                        let has_new_arc = state.has_new_releases.clone();
-                        let app_handle_clone = app.clone();
-                        tauri::async_runtime::spawn(async move {
-                            let mut has_new = has_new_arc.lock().await;
-                            if *has_new {
-                                *has_new = false;
+                    let app_handle_clone = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Some(state) = app_handle_clone.try_state::<AppState>() {
+                            let has_new = *state.has_new_releases.lock().await;
+                            if has_new {
+                                state.clear_pending_changes().await;
                                 let _ = update_tray_icon(&app_handle_clone, false);
                             }
-                        });
-                    }
+                        }
+                    });
                 }
             }
             MouseButton::Right => {}
@@ -89,17 +131,32 @@ pub fn handle_tray_event<R: Runtime>(app: &tauri::AppHandle<R>, event: TrayIconE
     }
 }
 
+pub fn show_main_window<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let windows = app.webview_windows();
+    if let Some(window) = windows.values().next() {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
 pub fn handle_tray_menu_event<R: Runtime>(
     app: &tauri::AppHandle<R>,
     event: tauri::menu::MenuEvent,
 ) {
     match event.id().as_ref() {
-        "show" => {
-            let windows = app.webview_windows();
-            if let Some(window) = windows.values().next() {
-                let _ = window.show();
-                let _ = window.set_focus();
-            }
+        "show" => show_main_window(app),
+        "open-latest" => {
+            let app_handle_clone = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Some(state) = app_handle_clone.try_state::<AppState>() {
+                    let url = state.last_notified_url.lock().await.clone();
+                    if let Some(url) = url {
+                        if let Err(e) = open::that(&url) {
+                            eprintln!("Failed to open URL: {}", e);
+                        }
+                    }
+                }
+            });
         }
         "quit" => {
             app.exit(0);
@@ -64,15 +64,20 @@ function showError(message) {
   refreshButtonEl.disabled = false;
 }
 
+// Identifies a release across instances and projects
+function releaseKey(release) {
+  return `${release.instance}:${release.project_path}-${release.tag_name}`;
+}
+
 // Show releases
 function showReleases(releasesData, markAsNew = false) {
   isLoading = false;
   
   // If markAsNew is true, detect new releases
   if (markAsNew && releases.length > 0) {
-    const currentIds = new Set(releases.map(r => `${r.project_path}-${r.tag_name}`));
+    const currentIds = new Set(releases.map(releaseKey));
     releasesData.forEach(release => {
-      const releaseId = `${release.project_path}-${release.tag_name}`;
+      const releaseId = releaseKey(release);
       if (!currentIds.has(releaseId)) {
         newReleaseIds.add(releaseId);
       }
@@ -131,13 +136,50 @@ function parseTagName(tagName) {
   }
 }
 
+// Element with a class and children; strings become text, never markup
+function element(tag, className, ...children) {
+  const el = document.createElement(tag);
+  el.className = className;
+  el.append(...children);
+  return el;
+}
+
+// Small labelled badge shown next to a release name
+function badge(className, text, title) {
+  const el = element('span', className, text);
+  el.title = title;
+  return el;
+}
+
+// Pipeline statuses GitLab reports; anything else is shown as unknown
+const PIPELINE_STATUSES = new Set([
+  'created', 'waiting_for_resource', 'preparing', 'pending', 'running',
+  'success', 'failed', 'canceled', 'skipped', 'manual', 'scheduled',
+]);
+
+function pipelineBadge(pipeline) {
+  const status = PIPELINE_STATUSES.has(pipeline.status) ? pipeline.status : 'unknown';
+  return badge(`pipeline-status pipeline-${status}`, status, `Pipeline #${pipeline.id}`);
+}
+
+// <option> elements for a datalist
+function datalistOptions(values) {
+  return values.map(value => {
+    const option = document.createElement('option');
+    option.value = value;
+    return option;
+  });
+}
+
 // Render releases in the list
 function renderReleases() {
   releasesListEl.innerHTML = '';
+  // Only name the instance when releases come from more than one
+  const multipleInstances = new Set(releases.map(r => r.instance)).size > 1;
   
   releases.forEach(release => {
     const releaseItem = document.createElement('div');
-    const releaseId = `${release.project_path}-${release.tag_name}`;
+    const releaseId = releaseKey(release);
     const isNew = newReleaseIds.has(releaseId);
     
     releaseItem.className = isNew ? 'release-item new-release' : 'release-item';
@@ -145,26 +187,33 @@ function renderReleases() {
     releaseItem.setAttribute('data-release-id', releaseId);
     
     const createdDate = new Date(release.created_at);
-    const { attribute, version } = parseTagName(release.tag_name);
+    const parsed = parseTagName(release.tag_name);
+    // Prefer the tag family computed by the backend from the project's grouping rule
+    const attribute = release.tag_family || parsed.attribute;
+    const version = parsed.version;
     
-    releaseItem.innerHTML = `
-      <div class="project-info">
-        <div class="project-name">${release.project_name}</div>
-      </div>
-      <div class="release-info">
-        <div class="release-name">${release.name || 'Unnamed Release'}</div>
-      </div>
-      <div class="attribute-info">
-        ${attribute === 'N/A' ? '<span class="attribute-na">N/A</span>' : `<span class="attribute-name">${attribute}</span>`}
-      </div>
-      <div class="version-info">
-        <span class="tag-name">${version}</span>
-      </div>
-      <div class="date-info">
-        <span class="date-time">${formatDate(release.created_at)}</span>
-        <span class="date-relative">${formatRelativeTime(createdDate)}</span>
-      </div>
-    `;
+    const projectInfo = element('div', 'project-info', element('div', 'project-name', release.project_name));
+    if (multipleInstances) {
+      projectInfo.append(element('div', 'project-instance', release.instance));
+    }
+    const releaseName = element('div', 'release-name', release.name || 'Unnamed Release');
+    if (release.pipeline) {
+      releaseName.append(pipelineBadge(release.pipeline));
+    }
+    releaseName.append(...(release.environments || [])
+      .map(env => badge('environment-badge', env, `Deployed to ${env}`)));
+
+    releaseItem.append(
+      projectInfo,
+      element('div', 'release-info', releaseName),
+      element('div', 'attribute-info', attribute === 'N/A'
+        ? element('span', 'attribute-na', 'N/A')
+        : element('span', 'attribute-name', attribute)),
+      element('div', 'version-info', element('span', 'tag-name', version)),
+      element('div', 'date-info',
+        element('span', 'date-time', formatDate(release.created_at)),
+        element('span', 'date-relative', formatRelativeTime(createdDate))),
+    );
     
     // Add hover handler to remove new release highlighting
     if (isNew) {
@@ -233,8 +282,285 @@ async function loadReleases() {
   }
 }
 
+// Describe where each instance's token comes from
+const TOKEN_SOURCES = {
+  'missing': 'no token',
+  'config-file': 'in plaintext in config.toml',
+  'environment': 'from GITLAB_API_TOKEN / .env (overrides the stored token)',
+  'secret-store': 'stored',
+};
+
+async function updateTokenStatus() {
+  const statusEl = document.querySelector("#token-status");
+  const instancesEl = document.querySelector("#token-instances");
+  try {
+    const status = await invoke("get_token_status");
+    instancesEl.replaceChildren(...datalistOptions(status.map(s => s.instance)));
+    const summary = status.map(s => `${s.instance}: ${TOKEN_SOURCES[s.source] || s.source}`).join(', ');
+    const store = status.length > 0 ? ` (saved to the ${status[0].store})` : '';
+    statusEl.textContent = summary + store;
+    const inConfigFile = status.some(s => s.source === 'config-file');
+    document.querySelector("#token-move").style.display = inConfigFile ? '' : 'none';
+  } catch (error) {
+    statusEl.textContent = `Failed to read token status: ${error}`;
+  }
+}
+
+async function toggleTokenPanel() {
+  const panelEl = document.querySelector("#token-panel");
+  const visible = panelEl.style.display !== 'none';
+  panelEl.style.display = visible ? 'none' : 'flex';
+  if (!visible) {
+    await updateTokenStatus();
+  }
+}
+
+async function saveToken() {
+  const instance = document.querySelector("#token-instance").value;
+  const tokenEl = document.querySelector("#token-value");
+  try {
+    await invoke("set_api_token", { instance, token: tokenEl.value });
+    tokenEl.value = '';
+    await updateTokenStatus();
+  } catch (error) {
+    document.querySelector("#token-status").textContent = error;
+  }
+}
+
+async function clearToken() {
+  const instance = document.querySelector("#token-instance").value;
+  try {
+    await invoke("clear_api_token", { instance });
+    await updateTokenStatus();
+  } catch (error) {
+    document.querySelector("#token-status").textContent = error;
+  }
+}
+
+async function moveTokens() {
+  try {
+    const moved = await invoke("move_tokens_to_store");
+    await updateTokenStatus();
+    const statusEl = document.querySelector("#token-status");
+    if (moved.length > 0) {
+      statusEl.textContent = `Moved the token(s) of ${moved.join(', ')} out of config.toml. ${statusEl.textContent}`;
+    }
+  } catch (error) {
+    document.querySelector("#token-status").textContent = error;
+  }
+}
+
+// Settings as stored in config.toml (tokens are never sent to the window)
+let settings = null;
+
+// Projects of all instances; those of the default instance are at the top level
+function settingsProjects() {
+  const projects = settings.projects.map(p => ({ instance: 'default', path: p.path }));
+  settings.instances.forEach(instance => {
+    instance.projects.forEach(p => projects.push({ instance: instance.name, path: p.path }));
+  });
+  return projects;
+}
+
+function setSettingsStatus(message) {
+  document.querySelector("#settings-status").textContent = message;
+}
+
+function renderSettings() {
+  document.querySelector("#settings-poll-interval").value = settings.poll_interval ?? '';
+  document.querySelector("#settings-channel").value = settings.defaults.channel ?? '';
+  document.querySelector("#settings-latest-by").value = settings.defaults.latest_by ?? '';
+  document.querySelector("#settings-tag-grouping").value = settings.defaults.tag_grouping ?? '';
+
+  const instances = ['default', ...settings.instances.map(i => i.name)];
+  document.querySelector("#token-instances").replaceChildren(...datalistOptions(instances));
+
+  document.querySelector("#settings-test-webhooks").style.display = settings.webhooks.length > 0 ? '' : 'none';
+  document.querySelector("#settings-test-digest").style.display = settings.email ? '' : 'none';
+
+  const projectsEl = document.querySelector("#settings-projects");
+  projectsEl.innerHTML = '';
+  settingsProjects().forEach(({ instance, path }) => {
+    const row = document.createElement('div');
+    row.className = 'settings-project';
+    row.textContent = settings.instances.length > 0 ? `${instance}: ${path}` : path;
+    const removeButton = document.createElement('button');
+    removeButton.className = 'token-button';
+    removeButton.textContent = 'Remove';
+    removeButton.addEventListener('click', () => removeProject(instance, path));
+    row.appendChild(removeButton);
+    projectsEl.appendChild(row);
+  });
+}
+
+async function loadSettings() {
+  try {
+    settings = await invoke("get_settings");
+    renderSettings();
+    setSettingsStatus('');
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+async function toggleSettingsPanel() {
+  const panelEl = document.querySelector("#settings-panel");
+  const visible = panelEl.style.display !== 'none';
+  panelEl.style.display = visible ? 'none' : 'flex';
+  if (!visible) {
+    await loadSettings();
+  }
+}
+
+async function saveSettings() {
+  const pollInterval = document.querySelector("#settings-poll-interval").value;
+  settings.poll_interval = pollInterval === '' ? null : Number(pollInterval);
+  settings.defaults.channel = document.querySelector("#settings-channel").value || null;
+  settings.defaults.latest_by = document.querySelector("#settings-latest-by").value || null;
+  settings.defaults.tag_grouping = document.querySelector("#settings-tag-grouping").value.trim() || null;
+  try {
+    await invoke("update_settings", { settings });
+    await loadSettings();
+    setSettingsStatus('Saved');
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+// Look the project up on GitLab first so typos are caught before saving
+async function addProject() {
+  const instance = document.querySelector("#settings-project-instance").value.trim() || 'default';
+  const pathEl = document.querySelector("#settings-project-path");
+  try {
+    setSettingsStatus('Checking project...');
+    const project = await invoke("validate_project", { instance, path: pathEl.value });
+    await invoke("add_project", { instance, path: project.path });
+    pathEl.value = '';
+    await loadSettings();
+    setSettingsStatus(`Added ${project.name}`);
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+async function removeProject(instance, path) {
+  try {
+    await invoke("remove_project", { instance, path });
+    await loadSettings();
+    setSettingsStatus(`Removed ${path}`);
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+async function testWebhooks() {
+  try {
+    setSettingsStatus('Sending test release...');
+    const count = await invoke("test_webhooks");
+    setSettingsStatus(`Test release sent to ${count} webhook(s)`);
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+async function testDigest() {
+  try {
+    setSettingsStatus('Sending test digest...');
+    await invoke("send_test_digest");
+    setSettingsStatus('Test digest sent');
+  } catch (error) {
+    setSettingsStatus(error);
+  }
+}
+
+// Result of the configuration checks
+const CHECK_ICONS = {
+  'ok': '✅',
+  'warning': '⚠️',
+  'error': '❌',
+  'skipped': '⏭',
+};
+
+function showDiagnostics(report) {
+  const listEl = document.querySelector("#diagnostics-list");
+  listEl.innerHTML = '';
+  report.checks.forEach(check => {
+    const row = document.createElement('div');
+    row.className = `diagnostic-check diagnostic-${check.status}`;
+    const where = check.instance ? `${check.instance} · ${check.subject}` : check.subject;
+    row.innerHTML = `${CHECK_ICONS[check.status] || ''} <span class="diagnostic-subject"></span>: <span class="diagnostic-message"></span>`;
+    row.querySelector('.diagnostic-subject').textContent = where;
+    row.querySelector('.diagnostic-message').textContent = check.message;
+    listEl.appendChild(row);
+  });
+  document.querySelector("#diagnostics-panel").style.display = 'flex';
+}
+
+async function runDiagnostics() {
+  const listEl = document.querySelector("#diagnostics-list");
+  listEl.textContent = 'Checking configuration...';
+  document.querySelector("#diagnostics-panel").style.display = 'flex';
+  try {
+    showDiagnostics(await invoke("run_diagnostics"));
+  } catch (error) {
+    listEl.textContent = `Failed to check configuration: ${error}`;
+  }
+}
+
+async function toggleDiagnosticsPanel() {
+  const panelEl = document.querySelector("#diagnostics-panel");
+  if (panelEl.style.display !== 'none') {
+    panelEl.style.display = 'none';
+  } else {
+    await runDiagnostics();
+  }
+}
+
+// Runs of the on_release hooks, newest first
+function showHookHistory(runs) {
+  const listEl = document.querySelector("#hooks-list");
+  listEl.innerHTML = '';
+  if (runs.length === 0) {
+    listEl.textContent = 'No hooks have run yet. Set on_release for a project in config.toml.';
+    return;
+  }
+  runs.forEach(run => {
+    const ok = run.exit_code === 0;
+    const status = run.error ?? (ok ? 'ok' : `exit code ${run.exit_code ?? '?'}`);
+    const row = document.createElement('details');
+    row.className = 'diagnostic-check';
+    row.innerHTML = `<summary>${ok ? '✅' : '❌'} <span class="diagnostic-subject"></span>: <span class="hook-status"></span></summary><pre class="hook-output"></pre>`;
+    row.querySelector('.diagnostic-subject').textContent = `${run.project_path} ${run.tag_name}`;
+    row.querySelector('.hook-status').textContent =
+      `${status} · ${new Date(run.started_at).toLocaleString()} · ${(run.duration_ms / 1000).toFixed(1)}s`;
+    row.querySelector('.hook-output').textContent =
+      [`$ ${run.command.join(' ')}`, run.stdout, run.stderr].filter(text => text).join('\n');
+    listEl.appendChild(row);
+  });
+}
+
+async function loadHookHistory() {
+  try {
+    showHookHistory(await invoke("get_hook_history"));
+  } catch (error) {
+    document.querySelector("#hooks-list").textContent = `Failed to load hook history: ${error}`;
+  }
+}
+
+async function toggleHooksPanel() {
+  const panelEl = document.querySelector("#hooks-panel");
+  const visible = panelEl.style.display !== 'none';
+  panelEl.style.display = visible ? 'none' : 'flex';
+  if (!visible) {
+    await loadHookHistory();
+  }
+}
+
 // Handle keyboard shortcuts
 function handleKeyboard(event) {
+  // Typing into a form field is not a shortcut
+  if (['INPUT', 'SELECT'].includes(event.target.tagName)) return;
+  
   // Refresh on 'R' key
   if (event.key.toLowerCase() === 'r' && !event.ctrlKey && !event.altKey && !event.metaKey) {
     event.preventDefault();
@@ -258,6 +584,18 @@ window.addEventListener("DOMContentLoaded", async () => {
   // Add event listeners
   refreshButtonEl.addEventListener("click", refreshReleases);
   retryButtonEl.addEventListener("click", refreshReleases);
+  document.querySelector("#token-button").addEventListener("click", toggleTokenPanel);
+  document.querySelector("#settings-button").addEventListener("click", toggleSettingsPanel);
+  document.querySelector("#hooks-button").addEventListener("click", toggleHooksPanel);
+  document.querySelector("#diagnostics-button").addEventListener("click", toggleDiagnosticsPanel);
+  document.querySelector("#diagnostics-rerun").addEventListener("click", runDiagnostics);
+  document.querySelector("#settings-save").addEventListener("click", saveSettings);
+  document.querySelector("#settings-project-add").addEventListener("click", addProject);
+  document.querySelector("#settings-test-webhooks").addEventListener("click", testWebhooks);
+  document.querySelector("#settings-test-digest").addEventListener("click", testDigest);
+  document.querySelector("#token-save").addEventListener("click", saveToken);
+  document.querySelector("#token-clear").addEventListener("click", clearToken);
+  document.querySelector("#token-move").addEventListener("click", moveTokens);
   
   // Add keyboard event listener
   document.addEventListener("keydown", handleKeyboard);
@@ -273,6 +611,43 @@ window.addEventListener("DOMContentLoaded", async () => {
     showReleases(event.payload, true); // Updated releases, mark new ones
   });
   
+  await listen("releases-changed", (event) => {
+    const { added, updated, removed, pipelines, deployed } = event.payload;
+    console.log(`Release changes: ${added.length} added, ${updated.length} updated, ${removed.length} removed, ${pipelines.length} pipeline(s) finished, ${deployed.length} deployed`);
+    
+    // Highlight edited releases the same way as new ones
+    added.forEach(release => newReleaseIds.add(releaseKey(release)));
+    updated.forEach(({ release }) => newReleaseIds.add(releaseKey(release)));
+    if (releases.length > 0) {
+      renderReleases();
+    }
+  });
+  
+  await listen("config-error", (event) => {
+    console.error("Configuration error:", event.payload);
+    showError(event.payload);
+  });
+
+  await listen("diagnostics", (event) => {
+    // Only interrupt the user when something is wrong
+    if (!event.payload.checks.some(check => check.status === 'error')) return;
+    showDiagnostics(event.payload);
+    if (isLoading) {
+      showError("GitLab monitoring found configuration problems, see the checks above.");
+    }
+  });
+
+  await listen("hooks-finished", async () => {
+    if (document.querySelector("#hooks-panel").style.display !== 'none') {
+      await loadHookHistory();
+    }
+  });
+
+  await listen("config-reloaded", (event) => {
+    // The restarted background task sends "releases-loaded" once it has fetched
+    console.log(`Configuration reloaded, monitoring ${event.payload.length} project(s)`);
+  });
+
   // Load initial releases
   await loadReleases();
   